@@ -1,12 +1,19 @@
 //use heck::ToSnakeCase;
-use pest::{iterators::Pair, Parser};
+use pest::{
+    error::{ErrorVariant, LineColLocation},
+    iterators::{Pair, Pairs},
+    Parser,
+};
 use pest_derive::Parser;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashSet},
     fs::File,
     io::{Read, Write},
-    path::Path,
+    ops::Range,
+    path::{Component, Path, PathBuf},
 };
 use thiserror::Error;
 
@@ -16,7 +23,7 @@ const _GRAMMAR: &str = include_str!("api.pest");
 ///
 /// Current primitive types
 ///
-const PRMITIVE_TYPES: &[&str] = &[
+pub(crate) const PRMITIVE_TYPES: &[&str] = &[
     "void", "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "bool", "f32", "f64",
 ];
 
@@ -24,6 +31,7 @@ const PRMITIVE_TYPES: &[&str] = &[
 /// Variable type
 ///
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum VariableType {
     None,
     /// Self (aka this pointer in C++ and self in Rust)
@@ -32,31 +40,34 @@ pub enum VariableType {
     Enum,
     /// Struct/other type
     Regular,
+    /// Struct with the `Handle` attribute. These are opaque and always used through a pointer
+    Handle,
     /// String type
     Str,
     /// Prmitive type (such as i32,u64,etc)
     Primitive,
+    /// Type alias declared with `type`
+    Alias,
+    /// Callback declared with `callback`
+    Callback,
 }
 
 ///
 /// Array Type
 ///
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ArrayType {
     /// Array is unsized
+    #[default]
     Unsized,
     /// Array with fixed size
     SizedArray(String),
 }
 
-impl Default for ArrayType {
-    fn default() -> ArrayType {
-        ArrayType::Unsized
-    }
-}
-
 /// Set if the type has a modifier on it (mutable pointer, const pointer or reference)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TypeModifier {
     // No modifier on the type
     None,
@@ -68,19 +79,42 @@ pub enum TypeModifier {
     Reference,
 }
 
+/// Location of an item in a def file
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    /// The def file (as given to the parser)
+    pub file: String,
+    /// Line of the name (1 based, 0 if unknown)
+    pub line: usize,
+    /// Column of the name (1 based, 0 if unknown)
+    pub column: usize,
+    /// Byte range of the whole declaration in the file (without doc comments)
+    pub byte_range: Range<usize>,
+}
+
 /// Holds the data for a variable. It's name and it's type and additional flags
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Variable {
     /// Documentation
     pub doc_comments: Vec<String>,
     /// Which def file this variable comes from
     pub def_file: String,
+    /// Namespace of the def file this variable comes from
+    pub namespace: Vec<String>,
     /// Name of the variable
     pub name: String,
     /// Type of the variable
     pub vtype: VariableType,
     /// Name of the variable type
     pub type_name: String,
+    /// Qualified filename (see `ApiDef::qualified_filename`) of the def file that defines
+    /// `type_name`. Set by the resolver and empty for primitives, strings and unresolved types
+    pub type_def_file: String,
+    /// Namespace of `type_name`. This is the qualifier as written (`render::Texture`) until the
+    /// resolver sets it to the full namespace of the type
+    pub type_namespace: Vec<String>,
     /// Name of the variable type
     pub default_value: String,
     /// Type of enum
@@ -91,6 +125,8 @@ pub struct Variable {
     pub type_modifier: TypeModifier,
     /// If variable is optional (nullable)
     pub optional: bool,
+    /// Where the variable is declared
+    pub span: Span,
 }
 
 /// Default implementation for Variable
@@ -100,13 +136,17 @@ impl Default for Variable {
             name: String::new(),
             doc_comments: Vec::new(),
             def_file: String::new(),
+            namespace: Vec::new(),
             vtype: VariableType::None,
             type_name: String::new(),
+            type_def_file: String::new(),
+            type_namespace: Vec::new(),
             enum_type: EnumType::Regular,
             default_value: String::new(),
             array: None,
             optional: false,
             type_modifier: TypeModifier::None,
+            span: Span::default(),
         }
     }
 }
@@ -115,6 +155,7 @@ impl Default for Variable {
 /// Function type
 ///
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FunctionType {
     /// This is a regular function
     Regular,
@@ -122,17 +163,39 @@ pub enum FunctionType {
     Static,
     /// Function that is manually implemented in some cases
     Manual,
+    /// Function that destroys the object (marked with `[drop]`)
+    Drop,
+}
+
+///
+/// Who owns a handle returned from a function
+///
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Ownership {
+    /// Static functions gives the caller ownership of the handle and methods returns a borrowed
+    /// handle
+    #[default]
+    Default,
+    /// Marked with `[owned]`. The caller owns the handle and has to destroy it
+    Owned,
+    /// Marked with `[borrowed]`. The handle is owned by something else and mustn't be destroyed
+    /// by the caller
+    Borrowed,
 }
 
 ///
 /// Holds the data for a function. Name, function_args, return_type, etc
 ///
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Function {
     /// Documentation
     pub doc_comments: Vec<String>,
     /// Which def file this function comes from
     pub def_file: String,
+    /// Namespace of the def file this function comes from
+    pub namespace: Vec<String>,
     /// Name of the function
     pub name: String,
     /// Function argumnts
@@ -141,6 +204,11 @@ pub struct Function {
     pub return_val: Option<Variable>,
     /// Type of function. See FunctionType descrition for more info
     pub func_type: FunctionType,
+    /// Who owns the returned handle. See `returns_owned`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ownership: Ownership,
+    /// Where the function is declared
+    pub span: Span,
 }
 
 /// Default implementation for Function
@@ -150,15 +218,19 @@ impl Default for Function {
             doc_comments: Vec::new(),
             name: String::new(),
             def_file: String::new(),
+            namespace: Vec::new(),
             function_args: Vec::new(),
             return_val: None,
             func_type: FunctionType::Regular,
+            ownership: Ownership::Default,
+            span: Span::default(),
         }
     }
 }
 
 /// Holds the data for a struct
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Struct {
     /// Docummentanion
     pub doc_comments: Vec<String>,
@@ -166,6 +238,8 @@ pub struct Struct {
     pub name: String,
     /// Which def file this struct comes from
     pub def_file: String,
+    /// Namespace of the def file this struct comes from
+    pub namespace: Vec<String>,
     /// Variables in the struct
     pub variables: Vec<Variable>,
     /// Functions for the struct
@@ -176,10 +250,13 @@ pub struct Struct {
     pub traits: Vec<String>,
     /// List of derives
     pub derives: Vec<String>,
+    /// Where the struct is declared
+    pub span: Span,
 }
 
 /// C/C++ style enum
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EnumEntry {
     /// Documentation
     pub doc_comments: Vec<String>,
@@ -187,26 +264,25 @@ pub struct EnumEntry {
     pub name: String,
     /// Value of the enum entry
     pub value: u64,
+    /// Where the entry is declared
+    pub span: Span,
 }
 
 /// Enums in C++ can have same value for different enum ids. This isn't supported in Rust.
 /// Also Rust doesn't support that your "or" enums flags so we need to handle that.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum EnumType {
     /// All values are in sequantial order and no overlap
+    #[default]
     Regular,
     /// This enum is constructed with bitflags due to being power of two or overlapping values
     Bitflags,
 }
 
-impl Default for EnumType {
-    fn default() -> EnumType {
-        EnumType::Regular
-    }
-}
-
 /// Enum type
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Enum {
     /// Documentation
     pub doc_comments: Vec<String>,
@@ -214,43 +290,78 @@ pub struct Enum {
     pub name: String,
     /// The file this enum is present in
     pub def_file: String,
+    /// Namespace of the file this enum is present in
+    pub namespace: Vec<String>,
     /// Type of enum
     pub enum_type: EnumType,
     /// Qt supports having a flags macro on enums being type checked with an extra name
     pub flags_name: String,
     /// All the enem entries
     pub entries: Vec<EnumEntry>,
+    /// Where the enum is declared
+    pub span: Span,
 }
 
 // Type type
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Type {
     /// Documentation
     pub doc_comments: Vec<String>,
     /// Variable that includes type and name
     pub var: Variable,
+    /// Where the type is declared
+    pub span: Span,
 }
 
 // Union type
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Const {
     /// Documentation
     pub doc_comments: Vec<String>,
     /// Name of the type
     pub name: String,
+    /// Namespace of the def file this const comes from
+    pub namespace: Vec<String>,
     /// Data
     pub value: String,
+    /// Where the const is declared
+    pub span: Span,
+}
+
+/// Import of another def file. `use file::Name` imports one name from `file.def` in the same
+/// directory and `import "path.def"` imports all the names in a file
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Import {
+    /// Path of the imported file relative to the directory of the importing file
+    pub path: String,
+    /// Base filename of the imported file
+    pub def_file: String,
+    /// Name imported with `use`. `None` for `import`
+    pub name: Option<String>,
+    /// Namespace of the imported file. Set by the resolver
+    pub namespace: Vec<String>,
+    /// Where the import is declared
+    pub span: Span,
 }
 
 /// Api definition for a file
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ApiDef {
     /// full filename path
     pub filename: String,
     /// Base filename (such as foo/file/some_name.def) is some_name
     pub base_filename: String,
+    /// Namespace declared with `namespace` (`render::gl` is `["render", "gl"]`). Empty for the
+    /// global namespace
+    pub namespace: Vec<String>,
     /// Mods to to be included in the file
     pub mods: Vec<String>,
+    /// Other def files this file imports from
+    pub imports: Vec<Import>,
     /// Callbacks types
     pub callbacks: Vec<Function>,
     /// Structs that only holds data
@@ -267,149 +378,658 @@ pub struct ApiDef {
 
 #[derive(Error, Debug)]
 pub enum ApigenError {
-    #[error("data store disconnected")]
-    Disconnect(#[from] std::io::Error),
-    #[error("the data for key `{0}` is not available")]
-    Redaction(String),
-    #[error("invalid header (expected {expected:?}, found {found:?})")]
-    InvalidHeader { expected: String, found: String },
-    #[error("unknown data store error")]
-    Unknown,
+    /// Failed to read or write a file
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The input doesn't match the grammar (or has an invalid value in it)
+    #[error("{filename}:{line}:{column}: {message}\n{snippet}")]
+    Parse {
+        /// File the error was found in
+        filename: String,
+        /// Line of the error (1 based)
+        line: usize,
+        /// Column of the error (1 based)
+        column: usize,
+        /// Grammar rules that was expected at the location of the error
+        expected: Vec<String>,
+        /// Description of the error
+        message: String,
+        /// The offending line with a marker pointing at the column
+        snippet: String,
+    },
+    /// An IR document (see the `ir` module) is invalid or has an unsupported version
+    #[error("invalid IR document: {0}")]
+    Ir(String),
+    /// A template (see `generators::template`) failed to load or render
+    #[error("template error: {0}")]
+    Template(String),
+    /// The language server (see the `lsp` module) failed to talk to the client or a request
+    /// couldn't be handled
+    #[error("{0}")]
+    Lsp(String),
 }
 
 pub type Result<T> = std::result::Result<T, ApigenError>;
 
 /// Checks if name is a primitive
 fn is_primitve(name: &str) -> bool {
-    PRMITIVE_TYPES.iter().any(|&type_name| type_name == name)
+    PRMITIVE_TYPES.contains(&name)
+}
+
+/// Splits up a namespace path such as `render::gl`
+pub(crate) fn split_namespace(path: &str) -> Vec<String> {
+    path.split("::")
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// C prefix for names in `namespace`. The namespace is added after the prefix, so with the
+/// prefix `FL_` the names in `render::gl` gets the prefix `FL_render_gl_`
+pub fn c_namespace_prefix(c_prefix: &str, namespace: &[String]) -> String {
+    let mut prefix = c_prefix.to_owned();
+
+    for name in namespace {
+        prefix.push_str(name);
+        prefix.push('_');
+    }
+
+    prefix
+}
+
+/// Array sizes can be a number or the name of a const. Consts gets the C prefix added
+fn get_c_array_size(size: &str, c_prefix: &str) -> String {
+    if size.starts_with(|c: char| c.is_ascii_digit()) {
+        size.to_owned()
+    } else {
+        format!("{}{}", c_prefix, size)
+    }
+}
+
+/// Pointer to const `c_type`. Pointer types (such as `const char*`) becomes `const char* const*`
+pub fn c_const_pointer(c_type: &str) -> String {
+    if c_type.ends_with('*') {
+        format!("{} const*", c_type)
+    } else {
+        format!("const {}*", c_type)
+    }
+}
+
+/// Renders the source line with a marker under the column, such as
+///
+/// ```text
+///   |
+/// 3 | struct Foo {
+///   |            ^
+/// ```
+fn render_snippet(line: usize, source_line: &str, column: usize) -> String {
+    let line_no = line.to_string();
+    let gutter = " ".repeat(line_no.len());
+    let source_line = source_line.trim_end_matches(['\r', '\n']);
+
+    format!(
+        "{gutter} |\n{line_no} | {source_line}\n{gutter} | {:>column$}",
+        "^",
+        column = column.max(1)
+    )
+}
+
+impl ApigenError {
+    /// Creates a parse error for the location of `pair` in the file
+    fn from_pair(pair: &Pair<Rule>, ctx: &ParseContext, message: String) -> ApigenError {
+        let span = ctx.span(pair);
+        let source_line = pair.as_span().start_pos().line_of();
+
+        ApigenError::Parse {
+            filename: ctx.filename.to_owned(),
+            line: span.line,
+            column: span.column,
+            expected: Vec::new(),
+            message,
+            snippet: render_snippet(span.line, source_line, span.column),
+        }
+    }
+
+    /// Moves the error `line_offset` lines down. Used when a part of `buffer` has been parsed on
+    /// its own.
+    fn with_line_offset(self, line_offset: usize, buffer: &str) -> ApigenError {
+        match self {
+            ApigenError::Parse {
+                filename,
+                line,
+                column,
+                expected,
+                message,
+                ..
+            } => {
+                let line = line + line_offset;
+                let source_line = buffer.lines().nth(line - 1).unwrap_or("");
+
+                ApigenError::Parse {
+                    filename,
+                    line,
+                    column,
+                    expected,
+                    message,
+                    snippet: render_snippet(line, source_line, column),
+                }
+            }
+            e => e,
+        }
+    }
+
+    /// Converts a grammar error from pest into a parse error
+    pub(crate) fn from_pest(err: pest::error::Error<Rule>, filename: &str) -> ApigenError {
+        let (line, column) = match err.line_col {
+            LineColLocation::Pos(pos) => pos,
+            LineColLocation::Span(start, _) => start,
+        };
+
+        let (expected, message) = match &err.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                let expected: Vec<String> = positives.iter().map(|r| format!("{:?}", r)).collect();
+                let message = if expected.is_empty() {
+                    "unexpected input".to_owned()
+                } else {
+                    format!("expected {}", expected.join(", "))
+                };
+                (expected, message)
+            }
+            ErrorVariant::CustomError { message } => (Vec::new(), message.to_owned()),
+        };
+
+        ApigenError::Parse {
+            filename: filename.to_owned(),
+            line,
+            column,
+            expected,
+            message,
+            snippet: render_snippet(line, err.line(), column),
+        }
+    }
 }
 
 #[derive(Parser)]
 #[grammar = "api.pest"]
 pub struct ApiParser;
 
+/// Info about the source that is being filled in from the parsed data
+struct ParseContext<'a> {
+    /// File being parsed
+    filename: &'a str,
+    /// Base filename that is set as `def_file`
+    def_file: &'a str,
+    /// Added to line numbers. Used when a part of a file is parsed on its own
+    line_offset: usize,
+    /// Added to byte offsets. Used when a part of a file is parsed on its own
+    byte_offset: usize,
+}
+
+/// Removes `.` and `..` from a path without touching the file system
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
+
+/// Removes the whitespace and comments that pest includes at the end of a pair
+fn trim_trivia(text: &str) -> &str {
+    let mut text = text.trim_end();
+
+    loop {
+        let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+        let mut in_string = false;
+        let mut comment = None;
+
+        for (i, c) in text[line_start..].char_indices() {
+            match c {
+                '"' => in_string = !in_string,
+                '/' if !in_string && text[line_start + i..].starts_with("//") => {
+                    comment = Some(line_start + i);
+                    break;
+                }
+                _ => (),
+            }
+        }
+
+        match comment {
+            Some(i) => text = text[..i].trim_end(),
+            None => return text,
+        }
+    }
+}
+
+impl ParseContext<'_> {
+    /// Get the location of a pair
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        let (line, column) = pair.line_col();
+        let start = pair.as_span().start() + self.byte_offset;
+        let end = start + trim_trivia(pair.as_str()).len();
+
+        Span {
+            file: self.filename.to_owned(),
+            line: line + self.line_offset,
+            column,
+            byte_range: start..end,
+        }
+    }
+
+    /// Location of the declaration `decl` with the line and column of its `name`
+    fn named_span(&self, decl: &Pair<Rule>, name: &Pair<Rule>) -> Span {
+        Span {
+            byte_range: self.span(decl).byte_range,
+            ..self.span(name)
+        }
+    }
+}
+
 /// Build struct info for a parsed API def file
 impl ApiParser {
     pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<ApiDef> {
         let mut buffer = String::new();
         let mut f = File::open(&path)?;
         f.read_to_string(&mut buffer)?;
-        Self::parse_string(&buffer, path.as_ref().to_str().unwrap())
+        Self::parse_string(&buffer, &path.as_ref().to_string_lossy())
     }
 
     pub fn parse_string(buffer: &str, filename: &str) -> Result<ApiDef> {
-        let mut api_def = ApiDef::default();
-
         let chunks = ApiParser::parse(Rule::chunk, buffer)
-            .unwrap_or_else(|e| panic!("APiParser: {} {}", filename, e));
+            .map_err(|e| ApigenError::from_pest(e, filename))?;
 
-        if let Some(base_name) = Path::new(filename).file_stem() {
-            let base_filename = base_name.to_str().unwrap();
-            api_def.filename = filename.to_owned();
-            api_def.base_filename = base_filename.to_owned();
-        }
+        Self::api_def_from_pairs(chunks, filename)
+    }
 
+    /// Builds the ApiDef from the top-level pairs of a parsed file
+    pub(crate) fn api_def_from_pairs(chunks: Pairs<Rule>, filename: &str) -> Result<ApiDef> {
+        let mut api_def = Self::empty_api_def(filename);
         let mut current_comments = Vec::new();
+        let def_file = api_def.base_filename.clone();
+        let ctx = ParseContext {
+            filename,
+            def_file: &def_file,
+            line_offset: 0,
+            byte_offset: 0,
+        };
 
         for chunk in chunks {
-            match chunk.as_rule() {
-                Rule::structdef => {
-                    let sdef = Self::fill_struct(chunk, &current_comments, &api_def.base_filename);
-                    current_comments.clear();
+            Self::fill_item(&mut api_def, chunk, &mut current_comments, &ctx)?;
+        }
+
+        Self::set_namespace(&mut api_def);
+
+        Ok(api_def)
+    }
+
+    /// Parse a file and keep going on errors. See `parse_string_with_recovery`
+    pub fn parse_file_with_recovery<P: AsRef<Path>>(path: P) -> Result<(ApiDef, Vec<ApigenError>)> {
+        let mut buffer = String::new();
+        let mut f = File::open(&path)?;
+        f.read_to_string(&mut buffer)?;
+        Ok(Self::parse_string_with_recovery(
+            &buffer,
+            &path.as_ref().to_string_lossy(),
+        ))
+    }
 
-                    // If we have some variables in the struct we push it to pod_struct
-                    api_def.structs.push(sdef);
+    /// Parse a string and keep going on errors. If the buffer fails to parse it's split up into
+    /// top-level items (struct, enum, union, callback, type, const, mod) that are parsed one by
+    /// one. Items that parse fine ends up in the returned ApiDef and all the errors are returned
+    /// as a list in the order they appear in the file.
+    pub fn parse_string_with_recovery(buffer: &str, filename: &str) -> (ApiDef, Vec<ApigenError>) {
+        let mut api_def = Self::empty_api_def(filename);
+        let mut current_comments = Vec::new();
+        let mut errors = Vec::new();
+        let def_file = api_def.base_filename.clone();
+
+        // Fast path: the whole file is fine
+        if let Ok(chunks) = ApiParser::parse(Rule::chunk, buffer) {
+            let ctx = ParseContext {
+                filename,
+                def_file: &def_file,
+                line_offset: 0,
+                byte_offset: 0,
+            };
+
+            for chunk in chunks {
+                if let Err(e) = Self::fill_item(&mut api_def, chunk, &mut current_comments, &ctx) {
+                    errors.push(e);
                 }
+            }
+
+            Self::set_namespace(&mut api_def);
+
+            return (api_def, errors);
+        }
+
+        let boundaries = Self::item_boundaries(buffer);
+
+        for (i, &(offset, line_offset)) in boundaries.iter().enumerate() {
+            let end = boundaries.get(i + 1).map_or(buffer.len(), |b| b.0);
+            let segment = &buffer[offset..end];
 
-                Rule::callbackdef => {
-                    let mut func = Self::fill_callback(chunk, &current_comments);
-                    func.func_type = FunctionType::Static;
-                    api_def.callbacks.push(func);
+            if Self::is_trivia(segment) {
+                continue;
+            }
+
+            let ctx = ParseContext {
+                filename,
+                def_file: &def_file,
+                line_offset,
+                byte_offset: offset,
+            };
+
+            match ApiParser::parse(Rule::chunk, segment) {
+                Ok(chunks) => {
+                    for chunk in chunks {
+                        if let Err(e) =
+                            Self::fill_item(&mut api_def, chunk, &mut current_comments, &ctx)
+                        {
+                            errors.push(e);
+                        }
+                    }
+                }
+
+                Err(e) => {
                     current_comments.clear();
+                    errors.push(
+                        ApigenError::from_pest(e, filename).with_line_offset(line_offset, buffer),
+                    )
                 }
+            }
+        }
 
-                Rule::moddef => {
-                    for entry in chunk.into_inner() {
-                        if entry.as_rule() == Rule::name {
-                            api_def.mods.push(entry.as_str().to_owned())
-                        }
+        Self::set_namespace(&mut api_def);
+
+        (api_def, errors)
+    }
+
+    /// The namespace is declared for the whole file so it's set on all the elements once the file
+    /// has been parsed
+    pub(crate) fn set_namespace(api_def: &mut ApiDef) {
+        let namespace = api_def.namespace.clone();
+
+        for s in api_def.structs.iter_mut().chain(api_def.unions.iter_mut()) {
+            s.namespace = namespace.clone();
+
+            for func in &mut s.functions {
+                func.namespace = namespace.clone();
+            }
+        }
+
+        for func in &mut api_def.callbacks {
+            func.namespace = namespace.clone();
+        }
+
+        for e in &mut api_def.enums {
+            e.namespace = namespace.clone();
+        }
+
+        for c in &mut api_def.consts {
+            c.namespace = namespace.clone();
+        }
+
+        api_def.visit_variables_mut(|var| var.namespace = namespace.clone());
+    }
+
+    /// Creates an empty ApiDef with the filenames setup
+    fn empty_api_def(filename: &str) -> ApiDef {
+        let mut api_def = ApiDef::default();
+
+        if let Some(base_name) = Path::new(filename).file_stem() {
+            api_def.filename = filename.to_owned();
+            api_def.base_filename = base_name.to_string_lossy().into_owned();
+        }
+
+        api_def
+    }
+
+    /// Returns the byte offset and line offset of where each top-level item starts. Items are
+    /// expected to start at the first column with a keyword and doc comments and attributes
+    /// directly before the keyword are included in the item. Anything before the first item is
+    /// returned as an item as well.
+    fn item_boundaries(buffer: &str) -> Vec<(usize, usize)> {
+        const KEYWORDS: &[&str] = &[
+            "struct",
+            "enum",
+            "union",
+            "callback",
+            "type",
+            "const",
+            "mod",
+            "namespace",
+            "use",
+            "import",
+        ];
+
+        let mut boundaries = vec![(0, 0)];
+        let mut offset = 0;
+        let mut prev_was_prefix = false;
+
+        for (line_no, line) in buffer.split_inclusive('\n').enumerate() {
+            let is_prefix =
+                line.starts_with("///") || line.starts_with("#[") || line.starts_with('[');
+            let is_keyword = KEYWORDS.iter().any(|k| {
+                line.strip_prefix(k)
+                    .is_some_and(|rest| rest.starts_with(char::is_whitespace))
+            });
+
+            if (is_prefix || is_keyword) && !prev_was_prefix && offset != 0 {
+                boundaries.push((offset, line_no));
+            }
+
+            prev_was_prefix = is_prefix;
+            offset += line.len();
+        }
+
+        boundaries
+    }
+
+    /// Checks if a piece of text only contains whitespace and regular comments
+    fn is_trivia(text: &str) -> bool {
+        text.lines().all(|l| {
+            let l = l.trim();
+            l.is_empty() || (l.starts_with("//") && !l.starts_with("///"))
+        })
+    }
+
+    /// Fill a top-level item into the api_def. Doc comments are collected into
+    /// `current_comments` until the next item that uses them.
+    fn fill_item(
+        api_def: &mut ApiDef,
+        chunk: Pair<Rule>,
+        current_comments: &mut Vec<String>,
+        ctx: &ParseContext,
+    ) -> Result<()> {
+        let base_filename = api_def.base_filename.clone();
+
+        match chunk.as_rule() {
+            Rule::structdef => {
+                let sdef = Self::fill_struct(chunk, current_comments, &base_filename, ctx);
+                current_comments.clear();
+
+                // If we have some variables in the struct we push it to pod_struct
+                api_def.structs.push(sdef);
+            }
+
+            Rule::callbackdef => {
+                let mut func = Self::fill_callback(chunk, current_comments, ctx);
+                func.func_type = FunctionType::Static;
+                api_def.callbacks.push(func);
+                current_comments.clear();
+            }
+
+            Rule::moddef => {
+                for entry in chunk.into_inner() {
+                    if entry.as_rule() == Rule::name {
+                        api_def.mods.push(entry.as_str().to_owned())
                     }
                 }
+            }
+
+            Rule::namespacedef => {
+                if !api_def.namespace.is_empty() {
+                    return Err(ApigenError::from_pair(
+                        &chunk,
+                        ctx,
+                        format!(
+                            "namespace is already declared as `{}`",
+                            api_def.namespace.join("::")
+                        ),
+                    ));
+                }
 
-                Rule::type_value => {
-                    let mut type_value = Type::default();
+                for entry in chunk.into_inner() {
+                    if entry.as_rule() == Rule::name_with_namespace {
+                        api_def.namespace = split_namespace(entry.as_str());
+                    }
+                }
+            }
 
-                    for entry in chunk.into_inner() {
-                        if entry.as_rule() == Rule::var {
-                            type_value.var = Self::get_variable(entry, &current_comments);
+            Rule::usedef => {
+                let mut import = Import {
+                    span: ctx.span(&chunk),
+                    ..Default::default()
+                };
+
+                for entry in chunk.into_inner() {
+                    match entry.as_rule() {
+                        Rule::name => {
+                            import.path = format!("{}.def", entry.as_str());
+                            import.def_file = entry.as_str().to_owned();
                         }
+                        Rule::vtype => import.name = Some(entry.as_str().to_owned()),
+                        _ => (),
                     }
+                }
+
+                api_def.imports.push(import);
+            }
 
-                    api_def.types.push(type_value);
+            Rule::importdef => {
+                let path = chunk
+                    .clone()
+                    .into_inner()
+                    .flat_map(|e| e.into_inner())
+                    .find(|e| e.as_rule() == Rule::raw_string_interior)
+                    .map_or("", |e| e.as_str());
+
+                let def_file = Path::new(path)
+                    .file_stem()
+                    .map_or("", |s| s.to_str().unwrap_or(""));
+
+                api_def.imports.push(Import {
+                    path: path.to_owned(),
+                    def_file: def_file.to_owned(),
+                    name: None,
+                    namespace: Vec::new(),
+                    span: ctx.span(&chunk),
+                });
+            }
 
-                    current_comments.clear();
+            Rule::type_value => {
+                let mut type_value = Type {
+                    doc_comments: current_comments.to_owned(),
+                    ..Default::default()
+                };
+
+                for entry in chunk.clone().into_inner() {
+                    if entry.as_rule() == Rule::var {
+                        type_value.span = ctx.named_span(&chunk, &entry);
+                        type_value.var = Self::get_variable(entry, current_comments, ctx);
+                    }
                 }
 
-                Rule::const_value => {
-                    let mut const_value = Const::default();
+                api_def.types.push(type_value);
 
-                    for entry in chunk.into_inner() {
-                        match entry.as_rule() {
-                            Rule::name => const_value.name = entry.as_str().to_owned(),
-                            Rule::name_or_num => const_value.value = entry.as_str().to_owned(),
-                            Rule::raw_string => const_value.value = entry.as_str().to_owned(),
-                            _ => (),
+                current_comments.clear();
+            }
+
+            Rule::const_value => {
+                let mut const_value = Const {
+                    doc_comments: current_comments.to_owned(),
+                    ..Default::default()
+                };
+                current_comments.clear();
+
+                for entry in chunk.clone().into_inner() {
+                    match entry.as_rule() {
+                        Rule::name => {
+                            const_value.name = entry.as_str().to_owned();
+                            const_value.span = ctx.named_span(&chunk, &entry);
                         }
+                        Rule::name_or_num => const_value.value = entry.as_str().to_owned(),
+                        Rule::raw_string => const_value.value = entry.as_str().to_owned(),
+                        _ => (),
                     }
-
-                    api_def.consts.push(const_value);
                 }
 
-                Rule::doc_comment => {
-                    current_comments.push(chunk.as_str()[4..].to_owned());
-                }
+                api_def.consts.push(const_value);
+            }
 
-                Rule::enumdef => {
-                    let mut enum_def = Enum {
-                        def_file: "".to_owned(), // TODO: fixme
-                        doc_comments: current_comments.to_owned(),
-                        ..Default::default()
-                    };
-                    current_comments.clear();
+            Rule::doc_comment => {
+                current_comments.push(Self::doc_comment_text(chunk.as_str()));
+            }
 
-                    for entry in chunk.into_inner() {
-                        match entry.as_rule() {
-                            Rule::name => enum_def.name = entry.as_str().to_owned(),
-                            Rule::fieldlist => enum_def.entries = Self::fill_field_list_enum(entry),
-                            Rule::enum_flags => {
-                                enum_def.flags_name = entry
-                                    .into_inner()
-                                    .next()
-                                    .map(|e| e.as_str())
-                                    .unwrap()
-                                    .to_owned();
-                            }
-                            _ => (),
+            Rule::enumdef => {
+                let mut enum_def = Enum {
+                    def_file: base_filename,
+                    doc_comments: current_comments.to_owned(),
+                    ..Default::default()
+                };
+                current_comments.clear();
+
+                for entry in chunk.clone().into_inner() {
+                    match entry.as_rule() {
+                        Rule::name => {
+                            enum_def.name = entry.as_str().to_owned();
+                            enum_def.span = ctx.named_span(&chunk, &entry);
+                        }
+                        Rule::fieldlist => {
+                            enum_def.entries = Self::fill_field_list_enum(entry, ctx)?
                         }
+                        Rule::enum_flags => {
+                            enum_def.flags_name = entry
+                                .into_inner()
+                                .next()
+                                .map(|e| e.as_str())
+                                .unwrap()
+                                .to_owned();
+                        }
+                        _ => (),
                     }
-
-                    // Figure out enum type
-                    enum_def.enum_type = Self::determine_enum_type(&enum_def);
-                    api_def.enums.push(enum_def);
                 }
 
-                Rule::uniondef => {
-                    let union_def =
-                        Self::fill_struct(chunk, &current_comments, &api_def.base_filename);
-                    current_comments.clear();
-                    api_def.unions.push(union_def);
-                }
+                // Figure out enum type
+                enum_def.enum_type = Self::determine_enum_type(&enum_def);
+                api_def.enums.push(enum_def);
+            }
 
-                _ => (),
+            Rule::uniondef => {
+                let union_def = Self::fill_struct(chunk, current_comments, &base_filename, ctx);
+                current_comments.clear();
+                api_def.unions.push(union_def);
             }
+
+            _ => (),
         }
+        Ok(())
+    }
 
-        Ok(api_def)
+    /// Text of a doc comment without `///` and the space after it
+    fn doc_comment_text(comment: &str) -> String {
+        let text = comment.trim_start();
+        let text = text.strip_prefix("///").unwrap_or(text);
+        text.strip_prefix(' ').unwrap_or(text).to_owned()
     }
 
     /// Check if the enum values are in a single sequnce
@@ -418,17 +1038,13 @@ impl ApiParser {
             return false;
         }
 
-        let mut current = enum_def.entries[0].value;
+        let first = enum_def.entries[0].value;
 
-        for e in &enum_def.entries {
-            if current != e.value {
-                return false;
-            }
-
-            current += 1;
-        }
-
-        true
+        enum_def
+            .entries
+            .iter()
+            .zip(first..)
+            .all(|(e, current)| e.value == current)
     }
 
     /// Check if the enum values overlaps
@@ -491,34 +1107,49 @@ impl ApiParser {
         }
     }
 
-    fn fill_callback(chunk: Pair<Rule>, doc_comments: &[String]) -> Function {
+    fn fill_callback(chunk: Pair<Rule>, doc_comments: &[String], ctx: &ParseContext) -> Function {
         let mut func = Function::default();
 
-        for entry in chunk.into_inner() {
+        for entry in chunk.clone().into_inner() {
             if entry.as_rule() == Rule::function {
-                func = Self::get_function(entry, doc_comments);
+                func = Self::get_function(entry, doc_comments, ctx);
             }
         }
 
+        // The declaration includes the `callback` keyword
+        func.span.byte_range = ctx.span(&chunk).byte_range;
+
+        // callbacks are free functions so they don't get a self argument
+        func.function_args
+            .retain(|arg| arg.vtype != VariableType::SelfType);
+
         func
     }
 
     /// Fill struct def
-    fn fill_struct(chunk: Pair<Rule>, doc_comments: &[String], def_file: &str) -> Struct {
+    fn fill_struct(
+        chunk: Pair<Rule>,
+        doc_comments: &[String],
+        def_file: &str,
+        ctx: &ParseContext,
+    ) -> Struct {
         let mut sdef = Struct {
             doc_comments: doc_comments.to_owned(),
             def_file: def_file.to_owned(),
             ..Default::default()
         };
 
-        for entry in chunk.into_inner() {
+        for entry in chunk.clone().into_inner() {
             match entry.as_rule() {
-                Rule::name => sdef.name = entry.as_str().to_owned(),
+                Rule::name => {
+                    sdef.name = entry.as_str().to_owned();
+                    sdef.span = ctx.named_span(&chunk, &entry);
+                }
                 Rule::attributes => sdef.attributes = Self::get_attrbutes(entry),
                 Rule::derive => sdef.derives = Self::get_attrbutes(entry),
                 Rule::traits => sdef.traits = Self::get_attrbutes(entry),
                 Rule::fieldlist => {
-                    let (var_entries, func_entries) = Self::fill_field_list(entry);
+                    let (var_entries, func_entries) = Self::fill_field_list(entry, ctx);
                     sdef.variables = var_entries;
                     sdef.functions = func_entries;
                 }
@@ -549,7 +1180,7 @@ impl ApiParser {
 
     /// Fill the entries in a struct
     /// Returns tuple with two ararys for variables and functions
-    fn fill_field_list(rule: Pair<Rule>) -> (Vec<Variable>, Vec<Function>) {
+    fn fill_field_list(rule: Pair<Rule>, ctx: &ParseContext) -> (Vec<Variable>, Vec<Function>) {
         let mut var_entries = Vec::new();
         let mut func_entries = Vec::new();
         let mut doc_comments = Vec::new();
@@ -561,11 +1192,11 @@ impl ApiParser {
 
                     match field.as_rule() {
                         Rule::var => {
-                            var_entries.push(Self::get_variable(field, &doc_comments));
+                            var_entries.push(Self::get_variable(field, &doc_comments, ctx));
                             doc_comments.clear();
                         }
                         Rule::function => {
-                            func_entries.push(Self::get_function(field, &doc_comments));
+                            func_entries.push(Self::get_function(field, &doc_comments, ctx));
                             doc_comments.clear();
                         }
                         _ => (),
@@ -573,9 +1204,7 @@ impl ApiParser {
                 }
 
                 Rule::doc_comment => {
-                    if entry.as_str().len() >= 4 {
-                        doc_comments.push(entry.as_str()[4..].to_owned());
-                    }
+                    doc_comments.push(Self::doc_comment_text(entry.as_str()));
                 }
 
                 _ => (),
@@ -588,21 +1217,28 @@ impl ApiParser {
     ///
     /// Get data for function declaration
     ///
-    fn get_function(rule: Pair<Rule>, doc_comments: &[String]) -> Function {
+    fn get_function(rule: Pair<Rule>, doc_comments: &[String], ctx: &ParseContext) -> Function {
         let mut is_static_func = false;
         let mut function = Function {
             doc_comments: doc_comments.to_owned(),
+            def_file: ctx.def_file.to_owned(),
             ..Function::default()
         };
 
-        for entry in rule.into_inner() {
+        for entry in rule.clone().into_inner() {
             match entry.as_rule() {
-                Rule::name => function.name = entry.as_str().to_owned(),
+                Rule::name => {
+                    function.name = entry.as_str().to_owned();
+                    function.span = ctx.named_span(&rule, &entry);
+                }
                 Rule::manual_typ => function.func_type = FunctionType::Manual,
+                Rule::drop_typ => function.func_type = FunctionType::Drop,
+                Rule::owned_typ => function.ownership = Ownership::Owned,
+                Rule::borrowed_typ => function.ownership = Ownership::Borrowed,
                 Rule::varlist => {
-                    function.function_args = Self::get_variable_list(entry, is_static_func)
+                    function.function_args = Self::get_variable_list(entry, is_static_func, ctx)
                 }
-                Rule::retexp => function.return_val = Some(Self::get_variable(entry, &Vec::new())),
+                Rule::retexp => function.return_val = Some(Self::get_variable(entry, &[], ctx)),
                 Rule::static_typ => {
                     function.func_type = FunctionType::Static;
                     is_static_func = true;
@@ -611,16 +1247,31 @@ impl ApiParser {
             }
         }
 
+        // Functions without arguments still needs self if they aren't static
+        if !is_static_func && function.function_args.is_empty() {
+            function.function_args.push(Variable {
+                name: "self".to_owned(),
+                def_file: ctx.def_file.to_owned(),
+                vtype: VariableType::SelfType,
+                ..Variable::default()
+            });
+        }
+
         function
     }
 
     ///
     /// Gather variable list
     ///
-    fn get_variable_list(rule: Pair<Rule>, is_static_func: bool) -> Vec<Variable> {
+    fn get_variable_list(
+        rule: Pair<Rule>,
+        is_static_func: bool,
+        ctx: &ParseContext,
+    ) -> Vec<Variable> {
         let mut variables = if !is_static_func {
             vec![Variable {
                 name: "self".to_owned(),
+                def_file: ctx.def_file.to_owned(),
                 vtype: VariableType::SelfType,
                 ..Variable::default()
             }]
@@ -631,7 +1282,7 @@ impl ApiParser {
         let t = Vec::new();
 
         for entry in rule.into_inner() {
-            variables.push(Self::get_variable(entry, &t));
+            variables.push(Self::get_variable(entry, &t, ctx));
         }
 
         variables
@@ -660,9 +1311,13 @@ impl ApiParser {
     ///
     /// Get variable
     ///
-    fn get_variable(rule: Pair<Rule>, doc_comments: &[String]) -> Variable {
+    fn get_variable(rule: Pair<Rule>, doc_comments: &[String], ctx: &ParseContext) -> Variable {
         let mut vtype = Rule::var;
-        let mut var = Variable::default();
+        let mut var = Variable {
+            def_file: ctx.def_file.to_owned(),
+            span: ctx.span(&rule),
+            ..Variable::default()
+        };
         let mut type_name = String::new();
 
         var.doc_comments = doc_comments.to_owned();
@@ -686,6 +1341,7 @@ impl ApiParser {
                             Rule::refexp => vtype = Rule::refexp,
                             Rule::pointer_exp => vtype = Rule::pointer_exp,
                             Rule::const_ptr_exp => vtype = Rule::const_ptr_exp,
+                            Rule::optional => var.optional = true,
                             Rule::array_size => {
                                 var.array = Some(ArrayType::SizedArray(
                                     entry.into_inner().as_str().to_owned(),
@@ -700,10 +1356,6 @@ impl ApiParser {
             }
         }
 
-        if !var.default_value.is_empty() {
-            dbg!(&var.default_value);
-        }
-
         // match up with the correct type
         let var_type = if type_name == "String" {
             VariableType::Str
@@ -715,18 +1367,26 @@ impl ApiParser {
 
         match vtype {
             Rule::pointer_exp => var.type_modifier = TypeModifier::MutPointer,
-            Rule::const_ptr_exp => var.type_modifier = TypeModifier::MutPointer,
+            Rule::const_ptr_exp => var.type_modifier = TypeModifier::ConstPointer,
             Rule::refexp => var.type_modifier = TypeModifier::Reference,
             _ => (),
         }
 
-        var.type_name = type_name;
+        // Qualified names (`render::Texture`) are split up into the namespace and the name
+        match type_name.rsplit_once("::") {
+            Some((namespace, name)) => {
+                var.type_namespace = split_namespace(namespace);
+                var.type_name = name.to_owned();
+            }
+            None => var.type_name = type_name,
+        }
+
         var.vtype = var_type;
         var
     }
 
     /// Get array of enums
-    fn fill_field_list_enum(rule: Pair<Rule>) -> Vec<EnumEntry> {
+    fn fill_field_list_enum(rule: Pair<Rule>, ctx: &ParseContext) -> Result<Vec<EnumEntry>> {
         let mut entries = Vec::new();
         let mut doc_comments = Vec::new();
 
@@ -736,13 +1396,13 @@ impl ApiParser {
                     let field = entry.clone().into_inner().next().unwrap();
 
                     if field.as_rule() == Rule::enum_type {
-                        entries.push(Self::get_enum(&doc_comments, field));
+                        entries.push(Self::get_enum(&doc_comments, field, ctx)?);
                         doc_comments.clear();
                     }
                 }
 
                 Rule::doc_comment => {
-                    doc_comments.push(entry.as_str()[4..].to_owned());
+                    doc_comments.push(Self::doc_comment_text(entry.as_str()));
                 }
 
                 _ => (),
@@ -760,37 +1420,54 @@ impl ApiParser {
             }
         }
 
-        entries
+        Ok(entries)
     }
 
     /// Get enum
-    fn get_enum(doc_comments: &[String], rule: Pair<Rule>) -> EnumEntry {
+    fn get_enum(
+        doc_comments: &[String],
+        rule: Pair<Rule>,
+        ctx: &ParseContext,
+    ) -> Result<EnumEntry> {
         let mut name = String::new();
+        let mut span = Span::default();
         let mut assign = None;
 
-        for entry in rule.into_inner() {
+        for entry in rule.clone().into_inner() {
             match entry.as_rule() {
-                Rule::name => name = entry.as_str().to_owned(),
+                Rule::name => {
+                    name = entry.as_str().to_owned();
+                    span = ctx.named_span(&rule, &entry);
+                }
                 Rule::enum_assign => {
-                    assign = Some(Self::get_enum_assign(entry).parse::<u64>().unwrap())
+                    let value = Self::get_enum_assign(entry.clone());
+                    let parsed = match value.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16),
+                        None => value.parse::<u64>(),
+                    };
+
+                    match parsed {
+                        Ok(v) => assign = Some(v),
+                        Err(_) => {
+                            return Err(ApigenError::from_pair(
+                                &entry,
+                                ctx,
+                                format!("invalid value `{}` for enum entry `{}`", value, name),
+                            ))
+                        }
+                    }
                 }
                 _ => (),
             }
         }
 
-        if let Some(value) = assign {
-            EnumEntry {
-                doc_comments: doc_comments.to_owned(),
-                name,
-                value,
-            }
-        } else {
-            EnumEntry {
-                doc_comments: doc_comments.to_owned(),
-                name,
-                value: u64::MAX, // TODO: Reassigned at patchup
-            }
-        }
+        Ok(EnumEntry {
+            doc_comments: doc_comments.to_owned(),
+            name,
+            // Entries without a value are given one after all entries has been parsed
+            value: assign.unwrap_or(u64::MAX),
+            span,
+        })
     }
 
     ///
@@ -809,51 +1486,97 @@ impl ApiParser {
         name_or_num
     }
 
+    /// Resolves the types of all variables. See `resolver::resolve` for details
     pub fn second_pass(api_defs: &mut [ApiDef]) {
-        // TODO: Investigate if we actually need this pass
-        // Build a hash map of all type and their types
-        // and we also build two hashmaps for all types and which modules they belong into
-        // and they are separate for structs and enums
-        let mut type_def_file = HashMap::new();
-        let mut enum_def_file_type = HashMap::new();
-        let mut empty_structs = HashSet::new();
-
-        for api_def in api_defs.iter() {
-            api_def.structs.iter().for_each(|s| {
-                if s.variables.is_empty() && !s.has_attribute("Handle") {
-                    empty_structs.insert(s.name.to_owned());
-                }
-                type_def_file.insert(s.name.to_owned(), s.def_file.to_owned());
-                type_def_file.insert(format!("{}Trait", s.name), s.def_file.to_owned());
-            });
+        crate::resolver::resolve(api_defs);
+    }
+}
 
-            api_def.enums.iter().for_each(|e| {
-                enum_def_file_type.insert(e.name.to_owned(), (e.def_file.to_owned(), e.enum_type));
+impl ApiDef {
+    /// Calls `f` for every variable in the file. This includes struct and union fields, function
+    /// arguments and return values, callbacks and type aliases.
+    pub fn visit_variables<F: FnMut(&Variable)>(&self, mut f: F) {
+        let functions = self
+            .structs
+            .iter()
+            .chain(self.unions.iter())
+            .flat_map(|s| s.functions.iter())
+            .chain(self.callbacks.iter());
 
-                if !e.flags_name.is_empty() {
-                    enum_def_file_type.insert(
-                        e.flags_name.to_owned(),
-                        (e.def_file.to_owned(), EnumType::Bitflags),
-                    );
-                }
-            });
+        for s in self.structs.iter().chain(self.unions.iter()) {
+            s.variables.iter().for_each(&mut f);
         }
 
-        for api_def in api_defs.iter_mut() {
-            for s in &mut api_def.structs {
-                for func in &mut s.functions {
-                    for arg in &mut func.function_args {
-                        if enum_def_file_type.contains_key(&arg.type_name) {
-                            arg.vtype = VariableType::Enum;
-                        }
-                    }
-                }
+        for func in functions {
+            func.function_args.iter().for_each(&mut f);
+            func.return_val.iter().for_each(&mut f);
+        }
+
+        for t in &self.types {
+            f(&t.var);
+        }
+    }
+
+    /// Mutable version of `visit_variables`
+    pub fn visit_variables_mut<F: FnMut(&mut Variable)>(&mut self, mut f: F) {
+        for s in self.structs.iter_mut().chain(self.unions.iter_mut()) {
+            s.variables.iter_mut().for_each(&mut f);
+
+            for func in &mut s.functions {
+                func.function_args.iter_mut().for_each(&mut f);
+                func.return_val.iter_mut().for_each(&mut f);
             }
         }
+
+        for func in &mut self.callbacks {
+            func.function_args.iter_mut().for_each(&mut f);
+            func.return_val.iter_mut().for_each(&mut f);
+        }
+
+        for t in &mut self.types {
+            f(&mut t.var);
+        }
+    }
+
+    /// Path of the file that `import` refers to. Paths are relative to the directory of this file
+    /// and `.` and `..` are removed so they can be compared with the `filename` of other files.
+    pub fn import_path(&self, import: &Import) -> PathBuf {
+        let dir = Path::new(&self.filename).parent().unwrap_or(Path::new(""));
+        normalize_path(&dir.join(&import.path))
+    }
+
+    /// Index of the file in `api_defs` that `import` refers to
+    pub fn find_import(&self, import: &Import, api_defs: &[ApiDef]) -> Option<usize> {
+        let path = self.import_path(import);
+
+        api_defs
+            .iter()
+            .position(|a| normalize_path(Path::new(&a.filename)) == path)
+    }
+
+    /// Qualified filenames (see `qualified_filename`) of the files in `api_defs` this file
+    /// imports
+    pub fn imported_files(&self, api_defs: &[ApiDef]) -> BTreeSet<String> {
+        self.imports
+            .iter()
+            .filter_map(|import| self.find_import(import, api_defs))
+            .map(|index| api_defs[index].qualified_filename())
+            .filter(|name| *name != self.qualified_filename())
+            .collect()
+    }
+
+    /// Base filename with the namespace in front (`render_gl_texture` for `texture.def` in
+    /// `render::gl`) so files with the same name in different namespaces can be told apart. This
+    /// is what `Variable::type_def_file` refers to and what the generated files are named after
+    pub fn qualified_filename(&self) -> String {
+        self.namespace
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(self.base_filename.as_str()))
+            .collect::<Vec<_>>()
+            .join("_")
     }
-}
 
-impl ApiDef {
     // Generates the constast _C_MANUAL data to output and patches {CPrefix} with c_prefix input
     pub fn write_c_manual<W: Write>(&self, out: &mut W, c_prefix: &str) -> Result<()> {
         for c in &self.consts {
@@ -875,6 +1598,35 @@ impl Struct {
     pub fn has_attribute(&self, attrib: &str) -> bool {
         self.attributes.iter().any(|s| s == attrib)
     }
+
+    /// Function that destroys the object. `[drop]` or `destroy` if the struct has `Drop`
+    pub fn get_drop_function(&self) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|f| f.is_type_drop())
+            .or_else(|| {
+                self.functions.iter().find(|f| {
+                    self.has_attribute("Drop") && f.name == "destroy" && !f.is_type_static()
+                })
+            })
+    }
+
+    /// Checks if `func` is the function returned by [`Struct::get_drop_function`]
+    pub fn is_drop_function(&self, func: &Function) -> bool {
+        self.get_drop_function()
+            .is_some_and(|f| std::ptr::eq(f, func))
+    }
+}
+
+/// Helper functions for enum
+impl Enum {
+    /// Checks if any of the values doesn't fit in 32 bits. Generators use a 64 bit type for these
+    /// enums (and their flags type) instead of a 32 bit one
+    pub fn is_64bit(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.value > u64::from(u32::MAX))
+    }
 }
 
 /// Helper functions for function
@@ -886,6 +1638,20 @@ impl Function {
             .collect()
     }
 
+    /// Checks if the caller owns a handle returned by this function and has to destroy it. Static
+    /// functions returns owned handles and methods borrowed ones unless the function is marked
+    /// with `[owned]` or `[borrowed]`
+    pub fn returns_owned(&self) -> bool {
+        match self.ownership {
+            Ownership::Owned => true,
+            Ownership::Borrowed => false,
+            Ownership::Default => !self
+                .function_args
+                .first()
+                .is_some_and(|arg| arg.vtype == VariableType::SelfType),
+        }
+    }
+
     pub fn is_type_manual_static(&self) -> bool {
         self.func_type == FunctionType::Static || self.func_type == FunctionType::Manual
     }
@@ -898,6 +1664,10 @@ impl Function {
         self.func_type == FunctionType::Static
     }
 
+    pub fn is_type_drop(&self) -> bool {
+        self.func_type == FunctionType::Drop
+    }
+
     // Returns a list of funuction arguments for C function
     pub fn get_c_separated_arguments(&self, self_name: &str, c_prefix: &str) -> Vec<String> {
         let mut args = Vec::with_capacity(self.function_args.len());
@@ -943,6 +1713,60 @@ impl Function {
         args
     }
 
+    /// C arguments as the generators writes them. Unlike `get_c_separated_arguments` handles and
+    /// self are pointers, unsized arrays are const as they are only read and consts used as array
+    /// sizes gets the C prefix
+    pub fn c_args(&self, self_name: &str, c_prefix: &str) -> Vec<String> {
+        let mut args = Vec::with_capacity(self.function_args.len());
+
+        for arg in &self.function_args {
+            match arg.vtype {
+                VariableType::Str => args.push(format!("const char* {}", arg.name)),
+
+                _ => match arg.array {
+                    None => {
+                        if arg.name != "va_args" && arg.type_name != "VA_ARGS" {
+                            args.push(format!("{} {}", arg.c_type(self_name, c_prefix), arg.name));
+                        } else {
+                            args.push("...".to_owned());
+                        }
+                    }
+
+                    Some(ArrayType::Unsized) => {
+                        args.push(format!(
+                            "{} {}",
+                            c_const_pointer(&arg.c_type(self_name, c_prefix)),
+                            arg.name
+                        ));
+                        args.push(format!("uint64_t {}_size", arg.name));
+                    }
+
+                    Some(ArrayType::SizedArray(ref size)) => {
+                        args.push(format!(
+                            "{} {}[{}]",
+                            arg.c_type(self_name, c_prefix),
+                            arg.name,
+                            get_c_array_size(size, &c_namespace_prefix(c_prefix, &arg.namespace))
+                        ));
+                    }
+                },
+            }
+        }
+
+        args
+    }
+
+    /// Name of the C function for a function in the struct `struct_name`. The namespace of the
+    /// function is added after `c_prefix`
+    pub fn get_c_name(&self, struct_name: &str, c_prefix: &str) -> String {
+        format!(
+            "{}{}_{}",
+            c_namespace_prefix(c_prefix, &self.namespace),
+            struct_name,
+            self.name
+        )
+    }
+
     pub fn get_c_arg_names(&self, self_name: &str) -> String {
         let mut output = String::with_capacity(256);
 
@@ -984,20 +1808,29 @@ impl Function {
         output
     }
 
-    pub fn get_c_return_value(&self, c_prefix: &str) -> Cow<str> {
+    pub fn get_c_return_value(&self, c_prefix: &str) -> Cow<'_, str> {
         if let Some(ret) = self.return_val.as_ref() {
             ret.get_c_variable("", c_prefix).into()
         } else {
             "void".into()
         }
     }
+
+    /// C return type as the generators writes it. Unlike `get_c_return_value` returned handles
+    /// are pointers
+    pub fn c_return_type(&self, c_prefix: &str) -> String {
+        match self.return_val.as_ref() {
+            Some(ret) => ret.c_type("", c_prefix),
+            None => "void".to_owned(),
+        }
+    }
 }
 
 ///
 /// Impl for Variable. Helper functions to make C and Rust generation easier
 ///
 impl Variable {
-    pub fn get_c_primitive_type(&self) -> Cow<str> {
+    pub fn get_c_primitive_type(&self) -> Cow<'_, str> {
         let tname = self.type_name.as_str();
 
         match tname {
@@ -1016,8 +1849,21 @@ impl Variable {
         }
     }
 
+    /// C type of the variable. The namespace of the type is added after `c_prefix`
     pub fn get_c_variable(&self, self_type: &str, c_prefix: &str) -> String {
+        self.c_type_with(self_type, c_prefix, false)
+    }
+
+    /// C type of the variable as the generators writes it. Unlike `get_c_variable` handles and
+    /// self are pointers as they are opaque types in C
+    pub fn c_type(&self, self_type: &str, c_prefix: &str) -> String {
+        self.c_type_with(self_type, c_prefix, true)
+    }
+
+    fn c_type_with(&self, self_type: &str, c_prefix: &str, handle_pointer: bool) -> String {
         let mut output = String::with_capacity(256);
+        let type_prefix = c_namespace_prefix(c_prefix, &self.type_namespace);
+        let pointer = if handle_pointer { "*" } else { "" };
 
         // TODO: If self type is a struct we should add struct at the front
 
@@ -1029,9 +1875,21 @@ impl Variable {
 
         match self.vtype {
             VariableType::None => output.push_str("void"),
-            VariableType::SelfType => output.push_str(&format!("struct {}{}", c_prefix, self_type)),
-            VariableType::Regular => output.push_str(&format!("{}{}", c_prefix, self.type_name)),
-            VariableType::Enum => output.push_str(&format!("{}{}", c_prefix, self.type_name)),
+            VariableType::SelfType => output.push_str(&format!(
+                "struct {}{}{}",
+                c_namespace_prefix(c_prefix, &self.namespace),
+                self_type,
+                pointer
+            )),
+            VariableType::Handle => {
+                output.push_str(&format!("{}{}{}", type_prefix, self.type_name, pointer))
+            }
+            VariableType::Regular
+            | VariableType::Enum
+            | VariableType::Alias
+            | VariableType::Callback => {
+                output.push_str(&format!("{}{}", type_prefix, self.type_name))
+            }
             VariableType::Str => output.push_str("const char*"),
             VariableType::Primitive => output.push_str(&self.get_c_primitive_type()),
         }
@@ -1046,7 +1904,7 @@ impl Variable {
         output
     }
 
-    pub fn get_primitive_type(&self) -> Cow<str> {
+    pub fn get_primitive_type(&self) -> Cow<'_, str> {
         let tname = self.type_name.as_str();
 
         match tname {
@@ -1056,13 +1914,28 @@ impl Variable {
     }
 
     pub fn get_ffi_type(&self, self_type: &str) -> String {
+        self.ffi_type_with(self_type, false)
+    }
+
+    /// Rust FFI type as the generators writes it. Unlike `get_ffi_type` handles are pointers
+    pub fn ffi_type(&self, self_type: &str) -> String {
+        self.ffi_type_with(self_type, true)
+    }
+
+    fn ffi_type_with(&self, self_type: &str, handle_pointer: bool) -> String {
         let mut output = String::with_capacity(256);
 
         match self.vtype {
             VariableType::None => output.push_str("c_void"),
             VariableType::SelfType => output.push_str(&format!("*mut {}", self_type)),
-            VariableType::Regular => output.push_str(&self.type_name),
-            VariableType::Enum => output.push_str(&self.type_name),
+            VariableType::Handle if handle_pointer => {
+                output.push_str(&format!("*mut {}", self.type_name))
+            }
+            VariableType::Handle
+            | VariableType::Regular
+            | VariableType::Enum
+            | VariableType::Alias
+            | VariableType::Callback => output.push_str(&self.type_name),
             VariableType::Str => output.push_str("*const c_char"),
             VariableType::Primitive => output.push_str(&self.get_primitive_type()),
         }
@@ -1105,6 +1978,27 @@ impl Variable {
 
         output
     }
+
+    /// C struct field as the generators writes it. Unlike `get_c_struct_variable` handles are
+    /// pointers and consts used as array sizes gets the C prefix
+    pub fn c_field(&self, c_prefix: &str) -> String {
+        let c_type = self.c_type("", c_prefix);
+
+        // for arrays we generate a pointer and a size
+        match self.array {
+            None => format!("    {} {};", c_type, self.name),
+            Some(ArrayType::Unsized) => format!(
+                "    {}* {};\n    uint64_t {}_size;",
+                c_type, self.name, self.name
+            ),
+            Some(ArrayType::SizedArray(ref size)) => format!(
+                "    {} {}[{}];",
+                c_type,
+                self.name,
+                get_c_array_size(size, &c_namespace_prefix(c_prefix, &self.namespace))
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1117,7 +2011,28 @@ mod tests {
 
     #[test]
     fn test_primitve_false() {
-        assert!(is_primitve("dummy"));
+        assert!(!is_primitve("dummy"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join("apigen_non_utf8_path");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(std::ffi::OsStr::from_bytes(b"im\xffage.def"));
+        std::fs::write(&path, "struct Foo { a: u32 }").unwrap();
+
+        let def = ApiParser::parse_file(&path).unwrap();
+        assert_eq!(def.base_filename, "im\u{fffd}age");
+        assert_eq!(def.structs[0].name, "Foo");
+
+        let (def, errors) = ApiParser::parse_file_with_recovery(&path).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(def.structs.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
@@ -1128,6 +2043,78 @@ mod tests {
         assert_eq!(def.types[0].var.type_name, "u64");
     }
 
+    #[test]
+    fn test_pointer_modifiers() {
+        let def = ApiParser::parse_string(
+            "struct Foo { a: *const u8, b: *u8, c: &u8, d: u8 }",
+            "ptr.def",
+        )
+        .unwrap();
+        let vars = &def.structs[0].variables;
+        assert_eq!(vars[0].type_modifier, TypeModifier::ConstPointer);
+        assert_eq!(vars[1].type_modifier, TypeModifier::MutPointer);
+        assert_eq!(vars[2].type_modifier, TypeModifier::Reference);
+        assert_eq!(vars[3].type_modifier, TypeModifier::None);
+
+        assert_eq!(vars[0].get_c_variable("Foo", ""), "const uint8_t*");
+        assert_eq!(vars[1].get_c_variable("Foo", ""), "uint8_t*");
+        assert_eq!(vars[0].get_ffi_type("Foo"), "*const u8");
+        assert_eq!(vars[1].get_ffi_type("Foo"), "*mut u8");
+    }
+
+    #[test]
+    fn test_handle_c_types() {
+        let mut defs = vec![ApiParser::parse_string(
+            "#[attributes(Handle)]\nstruct Image {\n    [static] create() -> Image,\n    get_size() -> u32,\n}\nstruct Info { image: Image }",
+            "image.def",
+        )
+        .unwrap()];
+        crate::resolver::resolve(&mut defs);
+
+        let create = &defs[0].structs[0].functions[0];
+        let ret = create.return_val.as_ref().unwrap();
+        assert_eq!(ret.vtype, VariableType::Handle);
+        assert_eq!(ret.c_type("Image", "FL_"), "FL_Image*");
+        assert_eq!(ret.ffi_type("Image"), "*mut Image");
+        assert_eq!(ret.get_c_variable("Image", "FL_"), "FL_Image");
+        assert_eq!(ret.get_ffi_type("Image"), "Image");
+
+        // `self` is always passed as a pointer to the struct
+        let self_arg = &defs[0].structs[0].functions[1].function_args[0];
+        assert_eq!(self_arg.vtype, VariableType::SelfType);
+        assert_eq!(self_arg.c_type("Image", "FL_"), "struct FL_Image*");
+        assert_eq!(self_arg.get_c_variable("Image", "FL_"), "struct FL_Image");
+
+        let field = &defs[0].structs[1].variables[0];
+        assert_eq!(field.vtype, VariableType::Handle);
+    }
+
+    #[test]
+    fn test_c_array_size() {
+        let def = ApiParser::parse_string(
+            "const COUNT = 4\nstruct Foo {\n    data: [u8; COUNT],\n    set(data: [u8; COUNT]),\n}",
+            "foo.def",
+        )
+        .unwrap();
+
+        let field = &def.structs[0].variables[0];
+        assert_eq!(
+            field.get_c_struct_variable("FL_"),
+            "    uint8_t data[COUNT];"
+        );
+        assert_eq!(field.c_field("FL_"), "    uint8_t data[FL_COUNT];");
+
+        let func = &def.structs[0].functions[0];
+        assert_eq!(
+            func.get_c_arguments("Foo", "FL_"),
+            "struct FL_Foo self, uint8_t data[COUNT]"
+        );
+        assert_eq!(
+            func.c_args("Foo", "FL_"),
+            ["struct FL_Foo* self", "uint8_t data[FL_COUNT]"]
+        );
+    }
+
     #[test]
     fn test_union() {
         let d = ApiParser::parse_string("union Test { foo: u64, bar: u32 }", "union.def").unwrap();
@@ -1155,4 +2142,179 @@ mod tests {
         assert_eq!(def.consts[0].name, "FOOBAR");
         assert_eq!(def.consts[0].value, "0x123");
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let code = "/// Image\n#[attributes(Handle)]\nstruct Image {\n    [static] load(name: String, data: [u8]) -> Image?,\n}\nenum Mode { A = 1, B = 2 }";
+        let def = ApiParser::parse_string(code, "image.def").unwrap();
+
+        let json = serde_json::to_string(&def).unwrap();
+        let def2: ApiDef = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(def2.structs[0].name, "Image");
+        assert_eq!(def2.structs[0].doc_comments, vec!["Image".to_owned()]);
+        assert_eq!(
+            def2.structs[0].functions[0].function_args[1].array,
+            Some(ArrayType::Unsized)
+        );
+        assert_eq!(def2.enums[0].entries[1].value, 2);
+        assert_eq!(serde_json::to_string(&def2).unwrap(), json);
+    }
+
+    #[test]
+    fn test_recovery() {
+        let data = "struct Foo {\n    a: u32,\n}\n\nstruct Bar {\n    a: \n}\n\n/// Doc\nenum Baz {\n    A,\n}\n\nunion Broken {\n    a: [u32\n}\n\ntype Id: u64\n";
+        let (def, errors) = ApiParser::parse_string_with_recovery(data, "recover.def");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(def.structs.len(), 1);
+        assert_eq!(def.structs[0].name, "Foo");
+        assert_eq!(def.enums.len(), 1);
+        assert_eq!(def.enums[0].doc_comments, vec!["Doc".to_owned()]);
+        assert_eq!(def.types.len(), 1);
+        assert_eq!(def.enums[0].span.line, 10);
+        assert_eq!(def.types[0].var.span.line, 18);
+        assert_eq!(&data[def.types[0].span.byte_range.clone()], "type Id: u64");
+
+        match (&errors[0], &errors[1]) {
+            (ApigenError::Parse { line: l0, .. }, ApigenError::Parse { line: l1, .. }) => {
+                assert_eq!(*l0, 7);
+                assert_eq!(*l1, 16);
+            }
+            _ => panic!("expected parse errors"),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let data = "/// Doc\nstruct Foo {\n    a: u32,\n    get() -> u32,\n}\n\nenum Bar {\n    A,\n    B = 2,\n}\n\ncallback Cb(v: u32)\n";
+        let def = ApiParser::parse_string(data, "dir/spans.def").unwrap();
+        let text = |span: &Span| &data[span.byte_range.clone()];
+
+        let s = &def.structs[0];
+        assert_eq!(s.span.file, "dir/spans.def");
+        assert_eq!((s.span.line, s.span.column), (2, 8));
+        assert!(text(&s.span).starts_with("struct Foo {"));
+        assert!(text(&s.span).ends_with('}'));
+        assert_eq!(text(&s.variables[0].span), "a: u32");
+        assert_eq!(text(&s.functions[0].span), "get() -> u32");
+        assert_eq!(s.functions[0].def_file, "spans");
+        assert_eq!(s.variables[0].def_file, "spans");
+
+        let e = &def.enums[0];
+        assert_eq!(e.def_file, "spans");
+        assert_eq!((e.span.line, e.span.column), (7, 6));
+        assert_eq!(text(&e.entries[1].span), "B = 2");
+        assert_eq!((e.entries[1].span.line, e.entries[1].span.column), (9, 5));
+
+        let cb = &def.callbacks[0];
+        assert_eq!(text(&cb.span), "callback Cb(v: u32)");
+        assert_eq!((cb.span.line, cb.span.column), (12, 10));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = ApiParser::parse_string("struct Foo {\n    foo: u32\n", "error.def").unwrap_err();
+
+        match err {
+            ApigenError::Parse {
+                filename,
+                line,
+                column,
+                expected,
+                snippet,
+                ..
+            } => {
+                assert_eq!(filename, "error.def");
+                assert_eq!(line, 3);
+                assert_eq!(column, 1);
+                assert!(!expected.is_empty());
+                assert!(snippet.contains('^'));
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_enum_value() {
+        let err = ApiParser::parse_string("enum Foo {\n    Bar = 1x2,\n}", "enum.def").unwrap_err();
+
+        match err {
+            ApigenError::Parse { line, message, .. } => {
+                assert_eq!(line, 2);
+                assert!(message.contains("Bar"));
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    #[test]
+    fn test_hex_enum_value() {
+        let def = ApiParser::parse_string("enum Foo {\n    Bar = 0x10,\n}", "enum.def").unwrap();
+        assert_eq!(def.enums[0].entries[0].value, 16);
+    }
+
+    #[test]
+    fn test_ownership() {
+        let def = ApiParser::parse_string(
+            "struct Image {
+    [static] load() -> Image?,
+    newest() -> Image,
+    [owned] copy() -> Image,
+    [static] [borrowed] get_default() -> Image,
+}",
+            "image.def",
+        )
+        .unwrap();
+
+        let funcs = &def.structs[0].functions;
+        assert_eq!(funcs[2].ownership, Ownership::Owned);
+        assert_eq!(funcs[3].ownership, Ownership::Borrowed);
+        assert_eq!(funcs[3].func_type, FunctionType::Static);
+        let owned: Vec<bool> = funcs.iter().map(|f| f.returns_owned()).collect();
+        assert_eq!(owned, [true, false, true, false]);
+    }
+
+    #[test]
+    fn test_namespace() {
+        let data = "namespace render::gl
+struct Texture {
+    device: render::Device,
+    get_size() -> Size,
+}
+enum Format { Rgba }";
+        let def = ApiParser::parse_string(data, "gl.def").unwrap();
+
+        assert_eq!(def.namespace, ["render", "gl"]);
+        assert_eq!(def.structs[0].namespace, ["render", "gl"]);
+        assert_eq!(def.enums[0].namespace, ["render", "gl"]);
+
+        let var = &def.structs[0].variables[0];
+        assert_eq!(var.namespace, ["render", "gl"]);
+        assert_eq!(var.type_namespace, ["render"]);
+        assert_eq!(var.type_name, "Device");
+
+        let func = &def.structs[0].functions[0];
+        assert_eq!(
+            func.get_c_name("Texture", "FL_"),
+            "FL_render_gl_Texture_get_size"
+        );
+
+        let err = ApiParser::parse_string(
+            "namespace a
+namespace b
+",
+            "twice.def",
+        )
+        .unwrap_err();
+
+        match err {
+            ApigenError::Parse { line, message, .. } => {
+                assert_eq!(line, 2);
+                assert!(message.contains("already declared"));
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
 }
@@ -0,0 +1,24 @@
+//! Language server for def files. Talks to the editor over stdin and stdout
+use lsp_server::Connection;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let (connection, io_threads) = Connection::stdio();
+    let result = apigen::lsp::run(&connection);
+
+    // The writer thread exits when the connection is dropped
+    drop(connection);
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    match io_threads.join() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use apigen::formatter;
+use apigen::generators::{DirSink, GeneratorOptions, Registry};
+use apigen::validate::{self, Severity};
+use apigen::ApiDef;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: apigen <command> [options]
+
+Commands:
+    check <dir>                         Parse and validate all files in <dir>
+    dump [--format json|debug] <dir>    Parse all files in <dir> and print the result (json needs
+                                        apigen to be built with the `ir` feature)
+    gen <backend> <dir> -o <out>        Generate code with <backend> for all files in <dir>
+    backends                            List the backends that can be used with gen
+    fmt [--check] <path>                Format the def files in <path> (a file or directory)
+
+Options:
+    -p, --prefix <prefix>               Prefix for generated C names (gen)
+    --ffi-module <path>                 Module with the rust-ffi code (gen rust, default crate::ffi)
+    --namespace <name>                  C++/C# namespace or Python module name (gen cpp/csharp/
+                                        python, defaults to the prefix in lower case)
+    --library <name>                    Library used with DllImport (gen csharp, default namespace)
+    --hide-function-types               Don't show [static], [manual] and [drop] (gen html/markdown)
+    --hide-optional                     Don't show ? on optional values (gen html/markdown)
+    --template <file>                   Template to render for each def file (gen template)
+    --option <key>=<value>              Option for backends that aren't part of apigen (gen)
+    --check                             Only list the files that aren't formatted (fmt)
+    -h, --help                          Print this help
+
+Exit codes:
+    0    Success
+    1    The def files has errors (or aren't formatted with fmt --check)
+    2    Invalid arguments, no def files in <dir> or failed to read/write files";
+
+/// Output format for `dump`
+enum Format {
+    Debug,
+    #[cfg(feature = "ir")]
+    Json,
+}
+
+/// Arguments split into positional arguments and options
+struct Args {
+    positional: Vec<String>,
+    format: Option<String>,
+    output: Option<PathBuf>,
+    prefix: String,
+    ffi_module: Option<String>,
+    namespace: Option<String>,
+    library: Option<String>,
+    hide_function_types: bool,
+    hide_optional: bool,
+    template: Option<PathBuf>,
+    extra: Vec<(String, String)>,
+    check: bool,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            format: None,
+            output: None,
+            prefix: String::new(),
+            ffi_module: None,
+            namespace: None,
+            library: None,
+            hide_function_types: false,
+            hide_optional: false,
+            template: None,
+            extra: Vec::new(),
+            check: false,
+        };
+
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let mut value = |name: &str| {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+
+            match arg.as_str() {
+                "--format" | "-f" => parsed.format = Some(value(arg)?),
+                "--output" | "-o" => parsed.output = Some(value(arg)?.into()),
+                "--prefix" | "-p" => parsed.prefix = value(arg)?,
+                "--ffi-module" => parsed.ffi_module = Some(value(arg)?),
+                "--namespace" => parsed.namespace = Some(value(arg)?),
+                "--library" => parsed.library = Some(value(arg)?),
+                "--hide-function-types" => parsed.hide_function_types = true,
+                "--hide-optional" => parsed.hide_optional = true,
+                "--check" => parsed.check = true,
+                "--template" => parsed.template = Some(value(arg)?.into()),
+                "--option" => parsed.extra.push(parse_option(&value(arg)?)?),
+                _ if arg.starts_with("--format=") => {
+                    parsed.format = Some(arg["--format=".len()..].to_owned())
+                }
+                _ if arg.starts_with("--output=") => {
+                    parsed.output = Some(arg["--output=".len()..].into())
+                }
+                _ if arg.starts_with("--prefix=") => {
+                    parsed.prefix = arg["--prefix=".len()..].to_owned()
+                }
+                _ if arg.starts_with("--ffi-module=") => {
+                    parsed.ffi_module = Some(arg["--ffi-module=".len()..].to_owned())
+                }
+                _ if arg.starts_with("--namespace=") => {
+                    parsed.namespace = Some(arg["--namespace=".len()..].to_owned())
+                }
+                _ if arg.starts_with("--library=") => {
+                    parsed.library = Some(arg["--library=".len()..].to_owned())
+                }
+                _ if arg.starts_with("--template=") => {
+                    parsed.template = Some(arg["--template=".len()..].into())
+                }
+                _ if arg.starts_with("--option=") => {
+                    parsed.extra.push(parse_option(&arg["--option=".len()..])?)
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+                _ => parsed.positional.push(arg.to_owned()),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Options for the generators
+    fn generator_options(&self) -> GeneratorOptions {
+        GeneratorOptions {
+            c_prefix: self.prefix.clone(),
+            namespace: self.namespace.clone(),
+            library: self.library.clone(),
+            ffi_module: self.ffi_module.clone(),
+            hide_function_types: self.hide_function_types,
+            hide_optional: self.hide_optional,
+            template: self.template.clone(),
+            extra: self.extra.iter().cloned().collect(),
+        }
+    }
+
+    /// Get the positional argument at `index` (after the command)
+    fn get(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(|s| s.as_str())
+            .ok_or_else(|| format!("missing <{}>", name))
+    }
+}
+
+/// Splits `key=value` given to `--option`
+fn parse_option(option: &str) -> Result<(String, String), String> {
+    option
+        .split_once('=')
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected <key>=<value> for --option, got `{}`", option))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match run(&args[0], &args[1..]) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(command: &str, args: &[String]) -> Result<ExitCode, String> {
+    let args = Args::parse(args)?;
+
+    match command {
+        "check" => check(args.get(0, "dir")?),
+        "dump" => {
+            let format = match args.format.as_deref() {
+                None | Some("debug") => Format::Debug,
+                #[cfg(feature = "ir")]
+                Some("json") => Format::Json,
+                #[cfg(not(feature = "ir"))]
+                Some("json") => {
+                    return Err(
+                        "`--format json` needs apigen to be built with the `ir` feature".to_owned(),
+                    )
+                }
+                Some(f) => return Err(format!("unknown format `{}`", f)),
+            };
+
+            dump(args.get(0, "dir")?, format)
+        }
+        "gen" => {
+            let output = args.output.as_ref().ok_or("missing -o <out>")?;
+            gen(args.get(0, "backend")?, args.get(1, "dir")?, output, &args)
+        }
+        "backends" => backends(),
+        "fmt" => fmt(args.get(0, "path")?, args.check),
+        _ => Err(format!("unknown command `{}`", command)),
+    }
+}
+
+/// Checks that `dir` can be read and has def files in it so a wrong path isn't reported as a
+/// successful run without any files
+fn check_dir(dir: &str) -> Result<(), String> {
+    if !Path::new(dir).exists() {
+        return Err(format!("`{}` doesn't exist", dir));
+    }
+
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry.map_err(|e| format!("unable to read {}: {}", dir, e))?;
+
+        if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "def") {
+            return Ok(());
+        }
+    }
+
+    Err(format!("no .def files in `{}`", dir))
+}
+
+/// Parse all files and print all the errors and warnings
+fn check(dir: &str) -> Result<ExitCode, String> {
+    check_dir(dir)?;
+
+    let (api_defs, parse_errors) =
+        apigen::parse_files_with_recovery(dir).map_err(|e| e.to_string())?;
+
+    for e in &parse_errors {
+        eprintln!("{}", e);
+    }
+
+    let diagnostics = validate::validate(&api_defs);
+
+    for d in &diagnostics {
+        eprintln!("{}", d);
+    }
+
+    let error_count = parse_errors.len()
+        + diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+    let warning_count = diagnostics.len() + parse_errors.len() - error_count;
+
+    eprintln!(
+        "checked {} file(s): {} error(s), {} warning(s)",
+        api_defs.len(),
+        error_count,
+        warning_count
+    );
+
+    if error_count > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Parse and validate all files. Bails on the first parse error and if validation finds any
+/// errors. Warnings are printed but doesn't stop the command
+fn parse(dir: &str) -> Result<Vec<ApiDef>, ExitCode> {
+    let api_defs = apigen::parse_files(dir, false).map_err(|e| {
+        eprintln!("{}", e);
+        ExitCode::FAILURE
+    })?;
+
+    let diagnostics = validate::validate(&api_defs);
+
+    for d in &diagnostics {
+        eprintln!("{}", d);
+    }
+
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return Err(ExitCode::FAILURE);
+    }
+
+    Ok(api_defs)
+}
+
+fn dump(dir: &str, format: Format) -> Result<ExitCode, String> {
+    check_dir(dir)?;
+
+    let api_defs = match parse(dir) {
+        Ok(api_defs) => api_defs,
+        Err(code) => return Ok(code),
+    };
+
+    let text = match format {
+        Format::Debug => format!("{:#?}", api_defs),
+        #[cfg(feature = "ir")]
+        Format::Json => apigen::ir::IrDocument::from_api_defs(&api_defs).to_json(),
+    };
+
+    // Writing into a closed pipe (such as `apigen dump dir | head`) isn't an error
+    match writeln!(io::stdout(), "{}", text) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(ExitCode::SUCCESS),
+    }
+}
+
+/// Prints the name and description of all backends
+fn backends() -> Result<ExitCode, String> {
+    let registry = Registry::with_builtins();
+    let width = registry
+        .generators()
+        .map(|g| g.name().len())
+        .max()
+        .unwrap_or(0);
+
+    for generator in registry.generators() {
+        println!(
+            "{:width$}    {}",
+            generator.name(),
+            generator.description(),
+            width = width
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn gen(backend: &str, dir: &str, output: &Path, args: &Args) -> Result<ExitCode, String> {
+    let registry = Registry::with_builtins();
+
+    let generator = registry.get(backend).ok_or_else(|| {
+        let names = registry.generators().map(|g| g.name()).collect::<Vec<_>>();
+        format!(
+            "unknown backend `{}` (available: {})",
+            backend,
+            names.join(", ")
+        )
+    })?;
+
+    check_dir(dir)?;
+
+    let api_defs = match parse(dir) {
+        Ok(api_defs) => api_defs,
+        Err(code) => return Ok(code),
+    };
+
+    std::fs::create_dir_all(output)
+        .map_err(|e| format!("unable to create {}: {}", output.display(), e))?;
+
+    generator
+        .generate(
+            &api_defs,
+            &args.generator_options(),
+            &mut DirSink::new(output),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Formats all def files in `path` (or `path` if it's a file). With `check` the files aren't
+/// changed and the ones that aren't formatted are listed instead
+fn fmt(path: &str, check: bool) -> Result<ExitCode, String> {
+    let files = if Path::new(path).is_dir() {
+        walkdir::WalkDir::new(path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "def"))
+            .collect::<Vec<_>>()
+    } else {
+        vec![PathBuf::from(path)]
+    };
+
+    let mut failed = false;
+
+    for file in &files {
+        let source = std::fs::read_to_string(file)
+            .map_err(|e| format!("unable to read {}: {}", file.display(), e))?;
+
+        let formatted = match formatter::format_string(&source, &file.to_string_lossy()) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            eprintln!("{}: not formatted", file.display());
+            failed = true;
+        } else {
+            std::fs::write(file, formatted)
+                .map_err(|e| format!("unable to write {}: {}", file.display(), e))?;
+            eprintln!("formatted {}", file.display());
+        }
+    }
+
+    if failed {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,396 @@
+//! Lossless concrete syntax tree for def files.
+//!
+//! The parser only keeps what is needed to build an `ApiDef`, so regular (`//`) comments,
+//! whitespace and punctuation are thrown away. A [`Cst`] keeps all of it: the tree has a node
+//! for each grammar rule that matched (see `api.pest`) and everything between them is kept as
+//! tokens, so the text of the tree is always the same as the source.
+//!
+//! The top-level nodes are mapped to the `ApiDef` elements they declare (see [`Cst::items`]) so
+//! tools can find the text for a definition and edit it with [`Cst::replace`] without touching
+//! the comments and layout around it.
+use crate::api_parser::{ApiDef, ApiParser, ApigenError, Result, Rule};
+use pest::iterators::{Pair, Pairs};
+use pest::Parser;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+
+/// Kind of a token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces and tabs
+    Whitespace,
+    /// `\n` or `\r\n`
+    Newline,
+    /// Regular comment (`// text`) without the line ending
+    Comment,
+    /// Keywords and punctuation such as `struct`, `{` and `->`
+    Punct,
+    /// Text of a rule that doesn't have any sub rules, such as names, numbers and doc comments
+    Text,
+}
+
+/// A piece of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    /// Byte range in the source
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// Kind of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The whole file
+    Root,
+    /// A grammar rule
+    Rule(Rule),
+}
+
+/// Child of a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// A node in the tree. The children covers all of `range`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    /// Byte range in the source
+    pub range: Range<usize>,
+    pub children: Vec<SyntaxElement>,
+}
+
+/// Reference to an element in `ApiDef`. The index is into the list for the kind (such as
+/// `ApiDef::structs`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemRef {
+    /// The `namespace` declaration (see `ApiDef::namespace`)
+    Namespace,
+    Mod(usize),
+    /// `use` and `import` (index into `ApiDef::imports`)
+    Import(usize),
+    Const(usize),
+    Type(usize),
+    Callback(usize),
+    Enum(usize),
+    Struct(usize),
+    Union(usize),
+}
+
+/// A top-level definition in the tree
+#[derive(Debug, Clone)]
+pub struct CstItem<'a> {
+    /// The element in `ApiDef`
+    pub item: ItemRef,
+    /// Node for the definition (such as `structdef`)
+    pub node: &'a SyntaxNode,
+    /// Byte range of the definition including the doc comments before it
+    pub range: Range<usize>,
+}
+
+/// Lossless syntax tree of a def file together with its `ApiDef`
+#[derive(Debug)]
+pub struct Cst {
+    source: String,
+    root: SyntaxNode,
+    api_def: ApiDef,
+    line_starts: Vec<usize>,
+}
+
+impl SyntaxNode {
+    /// The source text of the node
+    pub fn text(&self) -> String {
+        self.tokens().map(|t| t.text.as_str()).collect()
+    }
+
+    /// All tokens in the node (and its children) in source order
+    pub fn tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens.into_iter()
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// Nodes that are direct children of this node
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// First direct child that matched `rule`
+    pub fn child(&self, rule: Rule) -> Option<&SyntaxNode> {
+        self.child_nodes().find(|n| n.kind == NodeKind::Rule(rule))
+    }
+}
+
+impl Cst {
+    pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Cst> {
+        let mut buffer = String::new();
+        let mut f = File::open(&path)?;
+        f.read_to_string(&mut buffer)?;
+        Self::parse(&buffer, path.as_ref().to_str().unwrap())
+    }
+
+    /// Parses `source`. `filename` is used for errors and the `ApiDef`
+    pub fn parse(source: &str, filename: &str) -> Result<Cst> {
+        let pairs = ApiParser::parse(Rule::chunk, source)
+            .map_err(|e| ApigenError::from_pest(e, filename))?;
+
+        let root = SyntaxNode {
+            kind: NodeKind::Root,
+            range: 0..source.len(),
+            children: build_children(source, 0..source.len(), pairs.clone()),
+        };
+
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Ok(Cst {
+            source: source.to_owned(),
+            root,
+            api_def: ApiParser::api_def_from_pairs(pairs, filename)?,
+            line_starts,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// The parsed (but not resolved) data
+    pub fn api_def(&self) -> &ApiDef {
+        &self.api_def
+    }
+
+    /// Line and column (both 1 based) of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = self.source[self.line_starts[line - 1]..offset]
+            .chars()
+            .count()
+            + 1;
+        (line, column)
+    }
+
+    /// All regular comments in the file
+    pub fn comments(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.root.tokens().filter(|t| t.kind == TokenKind::Comment)
+    }
+
+    /// Top-level definitions in source order
+    pub fn items(&self) -> Vec<CstItem<'_>> {
+        let mut items = Vec::new();
+        let mut counts = [0usize; 9];
+        let mut doc_start = None;
+
+        for node in self.root.child_nodes() {
+            let NodeKind::Rule(rule) = node.kind else {
+                continue;
+            };
+
+            let (slot, item): (usize, fn(usize) -> ItemRef) = match rule {
+                Rule::doc_comment => {
+                    doc_start.get_or_insert(node.range.start);
+                    continue;
+                }
+                Rule::moddef => (0, ItemRef::Mod),
+                Rule::const_value => (1, ItemRef::Const),
+                Rule::type_value => (2, ItemRef::Type),
+                Rule::callbackdef => (3, ItemRef::Callback),
+                Rule::enumdef => (4, ItemRef::Enum),
+                Rule::structdef => (5, ItemRef::Struct),
+                Rule::uniondef => (6, ItemRef::Union),
+                Rule::usedef | Rule::importdef => (7, ItemRef::Import),
+                Rule::namespacedef => (8, |_| ItemRef::Namespace),
+                _ => continue,
+            };
+
+            // Doc comments before a `mod`, `namespace` or an import are used by the next item
+            let start = if matches!(
+                rule,
+                Rule::moddef | Rule::namespacedef | Rule::usedef | Rule::importdef
+            ) {
+                node.range.start
+            } else {
+                doc_start.take().unwrap_or(node.range.start)
+            };
+
+            items.push(CstItem {
+                item: item(counts[slot]),
+                node,
+                range: start..node.range.end,
+            });
+            counts[slot] += 1;
+        }
+
+        items
+    }
+
+    /// The top-level definition for `item`
+    pub fn item(&self, item: ItemRef) -> Option<CstItem<'_>> {
+        self.items().into_iter().find(|i| i.item == item)
+    }
+
+    /// Replaces `range` in the source with `text` and parses it again. If the new source doesn't
+    /// parse the error is returned and nothing is changed.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> Result<()> {
+        let mut source = self.source.clone();
+        source.replace_range(range, text);
+
+        *self = Cst::parse(&source, &self.api_def.filename)?;
+        Ok(())
+    }
+}
+
+/// Builds the children for `range` from the pairs in it. The text between the pairs becomes
+/// tokens
+fn build_children(source: &str, range: Range<usize>, pairs: Pairs<Rule>) -> Vec<SyntaxElement> {
+    let mut children = Vec::new();
+    let mut pos = range.start;
+
+    for pair in pairs {
+        // The end of input doesn't have any text
+        if pair.as_rule() == Rule::EOI {
+            continue;
+        }
+
+        let span = pair.as_span();
+        lex(source, pos..span.start(), &mut children);
+        children.push(SyntaxElement::Node(build_node(source, pair)));
+        pos = span.end();
+    }
+
+    lex(source, pos..range.end, &mut children);
+    children
+}
+
+fn build_node(source: &str, pair: Pair<Rule>) -> SyntaxNode {
+    let range = pair.as_span().start()..pair.as_span().end();
+    let kind = NodeKind::Rule(pair.as_rule());
+    let inner = pair.into_inner();
+
+    let children = if inner.peek().is_none() {
+        if range.is_empty() {
+            Vec::new()
+        } else {
+            vec![SyntaxElement::Token(SyntaxToken {
+                kind: TokenKind::Text,
+                range: range.clone(),
+                text: source[range.clone()].to_owned(),
+            })]
+        }
+    } else {
+        build_children(source, range.clone(), inner)
+    };
+
+    SyntaxNode {
+        kind,
+        range,
+        children,
+    }
+}
+
+/// Splits text that isn't part of a rule into whitespace, newlines, comments and punctuation
+fn lex(source: &str, range: Range<usize>, children: &mut Vec<SyntaxElement>) {
+    let text = &source[range.clone()];
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let rest = &text[pos..];
+
+        let (kind, len) = if rest.starts_with("//") {
+            let len = rest.find(['\r', '\n']).unwrap_or(rest.len());
+            (TokenKind::Comment, len)
+        } else if rest.starts_with("\r\n") {
+            (TokenKind::Newline, 2)
+        } else if rest.starts_with('\n') {
+            (TokenKind::Newline, 1)
+        } else if rest.starts_with(char::is_whitespace) {
+            let len = rest
+                .char_indices()
+                .find(|&(i, c)| !c.is_whitespace() || c == '\n' || rest[i..].starts_with("\r\n"))
+                .map_or(rest.len(), |(i, _)| i);
+            (TokenKind::Whitespace, len)
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace())
+                .unwrap_or(rest.len())
+                .min(rest.find("//").unwrap_or(rest.len()));
+            (TokenKind::Punct, len)
+        };
+
+        let start = range.start + pos;
+        children.push(SyntaxElement::Token(SyntaxToken {
+            kind,
+            range: start..start + len,
+            text: rest[..len].to_owned(),
+        }));
+        pos += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "// Header comment\r\nmod render\n\n/// Image data\n#[attributes(Handle)]\nstruct Image { // trailing\n    // own line\n    width: u32,\n    [static] create() -> Image?,\n}\n\nconst MAX = 4 // max\nenum Format {\n    Rgb,\n}\n";
+
+    #[test]
+    fn test_cst() {
+        let mut cst = Cst::parse(SOURCE, "image.def").unwrap();
+        assert_eq!(cst.root().text(), SOURCE);
+
+        let comments = cst.comments().map(|c| c.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            ["// Header comment", "// trailing", "// own line", "// max"]
+        );
+
+        let items = cst.items();
+        let refs = items.iter().map(|i| i.item).collect::<Vec<_>>();
+        assert_eq!(
+            refs,
+            [
+                ItemRef::Mod(0),
+                ItemRef::Struct(0),
+                ItemRef::Const(0),
+                ItemRef::Enum(0)
+            ]
+        );
+        assert!(cst.source()[items[1].range.clone()].starts_with("/// Image data\n#[attributes"));
+        assert_eq!(cst.line_col(items[1].node.range.start), (5, 1));
+
+        // Rename the struct without touching the comments
+        let name = items[1].node.child(Rule::name).unwrap().range.clone();
+        cst.replace(name, "Picture").unwrap();
+        assert_eq!(cst.api_def().structs[0].name, "Picture");
+        assert_eq!(cst.comments().count(), 4);
+        assert_eq!(
+            cst.source(),
+            SOURCE.replace("struct Image", "struct Picture")
+        );
+
+        assert!(cst.replace(0..0, "struct {").is_err());
+        assert_eq!(cst.api_def().structs[0].name, "Picture");
+    }
+}
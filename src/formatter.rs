@@ -0,0 +1,810 @@
+//! Writes an `ApiDef` back to def syntax in a canonical style.
+//!
+//! Items are written in the order they are declared in the file. Fields are indented with four
+//! spaces and all of them ends with `,`, doc comments are written as `/// text` and attributes are
+//! put on their own lines before the item. Enum values are only written when they differ from the
+//! implicit value, except for bitflags that always gets their value in hex.
+//!
+//! When formatting source (see [`format_string`] and [`format_cst`]) regular (`//`) comments are
+//! kept from the [`Cst`](crate::cst::Cst). Comments on their own line stays before the item or
+//! field that follows them and comments after code stays at the end of that line. Functions with
+//! comments between the arguments gets one argument per line so the comments stays after the
+//! argument they belong to. Single empty lines between fields and items are also kept.
+//!
+//! Formatting the output again gives the same text. Attributes on enums aren't part of `ApiDef` so
+//! they are lost.
+use crate::api_parser::{
+    ApiDef, ArrayType, Const, Enum, EnumType, Function, FunctionType, Import, Ownership, Result,
+    Rule, Struct, Type, TypeModifier, Variable, VariableType,
+};
+use crate::cst::{Cst, ItemRef, NodeKind};
+use crate::resolver::qualified_name;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+const INDENT: usize = 4;
+
+/// Top-level item in a def file
+enum Item<'a> {
+    Namespace(&'a [String]),
+    Mod(&'a str),
+    Import(&'a Import),
+    Const(&'a Const),
+    Type(&'a Type),
+    Callback(&'a Function),
+    Enum(usize, &'a Enum),
+    Struct(usize, &'a Struct),
+    Union(usize, &'a Struct),
+}
+
+impl Item<'_> {
+    fn item_ref(&self, index: usize) -> ItemRef {
+        match self {
+            Item::Namespace(_) => ItemRef::Namespace,
+            Item::Mod(_) => ItemRef::Mod(index),
+            Item::Import(_) => ItemRef::Import(index),
+            Item::Const(_) => ItemRef::Const(index),
+            Item::Type(_) => ItemRef::Type(index),
+            Item::Callback(_) => ItemRef::Callback(index),
+            Item::Enum(i, _) => ItemRef::Enum(*i),
+            Item::Struct(i, _) => ItemRef::Struct(*i),
+            Item::Union(i, _) => ItemRef::Union(*i),
+        }
+    }
+
+    /// Line of the name (0 if unknown)
+    fn line(&self) -> usize {
+        match self {
+            Item::Namespace(_) | Item::Mod(_) => 0,
+            Item::Import(i) => i.span.line,
+            Item::Const(c) => c.span.line,
+            Item::Type(t) => t.var.span.line,
+            Item::Callback(f) => f.span.line,
+            Item::Enum(_, e) => e.span.line,
+            Item::Struct(_, s) | Item::Union(_, s) => s.span.line,
+        }
+    }
+
+    fn doc_comments(&self) -> &[String] {
+        match self {
+            Item::Namespace(_) | Item::Mod(_) | Item::Import(_) => &[],
+            Item::Const(c) => &c.doc_comments,
+            Item::Type(t) => &t.var.doc_comments,
+            Item::Callback(f) => &f.doc_comments,
+            Item::Enum(_, e) => &e.doc_comments,
+            Item::Struct(_, s) | Item::Union(_, s) => &s.doc_comments,
+        }
+    }
+
+    /// Items that are written on one line. These are grouped without empty lines between them
+    fn single_line_kind(&self) -> Option<u8> {
+        match self {
+            Item::Namespace(_) => Some(0),
+            Item::Mod(_) => Some(1),
+            Item::Import(_) => Some(2),
+            Item::Const(_) => Some(3),
+            Item::Type(_) => Some(4),
+            Item::Callback(_) => Some(5),
+            _ => None,
+        }
+    }
+}
+
+/// Field in a struct or union
+enum Field<'a> {
+    Variable(&'a Variable),
+    Function(&'a Function),
+}
+
+/// Regular comment in the source
+struct Comment {
+    line: usize,
+    text: String,
+    /// There is no code before the comment on the line
+    own_line: bool,
+}
+
+/// The parts of the source that aren't in `ApiDef`. Empty if there is no source
+#[derive(Default)]
+struct Layout {
+    /// Comments in source order
+    comments: Vec<Comment>,
+    /// Lines that are empty or only has whitespace
+    blank_lines: HashSet<usize>,
+    /// First line (including doc comments) and last line of the top-level items
+    items: HashMap<ItemRef, (usize, usize)>,
+    /// Line of each enum entry by enum and entry index
+    enum_entries: HashMap<(usize, usize), usize>,
+    /// Byte offset where each line starts
+    line_starts: Vec<usize>,
+}
+
+impl Layout {
+    fn new(cst: &Cst) -> Layout {
+        let source = cst.source();
+        let mut layout = Layout::default();
+
+        for token in cst.comments() {
+            let (line, column) = cst.line_col(token.range.start);
+            let line_start = token.range.start - (column - 1);
+
+            layout.comments.push(Comment {
+                line,
+                text: token.text.trim_end().to_owned(),
+                own_line: source[line_start..token.range.start].trim().is_empty(),
+            });
+        }
+
+        for (i, line) in source.lines().enumerate() {
+            if line.trim().is_empty() {
+                layout.blank_lines.insert(i + 1);
+            }
+        }
+
+        layout.line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        for item in cst.items() {
+            let start = cst.line_col(item.range.start).0;
+            let end = cst
+                .line_col(item.range.end.saturating_sub(1).max(item.range.start))
+                .0;
+            layout.items.insert(item.item, (start, end));
+
+            let (ItemRef::Enum(index), Some(fields)) =
+                (item.item, item.node.child(Rule::fieldlist))
+            else {
+                continue;
+            };
+
+            let entries = fields
+                .child_nodes()
+                .filter(|n| n.kind == NodeKind::Rule(Rule::field));
+
+            for (i, entry) in entries.enumerate() {
+                let line = cst.line_col(entry.range.start).0;
+                layout.enum_entries.insert((index, i), line);
+            }
+        }
+
+        layout
+    }
+
+    /// Line of the last byte in `range` (0 if there is no source)
+    fn end_line(&self, range: &std::ops::Range<usize>) -> usize {
+        if self.line_starts.is_empty() || range.is_empty() {
+            return 0;
+        }
+
+        self.line_starts.partition_point(|&start| start < range.end)
+    }
+}
+
+/// Collects the output lines and writes the comments from the source between them
+struct Printer<'a> {
+    lines: Vec<String>,
+    layout: &'a Layout,
+    next_comment: usize,
+    /// Source line of the last thing written (0 if unknown)
+    last_line: usize,
+}
+
+impl Printer<'_> {
+    fn push(&mut self, indent: usize, text: &str) {
+        self.lines
+            .push(format!("{:indent$}{}", "", text, indent = indent));
+    }
+
+    /// Adds an empty line unless there already is one or it's the start of the file or a block
+    fn blank(&mut self) {
+        match self.lines.last() {
+            Some(line) if !line.is_empty() && !line.ends_with('{') => {
+                self.lines.push(String::new())
+            }
+            _ => (),
+        }
+    }
+
+    /// Checks if the source has an empty line between `from` and `to`
+    fn has_blank(&self, from: usize, to: usize) -> bool {
+        from != 0 && (from + 1..to).any(|l| self.layout.blank_lines.contains(&l))
+    }
+
+    /// Checks if there are comments that hasn't been written before `line`
+    fn has_comments_before(&self, line: usize) -> bool {
+        self.layout
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|c| c.line < line)
+    }
+
+    /// Writes the comments before `line`. Comments on their own line are written with `indent`
+    /// and the other ones are added to the end of the last line
+    fn comments_before(&mut self, line: usize, indent: usize) {
+        while self.has_comments_before(line) {
+            let comment = &self.layout.comments[self.next_comment];
+
+            if comment.own_line || self.lines.iter().all(|l| l.is_empty()) {
+                if self.has_blank(self.last_line, comment.line) {
+                    self.blank();
+                }
+
+                self.push(indent, &comment.text);
+            } else if let Some(last) = self.lines.iter_mut().rev().find(|l| !l.is_empty()) {
+                last.push(' ');
+                last.push_str(&comment.text);
+            }
+
+            self.last_line = comment.line;
+            self.next_comment += 1;
+        }
+    }
+
+    /// Starts an item or field at `line` (0 if unknown). The comments before it are written and
+    /// an empty line is kept if the source has one
+    fn start(&mut self, line: usize, indent: usize) {
+        if line == 0 {
+            return;
+        }
+
+        self.comments_before(line, indent);
+
+        if self.has_blank(self.last_line, line) {
+            self.blank();
+        }
+
+        self.last_line = line;
+    }
+
+    /// Writes `text` that is at `line` in the source (0 if unknown)
+    fn line(&mut self, indent: usize, text: &str, line: usize) {
+        if line != 0 {
+            self.comments_before(line, indent);
+            self.last_line = line;
+        }
+
+        self.push(indent, text);
+    }
+
+    /// Writes `func` that is declared from `line` to `end` in the source. It's written on one line
+    /// unless there are comments inside it. Then each argument is put on its own line so the
+    /// comments stays after the argument they belong to
+    fn function(
+        &mut self,
+        indent: usize,
+        func: &Function,
+        is_callback: bool,
+        line: usize,
+        end: usize,
+    ) {
+        let (head, args, tail) = function_parts(func, is_callback);
+
+        if line != 0 {
+            self.comments_before(line, indent);
+        }
+
+        if line == 0 || end <= line || !self.has_comments_before(end) {
+            let args = args.iter().map(|arg| variable(arg)).collect::<Vec<_>>();
+            let text = format!("{}({}){}", head, args.join(", "), tail);
+            self.line(indent, &text, line);
+            return;
+        }
+
+        self.line(indent, &format!("{}(", head), line);
+
+        for (i, arg) in args.iter().enumerate() {
+            let sep = if i + 1 < args.len() { "," } else { "" };
+            self.line(
+                indent + INDENT,
+                &format!("{}{}", variable(arg), sep),
+                arg.span.line,
+            );
+        }
+
+        self.line(indent, &format!("){}", tail), end);
+    }
+
+    fn doc_comments(&mut self, comments: &[String], indent: usize) {
+        for c in comments {
+            let c = c.trim_end();
+
+            if c.is_empty() {
+                self.push(indent, "///");
+            } else {
+                self.push(indent, &format!("/// {}", c));
+            }
+        }
+    }
+
+    /// Writes `}` at `line` with the comments before it indented as fields
+    fn close(&mut self, line: usize) {
+        if line != 0 {
+            self.comments_before(line, INDENT);
+            self.last_line = line;
+        }
+
+        self.push(0, "}");
+    }
+}
+
+/// Formats `api_def` as def syntax
+pub fn format_api_def(api_def: &ApiDef) -> String {
+    format(api_def, &Layout::default())
+}
+
+/// Formats the def file in `cst` and keeps its comments
+pub fn format_cst(cst: &Cst) -> String {
+    format(cst.api_def(), &Layout::new(cst))
+}
+
+/// Parses `source` and formats it. `filename` is used for errors
+pub fn format_string(source: &str, filename: &str) -> Result<String> {
+    Ok(format_cst(&Cst::parse(source, filename)?))
+}
+
+/// Writes `api_def` as def syntax to `out`
+pub fn write_api_def<W: Write>(out: &mut W, api_def: &ApiDef) -> Result<()> {
+    out.write_all(format_api_def(api_def).as_bytes())?;
+    Ok(())
+}
+
+fn format(api_def: &ApiDef, layout: &Layout) -> String {
+    let mut items = Vec::new();
+
+    if !api_def.namespace.is_empty() {
+        items.push(Item::Namespace(&api_def.namespace));
+    }
+
+    items.extend(api_def.mods.iter().map(|m| Item::Mod(m)));
+    items.extend(api_def.imports.iter().map(Item::Import));
+    items.extend(api_def.consts.iter().map(Item::Const));
+    items.extend(api_def.types.iter().map(Item::Type));
+    items.extend(api_def.callbacks.iter().map(Item::Callback));
+    items.extend(
+        api_def
+            .enums
+            .iter()
+            .enumerate()
+            .map(|(i, e)| Item::Enum(i, e)),
+    );
+    items.extend(
+        api_def
+            .structs
+            .iter()
+            .enumerate()
+            .map(|(i, s)| Item::Struct(i, s)),
+    );
+    items.extend(
+        api_def
+            .unions
+            .iter()
+            .enumerate()
+            .map(|(i, s)| Item::Union(i, s)),
+    );
+
+    // First and last line of the items. The index for mods, imports, consts, types and callbacks
+    // is the position among the items of the same kind
+    let mut counts = [0usize; 6];
+    let mut items = items
+        .into_iter()
+        .map(|item| {
+            let index = item.single_line_kind().map_or(0, |k| {
+                counts[k as usize] += 1;
+                counts[k as usize] - 1
+            });
+
+            let lines = layout
+                .items
+                .get(&item.item_ref(index))
+                .copied()
+                .unwrap_or((item.line(), 0));
+
+            (lines, item)
+        })
+        .collect::<Vec<_>>();
+
+    // Items without a location (created in code) keeps the order above
+    items.sort_by_key(|((start, _), _)| *start);
+
+    let mut printer = Printer {
+        lines: Vec::new(),
+        layout,
+        next_comment: 0,
+        last_line: 0,
+    };
+
+    let mut prev_kind = None;
+
+    for ((start, end), item) in &items {
+        let kind = item.single_line_kind();
+
+        if let Some(prev_kind) = prev_kind {
+            if kind.is_none() || kind != prev_kind || !item.doc_comments().is_empty() {
+                printer.blank();
+            }
+        }
+
+        printer.start(*start, 0);
+        printer.doc_comments(item.doc_comments(), 0);
+
+        let line = item.line();
+
+        match item {
+            Item::Namespace(path) => {
+                printer.line(0, &format!("namespace {}", path.join("::")), line)
+            }
+            Item::Mod(name) => printer.line(0, &format!("mod {}", name), line),
+            Item::Import(i) => printer.line(0, &import(i), line),
+            Item::Const(c) => printer.line(0, &const_value(c), line),
+            Item::Type(t) => printer.line(0, &format!("type {}", variable(&t.var)), line),
+            Item::Callback(f) => printer.function(0, f, true, line, *end),
+            Item::Enum(index, e) => write_enum(&mut printer, *index, e, *end),
+            Item::Struct(_, s) => write_struct(&mut printer, s, "struct", *end),
+            Item::Union(_, s) => write_struct(&mut printer, s, "union", *end),
+        }
+
+        prev_kind = Some(kind);
+    }
+
+    printer.comments_before(usize::MAX, 0);
+
+    let mut output = printer.lines.join("\n");
+    output.push('\n');
+    output
+}
+
+fn import(i: &Import) -> String {
+    match &i.name {
+        Some(name) => format!("use {}::{}", i.def_file, name),
+        None => format!("import \"{}\"", i.path),
+    }
+}
+
+fn const_value(c: &Const) -> String {
+    if c.value.is_empty() {
+        format!("const {} =", c.name)
+    } else {
+        format!("const {} = {}", c.name, c.value)
+    }
+}
+
+fn write_enum(printer: &mut Printer, index: usize, e: &Enum, end: usize) {
+    if !e.flags_name.is_empty() {
+        printer.push(0, &format!("[enum_flags({})]", e.flags_name));
+    }
+
+    if e.entries.is_empty() && !printer.has_comments_before(end) {
+        printer.line(0, &format!("enum {} {{}}", e.name), e.span.line);
+        return;
+    }
+
+    printer.line(0, &format!("enum {} {{", e.name), e.span.line);
+
+    // Value given to entries without an explicit value
+    let mut next_value = 0;
+
+    for (i, entry) in e.entries.iter().enumerate() {
+        let line = printer
+            .layout
+            .enum_entries
+            .get(&(index, i))
+            .copied()
+            .unwrap_or(0);
+
+        printer.start(line.saturating_sub(entry.doc_comments.len()), INDENT);
+        printer.doc_comments(&entry.doc_comments, INDENT);
+
+        let text = if e.enum_type == EnumType::Bitflags {
+            format!("{} = 0x{:x},", entry.name, entry.value)
+        } else if entry.value == next_value {
+            format!("{},", entry.name)
+        } else {
+            format!("{} = {},", entry.name, entry.value)
+        };
+
+        printer.line(INDENT, &text, line);
+        next_value = entry.value.wrapping_add(1);
+    }
+
+    printer.close(end);
+}
+
+fn write_struct(printer: &mut Printer, s: &Struct, keyword: &str, end: usize) {
+    if !s.attributes.is_empty() {
+        printer.push(0, &format!("#[attributes({})]", s.attributes.join(", ")));
+    }
+
+    if !s.traits.is_empty() {
+        printer.push(0, &format!("[traits({})]", s.traits.join(", ")));
+    }
+
+    let mut header = format!("{} {}", keyword, s.name);
+
+    if !s.derives.is_empty() {
+        header.push_str(" : ");
+        header.push_str(&s.derives.join(", "));
+    }
+
+    let mut fields = s
+        .variables
+        .iter()
+        .map(|v| (v.span.line, v.span.column, Field::Variable(v)))
+        .chain(
+            s.functions
+                .iter()
+                .map(|f| (f.span.line, f.span.column, Field::Function(f))),
+        )
+        .collect::<Vec<_>>();
+
+    if fields.is_empty() && !printer.has_comments_before(end) {
+        printer.line(0, &format!("{} {{}}", header), s.span.line);
+        return;
+    }
+
+    fields.sort_by_key(|(line, column, _)| (*line, *column));
+    printer.line(0, &format!("{} {{", header), s.span.line);
+
+    for (line, _, field) in &fields {
+        match field {
+            Field::Variable(v) => {
+                printer.start(line.saturating_sub(v.doc_comments.len()), INDENT);
+                printer.doc_comments(&v.doc_comments, INDENT);
+                printer.line(INDENT, &format!("{},", variable(v)), *line);
+            }
+            Field::Function(f) => {
+                let end = printer.layout.end_line(&f.span.byte_range);
+                printer.start(line.saturating_sub(f.doc_comments.len()), INDENT);
+                printer.doc_comments(&f.doc_comments, INDENT);
+                printer.function(INDENT, f, false, *line, end);
+
+                if let Some(last) = printer.lines.last_mut() {
+                    last.push(',');
+                }
+            }
+        }
+    }
+
+    printer.close(end);
+}
+
+/// `name: type = default`
+fn variable(var: &Variable) -> String {
+    let mut output = format!("{}: {}", var.name, type_name(var, false));
+
+    if !var.default_value.is_empty() {
+        output.push_str(" = ");
+        output.push_str(&var.default_value);
+    }
+
+    output
+}
+
+/// The type of `var` with modifiers and array. `?` is only supported on arrays and return values
+fn type_name(var: &Variable, is_return: bool) -> String {
+    let modifier = match var.type_modifier {
+        TypeModifier::None => "",
+        TypeModifier::ConstPointer => "*const ",
+        TypeModifier::MutPointer => "*",
+        TypeModifier::Reference => "&",
+    };
+
+    // Types in the same namespace as the variable doesn't need to be qualified
+    let name = if var.type_namespace.is_empty() || var.type_namespace == var.namespace {
+        var.type_name.to_owned()
+    } else {
+        qualified_name(&var.type_namespace, &var.type_name)
+    };
+
+    let mut output = match &var.array {
+        None => format!("{}{}", modifier, name),
+        Some(ArrayType::Unsized) => format!("[{}{}]", modifier, name),
+        Some(ArrayType::SizedArray(size)) => {
+            format!("[{}{}; {}]", modifier, name, size)
+        }
+    };
+
+    if var.optional && (is_return || var.array.is_some()) {
+        output.push('?');
+    }
+
+    output
+}
+
+/// Function declaration split up in the part before the arguments, the arguments (without self)
+/// and the part after them. Callbacks are always static so they don't get `[static]`
+fn function_parts(func: &Function, is_callback: bool) -> (String, Vec<&Variable>, String) {
+    let has_self = func
+        .function_args
+        .first()
+        .is_some_and(|arg| arg.vtype == VariableType::SelfType);
+
+    let prefix = match func.func_type {
+        _ if is_callback => "callback ",
+        FunctionType::Regular => "",
+        FunctionType::Static => "[static] ",
+        // Without `[static]` the function would get a self argument when parsed
+        FunctionType::Manual if !has_self => "[static] [manual] ",
+        FunctionType::Manual => "[manual] ",
+        FunctionType::Drop if !has_self => "[static] [drop] ",
+        FunctionType::Drop => "[drop] ",
+    };
+
+    let ownership = match func.ownership {
+        Ownership::Default => "",
+        Ownership::Owned => "[owned] ",
+        Ownership::Borrowed => "[borrowed] ",
+    };
+
+    let args = func
+        .function_args
+        .iter()
+        .filter(|arg| arg.vtype != VariableType::SelfType)
+        .collect::<Vec<_>>();
+
+    let tail = match func.return_val.as_ref() {
+        Some(ret) => format!(" -> {}", type_name(ret, true)),
+        None => String::new(),
+    };
+
+    (format!("{}{}{}", prefix, ownership, func.name), args, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+
+    const MESSY: &str = r#"
+mod render
+use   base::Base
+import  "../render.def"
+const  MAX_SIZE=16
+/// Pixel format
+[enum_flags(Formats)] enum Format { Rgb , Rgba=4, Bgra,
+}
+///Size of an image
+type Size : u64
+callback   Loaded(image: *const Image, user_data:*u8)
+#[attributes(Handle,Drop)] [traits(Debug)]
+struct Image : Base {
+  ///   Create an image
+  [static]create(width:u32,height :u32)->Image?,
+      name: [u8;MAX_SIZE],
+  [manual] get_data() -> [u8]?,
+  [owned]clone()->Image,   [borrowed]  [static] get_default() -> Image,
+  destroy()
+}
+enum Flags { A = 1, B = 0x2, C = 4 }
+union Value { int: i32, float: f32 }
+struct Empty {}
+"#;
+
+    const FORMATTED: &str = r#"mod render
+
+use base::Base
+import "../render.def"
+
+const MAX_SIZE = 16
+
+/// Pixel format
+[enum_flags(Formats)]
+enum Format {
+    Rgb,
+    Rgba = 4,
+    Bgra,
+}
+
+/// Size of an image
+type Size: u64
+
+callback Loaded(image: *const Image, user_data: *u8)
+
+#[attributes(Handle, Drop)]
+[traits(Debug)]
+struct Image : Base {
+    ///   Create an image
+    [static] create(width: u32, height: u32) -> Image?,
+    name: [u8; MAX_SIZE],
+    [manual] get_data() -> [u8]?,
+    [owned] clone() -> Image,
+    [static] [borrowed] get_default() -> Image,
+    destroy(),
+}
+
+enum Flags {
+    A = 0x1,
+    B = 0x2,
+    C = 0x4,
+}
+
+union Value {
+    int: i32,
+    float: f32,
+}
+
+struct Empty {}
+"#;
+
+    #[test]
+    fn test_format() {
+        let output = format_string(MESSY, "image.def").unwrap();
+        assert_eq!(output, FORMATTED);
+        assert_eq!(format_string(&output, "image.def").unwrap(), output);
+
+        let def = ApiParser::parse_string(&output, "image.def").unwrap();
+        assert_eq!(def.structs[0].functions[0].func_type, FunctionType::Static);
+        assert_eq!(def.structs[0].functions[1].function_args.len(), 1);
+        assert_eq!(def.enums[0].entries[2].value, 5);
+        assert_eq!(def.imports[0].path, "base.def");
+        assert_eq!(def.imports[0].name.as_deref(), Some("Base"));
+        assert_eq!(def.imports[1].def_file, "render");
+    }
+
+    const COMMENTED: &str = r#"// Header
+
+mod render
+// Sizes
+const A = 1 // first
+
+
+const B = 2
+/// Image
+#[attributes(Handle)]
+// Before the name
+struct Image { // open
+    // Size
+    width: u32, // in pixels
+
+    height: u32,
+    // Last
+}
+enum Empty {
+    // Nothing yet
+}
+// End
+"#;
+
+    const COMMENTED_FORMATTED: &str = r#"// Header
+
+mod render
+
+// Sizes
+const A = 1 // first
+
+const B = 2
+
+/// Image
+#[attributes(Handle)]
+// Before the name
+struct Image { // open
+    // Size
+    width: u32, // in pixels
+
+    height: u32,
+    // Last
+}
+
+enum Empty {
+    // Nothing yet
+}
+// End
+"#;
+
+    #[test]
+    fn test_format_comments() {
+        let output = format_string(COMMENTED, "image.def").unwrap();
+        assert_eq!(output, COMMENTED_FORMATTED);
+        assert_eq!(format_string(&output, "image.def").unwrap(), output);
+    }
+
+    #[test]
+    fn test_format_argument_comments() {
+        let source = "struct Foo {\n    foo(a: u32, // arg comment\n    b: u32) -> u32, // ret\n    bar(a: u32,\n        b: u32),\n}\n\ncallback Cb(a: u32, // first\n    b: u32)\n";
+        let formatted = "struct Foo {\n    foo(\n        a: u32, // arg comment\n        b: u32\n    ) -> u32, // ret\n    bar(a: u32, b: u32),\n}\n\ncallback Cb(\n    a: u32, // first\n    b: u32\n)\n";
+
+        let output = format_string(source, "foo.def").unwrap();
+        assert_eq!(output, formatted);
+        assert_eq!(format_string(&output, "foo.def").unwrap(), output);
+    }
+}
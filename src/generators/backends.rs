@@ -0,0 +1,364 @@
+//! The generators that are part of apigen as [`Generator`]s so they can be used from a
+//! [`Registry`]
+use crate::api_parser::{ApiDef, Result};
+use crate::generators::c::CGenerator;
+use crate::generators::cpp::CppGenerator;
+use crate::generators::csharp::CSharpGenerator;
+use crate::generators::docs::{DocFormat, DocsGenerator};
+use crate::generators::lua::LuaGenerator;
+use crate::generators::python::PythonGenerator;
+use crate::generators::rust::RustGenerator;
+use crate::generators::rust_ffi::RustFfiGenerator;
+use crate::generators::zig::ZigGenerator;
+use crate::generators::{Generator, GeneratorOptions, OutputSink, Registry};
+
+/// Adds all the built-in generators to `registry`
+pub fn register_builtins(registry: &mut Registry) {
+    registry.register(Box::new(CBackend));
+    registry.register(Box::new(CppBackend));
+    registry.register(Box::new(CSharpBackend));
+    registry.register(Box::new(DocsBackend(DocFormat::Html)));
+    registry.register(Box::new(LuaBackend));
+    registry.register(Box::new(DocsBackend(DocFormat::Markdown)));
+    registry.register(Box::new(PythonBackend));
+    registry.register(Box::new(RustBackend));
+    registry.register(Box::new(RustFfiBackend));
+    #[cfg(feature = "template")]
+    registry.register(Box::new(TemplateBackend));
+    registry.register(Box::new(ZigBackend));
+}
+
+/// Writes one file per def file. `f` writes the data and returns the file name
+fn write_per_file<F>(api_defs: &[ApiDef], sink: &mut dyn OutputSink, f: F) -> Result<()>
+where
+    F: Fn(&mut Vec<u8>, &ApiDef) -> Result<String>,
+{
+    for api_def in api_defs {
+        let mut data = Vec::new();
+        let name = f(&mut data, api_def)?;
+        sink.write_file(&name, &data)?;
+    }
+
+    Ok(())
+}
+
+/// The Rust modules needs a parent module that declares them
+fn write_rust_mod(
+    api_defs: &[ApiDef],
+    options: &GeneratorOptions,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let mut data = Vec::new();
+    RustFfiGenerator::new(&options.c_prefix).generate_mod(&mut data, api_defs)?;
+    sink.write_file("mod.rs", &data)
+}
+
+/// C headers (see [`CGenerator`])
+pub struct CBackend;
+
+impl Generator for CBackend {
+    fn name(&self) -> &str {
+        "c"
+    }
+
+    fn description(&self) -> &str {
+        "C header per def file"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = CGenerator::new(&options.c_prefix);
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(CGenerator::header_name(api_def))
+        })
+    }
+}
+
+/// C++ wrappers (see [`CppGenerator`])
+pub struct CppBackend;
+
+impl Generator for CppBackend {
+    fn name(&self) -> &str {
+        "cpp"
+    }
+
+    fn description(&self) -> &str {
+        "Header-only C++ wrapper per def file"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = CppGenerator::new(&options.c_prefix, &options.namespace());
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(CppGenerator::header_name(api_def))
+        })
+    }
+}
+
+/// C# bindings (see [`CSharpGenerator`])
+pub struct CSharpBackend;
+
+impl Generator for CSharpBackend {
+    fn name(&self) -> &str {
+        "csharp"
+    }
+
+    fn description(&self) -> &str {
+        "C# P/Invoke bindings per def file"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator =
+            CSharpGenerator::new(&options.c_prefix, &options.namespace(), &options.library());
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(CSharpGenerator::file_name(api_def))
+        })
+    }
+}
+
+/// API reference as Markdown or HTML (see [`DocsGenerator`])
+pub struct DocsBackend(pub DocFormat);
+
+impl Generator for DocsBackend {
+    fn name(&self) -> &str {
+        match self.0 {
+            DocFormat::Markdown => "markdown",
+            DocFormat::Html => "html",
+        }
+    }
+
+    fn description(&self) -> &str {
+        match self.0 {
+            DocFormat::Markdown => "Markdown API reference",
+            DocFormat::Html => "HTML API reference",
+        }
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let mut generator = DocsGenerator::new(self.0);
+        generator.show_function_types = !options.hide_function_types;
+        generator.show_optional = !options.hide_optional;
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(generator.file_name(api_def))
+        })?;
+
+        let mut data = Vec::new();
+        generator.generate_index(&mut data, api_defs)?;
+        sink.write_file(&generator.index_name(), &data)
+    }
+}
+
+/// Lua bindings (see [`LuaGenerator`])
+pub struct LuaBackend;
+
+impl Generator for LuaBackend {
+    fn name(&self) -> &str {
+        "lua"
+    }
+
+    fn description(&self) -> &str {
+        "C source per def file that exposes the C API to Lua"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = LuaGenerator::new(&options.c_prefix);
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(LuaGenerator::file_name(api_def))
+        })
+    }
+}
+
+/// Python module for all def files (see [`PythonGenerator`])
+pub struct PythonBackend;
+
+impl Generator for PythonBackend {
+    fn name(&self) -> &str {
+        "python"
+    }
+
+    fn description(&self) -> &str {
+        "Python ctypes module for all def files"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = PythonGenerator::new(&options.c_prefix, &options.namespace());
+        let mut data = Vec::new();
+        generator.generate(&mut data, api_defs)?;
+        sink.write_file(&generator.file_name(), &data)
+    }
+}
+
+/// Safe Rust wrappers (see [`RustGenerator`])
+pub struct RustBackend;
+
+impl Generator for RustBackend {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn description(&self) -> &str {
+        "Safe Rust wrappers on top of the rust-ffi bindings"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = RustGenerator::new(&options.c_prefix, options.ffi_module());
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(RustGenerator::file_name(api_def))
+        })?;
+
+        write_rust_mod(api_defs, options, sink)
+    }
+}
+
+/// Rust FFI bindings (see [`RustFfiGenerator`])
+pub struct RustFfiBackend;
+
+impl Generator for RustFfiBackend {
+    fn name(&self) -> &str {
+        "rust-ffi"
+    }
+
+    fn description(&self) -> &str {
+        "#[repr(C)] Rust bindings per def file"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = RustFfiGenerator::new(&options.c_prefix);
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(RustFfiGenerator::file_name(api_def))
+        })?;
+
+        write_rust_mod(api_defs, options, sink)
+    }
+}
+
+/// Renders the template in the options for each def file (see
+/// [`TemplateGenerator`](crate::generators::template::TemplateGenerator)). Templates that it
+/// includes are loaded from the same directory
+#[cfg(feature = "template")]
+pub struct TemplateBackend;
+
+#[cfg(feature = "template")]
+impl Generator for TemplateBackend {
+    fn name(&self) -> &str {
+        "template"
+    }
+
+    fn description(&self) -> &str {
+        "Renders a minijinja template for each def file"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        use crate::api_parser::ApigenError;
+        use crate::generators::template::TemplateGenerator;
+
+        let path = options
+            .template
+            .as_ref()
+            .ok_or_else(|| ApigenError::Template("no template has been set".to_owned()))?;
+
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("unable to read {}: {}", path.display(), e),
+            )
+        })?;
+
+        let name = path.to_string_lossy();
+        let mut generator = TemplateGenerator::new(&options.c_prefix);
+        generator.add_template(&name, &source)?;
+
+        if let Some(dir) = path.parent() {
+            generator.set_template_dir(dir);
+        }
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, &name, api_def, api_defs)?;
+            Ok(TemplateGenerator::file_name(&name, api_def))
+        })
+    }
+}
+
+/// Zig bindings (see [`ZigGenerator`])
+pub struct ZigBackend;
+
+impl Generator for ZigBackend {
+    fn name(&self) -> &str {
+        "zig"
+    }
+
+    fn description(&self) -> &str {
+        "Zig bindings per def file"
+    }
+
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()> {
+        let generator = ZigGenerator::new(&options.c_prefix);
+
+        write_per_file(api_defs, sink, |data, api_def| {
+            generator.generate(data, api_def, api_defs)?;
+            Ok(ZigGenerator::file_name(api_def))
+        })
+    }
+}
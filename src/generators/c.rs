@@ -0,0 +1,495 @@
+//! Generates a C header for each def file.
+//!
+//! All names are prefixed with the C prefix followed by the namespace (`FL_render_gl_Texture` for
+//! `Texture` in `render::gl`). Structs with the `Handle` attribute are opaque and used through
+//! pointers. Functions are declared as `<prefix><Struct>_<function>` and each struct
+//! with functions also gets a function table (`<prefix><Struct>Funcs`) with pointers to them.
+use crate::api_parser::{c_namespace_prefix, ApiDef, Enum, EnumType, Function, Result, Struct};
+use crate::generators::util::dependencies;
+use crate::{get_derived_structs, write_c_commments};
+use heck::ToShoutySnakeCase;
+use std::io::Write;
+
+/// Writes C headers
+#[derive(Debug, Clone, Default)]
+pub struct CGenerator {
+    /// Added in front of all types and functions
+    pub c_prefix: String,
+}
+
+impl CGenerator {
+    pub fn new(c_prefix: &str) -> CGenerator {
+        CGenerator {
+            c_prefix: c_prefix.to_owned(),
+        }
+    }
+
+    /// Name of the header generated for `api_def`
+    pub fn header_name(api_def: &ApiDef) -> String {
+        format!("{}.h", api_def.qualified_filename())
+    }
+
+    /// Writes the header for `api_def`. `api_defs` is all the def files and is used to look up
+    /// structs used with derive. The headers for the files `api_def` imports or uses types and
+    /// consts from are included.
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let guard =
+            format!("{}{}_H", self.c_prefix, api_def.qualified_filename()).to_shouty_snake_case();
+        let prefix = self.prefix(&api_def.namespace);
+
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+        writeln!(out)?;
+        writeln!(out, "#ifndef {}", guard)?;
+        writeln!(out, "#define {}", guard)?;
+        writeln!(out)?;
+        writeln!(out, "#include <stdint.h>")?;
+        writeln!(out, "#include <stdbool.h>")?;
+
+        for file in dependencies(api_def, api_defs, |_| true) {
+            writeln!(out, "#include \"{}.h\"", file)?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "#ifdef __cplusplus")?;
+        writeln!(out, "extern \"C\" {{")?;
+        writeln!(out, "#endif")?;
+
+        self.write_consts(out, api_def)?;
+        self.write_forward_declarations(out, api_def)?;
+
+        for t in &api_def.types {
+            writeln!(out)?;
+            write_c_commments(out, &t.var.doc_comments, 0)?;
+            writeln!(
+                out,
+                "typedef {} {}{};",
+                t.var.c_type("", &self.c_prefix),
+                prefix,
+                t.var.name
+            )?;
+        }
+
+        for e in &api_def.enums {
+            self.write_enum(out, e)?;
+        }
+
+        for c in &api_def.callbacks {
+            writeln!(out)?;
+            write_c_commments(out, &c.doc_comments, 0)?;
+            writeln!(
+                out,
+                "typedef {} (*{}{})({});",
+                c.c_return_type(&self.c_prefix),
+                prefix,
+                c.name,
+                self.c_arguments(c, "")
+            )?;
+        }
+
+        for s in api_def.structs.iter().filter(|s| !is_opaque(s)) {
+            self.write_struct(out, "struct", s, api_defs)?;
+        }
+
+        for u in &api_def.unions {
+            self.write_struct(out, "union", u, api_defs)?;
+        }
+
+        for s in api_def.structs.iter().filter(|s| !s.functions.is_empty()) {
+            self.write_functions(out, s)?;
+        }
+
+        api_def.write_c_manual(out, &prefix)?;
+
+        writeln!(out)?;
+        writeln!(out, "#ifdef __cplusplus")?;
+        writeln!(out, "}}")?;
+        writeln!(out, "#endif")?;
+        writeln!(out)?;
+        writeln!(out, "#endif // {}", guard)?;
+
+        Ok(())
+    }
+
+    /// Consts are written as defines. `_MANUAL_C` is spliced in by `write_c_manual` instead
+    fn write_consts<W: Write>(&self, out: &mut W, api_def: &ApiDef) -> Result<()> {
+        let consts = api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+            .collect::<Vec<_>>();
+
+        if consts.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(out)?;
+
+        for c in consts {
+            write_c_commments(out, &c.doc_comments, 0)?;
+            writeln!(
+                out,
+                "#define {}{} {}",
+                self.prefix(&c.namespace),
+                c.name,
+                c.value
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Typedefs for all structs and unions so they can be used before they are declared
+    fn write_forward_declarations<W: Write>(&self, out: &mut W, api_def: &ApiDef) -> Result<()> {
+        let structs = api_def.structs.iter().map(|s| ("struct", s));
+        let unions = api_def.unions.iter().map(|u| ("union", u));
+
+        let mut first = true;
+
+        for (kind, s) in structs.chain(unions) {
+            if first {
+                writeln!(out)?;
+                first = false;
+            }
+
+            let name = format!("{}{}", self.prefix(&s.namespace), s.name);
+            writeln!(out, "typedef {} {} {};", kind, name, name)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_enum<W: Write>(&self, out: &mut W, e: &Enum) -> Result<()> {
+        let prefix = self.prefix(&e.namespace);
+        let name = format!("{}{}", prefix, e.name);
+
+        writeln!(out)?;
+        write_c_commments(out, &e.doc_comments, 0)?;
+        writeln!(out, "typedef enum {} {{", name)?;
+
+        for entry in &e.entries {
+            write_c_commments(out, &entry.doc_comments, 4)?;
+
+            match e.enum_type {
+                EnumType::Regular => {
+                    writeln!(out, "    {}_{} = {},", name, entry.name, entry.value)?
+                }
+                EnumType::Bitflags => {
+                    writeln!(out, "    {}_{} = 0x{:x},", name, entry.name, entry.value)?
+                }
+            }
+        }
+
+        writeln!(out, "}} {};", name)?;
+
+        // The flags type can hold any combination of the values so it can't be the enum itself
+        if !e.flags_name.is_empty() {
+            writeln!(out)?;
+            let flags_type = if e.is_64bit() { "uint64_t" } else { "uint32_t" };
+            writeln!(out, "typedef {} {}{};", flags_type, prefix, e.flags_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a struct or union. Variables from derived structs are added first
+    fn write_struct<W: Write>(
+        &self,
+        out: &mut W,
+        kind: &str,
+        s: &Struct,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        writeln!(out)?;
+        write_c_commments(out, &s.doc_comments, 0)?;
+        writeln!(out, "{} {}{} {{", kind, self.prefix(&s.namespace), s.name)?;
+
+        for derived in get_derived_structs(api_defs, s) {
+            writeln!(out, "    // From {}", derived.name)?;
+
+            for var in &derived.variables {
+                writeln!(out, "{}", var.c_field(&self.c_prefix))?;
+            }
+        }
+
+        for var in &s.variables {
+            write_c_commments(out, &var.doc_comments, 4)?;
+            writeln!(out, "{}", var.c_field(&self.c_prefix))?;
+        }
+
+        writeln!(out, "}};")?;
+
+        Ok(())
+    }
+
+    /// Writes function declarations and the function table for a struct
+    fn write_functions<W: Write>(&self, out: &mut W, s: &Struct) -> Result<()> {
+        writeln!(out)?;
+
+        for func in &s.functions {
+            write_c_commments(out, &func.doc_comments, 0)?;
+            writeln!(
+                out,
+                "{} {}({});",
+                func.c_return_type(&self.c_prefix),
+                func.get_c_name(&s.name, &self.c_prefix),
+                self.c_arguments(func, &s.name)
+            )?;
+        }
+
+        let prefix = self.prefix(&s.namespace);
+
+        writeln!(out)?;
+        writeln!(out, "typedef struct {}{}Funcs {{", prefix, s.name)?;
+
+        for func in &s.functions {
+            writeln!(
+                out,
+                "    {} (*{})({});",
+                func.c_return_type(&self.c_prefix),
+                func.name,
+                self.c_arguments(func, &s.name)
+            )?;
+        }
+
+        writeln!(out, "}} {}{}Funcs;", prefix, s.name)?;
+
+        Ok(())
+    }
+
+    /// Prefix for the names declared in `namespace`
+    fn prefix(&self, namespace: &[String]) -> String {
+        c_namespace_prefix(&self.c_prefix, namespace)
+    }
+
+    /// Arguments for a function. Functions without any arguments takes `void`
+    fn c_arguments(&self, func: &Function, self_name: &str) -> String {
+        if func.function_args.is_empty() {
+            "void".to_owned()
+        } else {
+            func.c_args(self_name, &self.c_prefix).join(", ")
+        }
+    }
+}
+
+/// Handles and structs without any data are only declared
+pub(crate) fn is_opaque(s: &Struct) -> bool {
+    s.has_attribute("Handle") || (s.variables.is_empty() && s.derives.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::{test_util, GeneratorOptions, MemorySink, Registry};
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+const MAX_NAME = 32
+
+/// Info about an image
+struct ImageInfo {
+    /// width of the image
+    width: u32,
+    height: u32,
+    name: [u8; MAX_NAME],
+    format: Format,
+}
+
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+[enum_flags(LoadFlags)]
+enum LoadFlag {
+    Cache = 1,
+    Mips = 2,
+    Srgb = 4,
+}
+
+type ImageId: u64
+
+callback LoadCallback(image: Image, user_data: *void)
+
+#[attributes(Handle, Drop)]
+struct Image {
+    [static] create_from_file(filename: String, flags: LoadFlags) -> Image?,
+    [static] create_from_memory(name: String, data: [u8]) -> Image?,
+    get_info() -> *const ImageInfo?,
+    destroy(),
+}
+
+union Color {
+    rgba: u32,
+    data: [u8; 4],
+}
+
+const _MANUAL_C = "
+#define {CPrefix}IMAGE_MANUAL 1
+"
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        CGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_header() {
+        let h = generate();
+
+        assert!(h.contains("#ifndef FL_IMAGE_H\n#define FL_IMAGE_H"));
+        assert!(h.contains("#include <stdint.h>"));
+        assert!(h.contains("#define FL_MAX_NAME 32"));
+        assert!(h.contains("typedef struct FL_Image FL_Image;"));
+        assert!(h.contains("typedef union FL_Color FL_Color;"));
+        assert!(h.contains("typedef uint64_t FL_ImageId;"));
+        assert!(h.contains("    FL_Format_Rgba = 1,"));
+        assert!(h.contains("    FL_LoadFlag_Srgb = 0x4,"));
+        assert!(h.contains("typedef uint32_t FL_LoadFlags;"));
+        assert!(h.contains("typedef void (*FL_LoadCallback)(FL_Image* image, void* user_data);"));
+        assert!(h.contains("// Info about an image\nstruct FL_ImageInfo {\n    // width of the image\n    uint32_t width;"));
+        assert!(h.contains("    uint8_t name[FL_MAX_NAME];"));
+        assert!(h.contains("    FL_Format format;"));
+        assert!(!h.contains("struct FL_Image {"));
+        assert!(h.contains(
+            "FL_Image* FL_Image_create_from_memory(const char* name, const uint8_t* data, uint64_t data_size);"
+        ));
+        assert!(h.contains("const FL_ImageInfo* FL_Image_get_info(struct FL_Image* self);"));
+        assert!(h.contains("void FL_Image_destroy(struct FL_Image* self);"));
+        assert!(h.contains("typedef struct FL_ImageFuncs {\n    FL_Image* (*create_from_file)(const char* filename, FL_LoadFlags flags);"));
+        assert!(h.contains("#define FL_IMAGE_MANUAL 1"));
+        assert!(h.ends_with("#endif // FL_IMAGE_H\n"));
+    }
+
+    const EDGE_CASES: &str = r#"
+const COUNT = 4
+
+[enum_flags(BigFlags)]
+enum BigFlag {
+    Low = 1,
+    High = 0x100000000,
+}
+
+struct Buffer {
+    fixed: [u8; 16],
+    counted: [f32; COUNT],
+    data: [u8],
+    flags: BigFlags,
+}
+
+#[attributes(Handle, Drop)]
+struct Device {
+    [static] create() -> Device?,
+    get_buffer() -> *const Buffer,
+    set_flags(flags: BigFlags) -> BigFlag,
+    upload(data: [u8], fixed: [f32; 4], other: Device),
+    share(devices: [Device]),
+    destroy(),
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("edge.def", EDGE_CASES)]);
+
+        let mut out = Vec::new();
+        CGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        let h = String::from_utf8(out).unwrap();
+
+        assert!(h.contains("    FL_BigFlag_High = 0x100000000,"));
+        assert!(h.contains("typedef uint64_t FL_BigFlags;"));
+        assert!(h.contains("    uint8_t fixed[16];\n    float counted[FL_COUNT];\n    uint8_t* data;\n    uint64_t data_size;"));
+        assert!(h.contains("void FL_Device_upload(struct FL_Device* self, const uint8_t* data, uint64_t data_size, float fixed[4], FL_Device* other);"));
+        // Unsized arrays are only read so the elements are const (and not what they point to)
+        assert!(h.contains("void FL_Device_share(struct FL_Device* self, FL_Device* const* devices, uint64_t devices_size);"));
+
+        let main = "#include \"edge.h\"\n_Static_assert(sizeof(FL_BigFlags) == 8, \"flags\");\nint main(void) { return 0; }\n";
+        test_util::compiles(
+            "c",
+            &[("edge.h", h.as_bytes()), ("main.c", main.as_bytes())],
+            "cc",
+            &[
+                "-std=c11",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-fsyntax-only",
+                "main.c",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        // Types and consts from other files can be used without importing them
+        let defs = test_util::parse(&[
+            (
+                "image.def",
+                "struct ImageInfo : Base {\n    name: [u8; MAX_NAME],\n    size: Size,\n}\n\n#[attributes(Handle, Drop)]\nstruct Image {\n    [static] create(size: Size) -> Image?,\n    get_device() -> Device,\n    destroy(),\n}",
+            ),
+            (
+                "types.def",
+                "const MAX_NAME = 64\nstruct Size { width: u32, height: u32 }\nenum Format { Rgb, Rgba }",
+            ),
+            ("base.def", "struct Base { format: Format }"),
+            (
+                "device.def",
+                "#[attributes(Handle)]\nstruct Device { get_size() -> Size }",
+            ),
+        ]);
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let mut sink = MemorySink::default();
+        Registry::with_builtins()
+            .get("c")
+            .unwrap()
+            .generate(&defs, &options, &mut sink)
+            .unwrap();
+
+        let h = String::from_utf8(sink.files["image.h"].clone()).unwrap();
+        assert!(h.contains("#include <stdbool.h>\n#include \"device.h\"\n#include \"types.h\"\n"));
+
+        let main = "#include \"image.h\"\nint main(void) { return 0; }\n";
+        let mut files = sink
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect::<Vec<_>>();
+        files.push(("main.c", main.as_bytes()));
+
+        test_util::compiles(
+            "c_dependencies",
+            &files,
+            "cc",
+            &[
+                "-std=c11",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-fsyntax-only",
+                "main.c",
+            ],
+        );
+    }
+}
@@ -0,0 +1,865 @@
+//! Generates a header-only C++ wrapper on top of the header from the `c` generator.
+//!
+//! Structs with the `Handle` attribute becomes classes that own the handle (RAII) when there is a
+//! `[drop]` function (or `destroy` if the struct has the `Drop` attribute). `String` is passed as
+//! `std::string_view`, unsized arrays as `std::span`, optional handles and strings are returned as
+//! `std::optional` and regular enums become `enum class`. Bitflags are `enum class` too but with
+//! the bitwise operators overloaded. Requires C++20 (for `std::span`).
+//!
+//! The namespace of a def file is put inside the namespace of the generator, so `render::gl` is
+//! written to `fl::render::gl` with the namespace `fl`.
+use crate::api_parser::{
+    c_const_pointer, c_namespace_prefix, ApiDef, ArrayType, Enum, EnumType, Function, Result,
+    Struct, TypeModifier, Variable, VariableType,
+};
+use crate::generators::util::dependencies;
+use crate::write_c_commments;
+use std::io::Write;
+
+/// Keywords in C++ that aren't keywords in C. Names that match these gets a `_` suffix
+const CPP_KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "asm",
+    "bool",
+    "catch",
+    "char8_t",
+    "class",
+    "concept",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "decltype",
+    "delete",
+    "explicit",
+    "export",
+    "false",
+    "friend",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "nullptr",
+    "operator",
+    "or",
+    "private",
+    "protected",
+    "public",
+    "requires",
+    "static_assert",
+    "template",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeid",
+    "typename",
+    "using",
+    "virtual",
+    "xor",
+];
+
+/// Writes C++ headers
+#[derive(Debug, Clone, Default)]
+pub struct CppGenerator {
+    /// Has to match the prefix used for the C header
+    pub c_prefix: String,
+    /// Namespace for all the generated code. Has to be set if `c_prefix` is empty as the names
+    /// would clash with the C names otherwise
+    pub namespace: String,
+}
+
+/// Code for calling a C function
+#[derive(Default)]
+struct Call {
+    /// Parameters of the C++ function (excluding self)
+    params: Vec<String>,
+    /// Arguments to the C function
+    args: Vec<String>,
+}
+
+fn cpp_name(name: &str) -> String {
+    if CPP_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Checks if `var` is used by value (no pointer or array)
+fn is_plain(var: &Variable) -> bool {
+    var.type_modifier == TypeModifier::None && var.array.is_none()
+}
+
+/// Handles and strings are wrapped and everything else is a pointer or a value
+fn is_wrapped(var: &Variable) -> bool {
+    is_plain(var) && matches!(var.vtype, VariableType::Handle | VariableType::Str)
+}
+
+impl CppGenerator {
+    pub fn new(c_prefix: &str, namespace: &str) -> CppGenerator {
+        CppGenerator {
+            c_prefix: c_prefix.to_owned(),
+            namespace: namespace.to_owned(),
+        }
+    }
+
+    /// Name of the header generated for `api_def`
+    pub fn header_name(api_def: &ApiDef) -> String {
+        format!("{}.hpp", api_def.qualified_filename())
+    }
+
+    /// Writes the header for `api_def`. The C header for `api_def` and the C++ headers for the
+    /// other def files it uses types from are included. `api_defs` is all the def files.
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+        writeln!(out)?;
+        writeln!(out, "#pragma once")?;
+        writeln!(out)?;
+        writeln!(out, "#include \"{}.h\"", api_def.qualified_filename())?;
+
+        for module in dependencies(api_def, api_defs, |_| true) {
+            writeln!(out, "#include \"{}.hpp\"", module)?;
+        }
+
+        writeln!(out)?;
+
+        for header in [
+            "cstdint",
+            "optional",
+            "span",
+            "string",
+            "string_view",
+            "utility",
+        ] {
+            writeln!(out, "#include <{}>", header)?;
+        }
+
+        let namespace = self.full_namespace(&api_def.namespace);
+        let prefix = c_namespace_prefix(&self.c_prefix, &api_def.namespace);
+
+        if !namespace.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "namespace {} {{", namespace)?;
+        }
+
+        for t in &api_def.types {
+            writeln!(out)?;
+            write_c_commments(out, &t.var.doc_comments, 0)?;
+            writeln!(out, "using {} = ::{}{};", t.var.name, prefix, t.var.name)?;
+        }
+
+        for e in &api_def.enums {
+            self.write_enum(out, e)?;
+        }
+
+        let handles = api_def
+            .structs
+            .iter()
+            .filter(|s| s.has_attribute("Handle"))
+            .collect::<Vec<_>>();
+
+        let data_types = api_def
+            .structs
+            .iter()
+            .filter(|s| !s.has_attribute("Handle"))
+            .chain(api_def.unions.iter());
+
+        for s in data_types {
+            writeln!(out)?;
+            write_c_commments(out, &s.doc_comments, 0)?;
+            writeln!(out, "using {} = ::{}{};", s.name, prefix, s.name)?;
+        }
+
+        for c in &api_def.callbacks {
+            writeln!(out)?;
+            write_c_commments(out, &c.doc_comments, 0)?;
+            writeln!(out, "using {} = ::{}{};", c.name, prefix, c.name)?;
+        }
+
+        // Classes are declared first and the functions are defined after all of them so classes
+        // can use each other
+        if !handles.is_empty() {
+            writeln!(out)?;
+
+            for s in &handles {
+                writeln!(out, "class {};", s.name)?;
+            }
+        }
+
+        for s in &handles {
+            self.write_class(out, s)?;
+        }
+
+        for s in &handles {
+            for func in s.functions.iter() {
+                if !s.is_drop_function(func) {
+                    self.write_function(out, s, func)?;
+                }
+            }
+        }
+
+        if !namespace.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "}} // namespace {}", namespace)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_enum<W: Write>(&self, out: &mut W, e: &Enum) -> Result<()> {
+        let bitflags = e.enum_type == EnumType::Bitflags;
+
+        writeln!(out)?;
+        write_c_commments(out, &e.doc_comments, 0)?;
+
+        // Same size as the C enum (and flags type) which is 64 bit when the values doesn't fit in
+        // 32 bits
+        let underlying = if e.is_64bit() { "uint64_t" } else { "uint32_t" };
+
+        if bitflags || e.is_64bit() {
+            writeln!(out, "enum class {} : {} {{", e.name, underlying)?;
+        } else {
+            writeln!(out, "enum class {} {{", e.name)?;
+        }
+
+        for entry in &e.entries {
+            write_c_commments(out, &entry.doc_comments, 4)?;
+
+            if bitflags {
+                writeln!(out, "    {} = 0x{:x},", cpp_name(&entry.name), entry.value)?;
+            } else {
+                writeln!(out, "    {} = {},", cpp_name(&entry.name), entry.value)?;
+            }
+        }
+
+        writeln!(out, "}};")?;
+
+        if !bitflags {
+            return Ok(());
+        }
+
+        let name = &e.name;
+
+        writeln!(out)?;
+        writeln!(out, "constexpr {} operator~({} a) {{", name, name)?;
+        writeln!(
+            out,
+            "    return static_cast<{}>(~static_cast<{}>(a));",
+            name, underlying
+        )?;
+        writeln!(out, "}}")?;
+
+        for op in ["|", "&", "^"] {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "constexpr {} operator{}({} a, {} b) {{",
+                name, op, name, name
+            )?;
+            writeln!(
+                out,
+                "    return static_cast<{}>(static_cast<{}>(a) {} static_cast<{}>(b));",
+                name, underlying, op, underlying
+            )?;
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "constexpr {}& operator{}=({}& a, {} b) {{",
+                name, op, name, name
+            )?;
+            writeln!(out, "    return a = a {} b;", op)?;
+            writeln!(out, "}}")?;
+        }
+
+        if !e.flags_name.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "using {} = {};", e.flags_name, name)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_class<W: Write>(&self, out: &mut W, s: &Struct) -> Result<()> {
+        let name = &s.name;
+        let c_type = format!(
+            "::{}{}",
+            c_namespace_prefix(&self.c_prefix, &s.namespace),
+            name
+        );
+        let drop_func = s.get_drop_function();
+
+        writeln!(out)?;
+        write_c_commments(out, &s.doc_comments, 0)?;
+        writeln!(out, "class {} {{", name)?;
+        writeln!(out, "public:")?;
+        writeln!(out, "    {}() = default;", name)?;
+        writeln!(
+            out,
+            "    explicit {}({}* handle) : m_handle(handle) {{}}",
+            name, c_type
+        )?;
+
+        // Only classes that destroys the handle owns it. Others can be copied
+        if let Some(func) = drop_func {
+            writeln!(out)?;
+            writeln!(out, "    ~{}() {{", name)?;
+            writeln!(out, "        if (m_handle) {{")?;
+            writeln!(
+                out,
+                "            {}(m_handle);",
+                func.get_c_name(name, &self.c_prefix)
+            )?;
+            writeln!(out, "        }}")?;
+            writeln!(out, "    }}")?;
+            writeln!(out)?;
+            writeln!(out, "    {}(const {}&) = delete;", name, name)?;
+            writeln!(out, "    {}& operator=(const {}&) = delete;", name, name)?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "    {}({}&& other) noexcept : m_handle(std::exchange(other.m_handle, nullptr)) {{}}",
+                name, name
+            )?;
+            writeln!(out)?;
+            writeln!(out, "    {}& operator=({}&& other) noexcept {{", name, name)?;
+            writeln!(out, "        std::swap(m_handle, other.m_handle);")?;
+            writeln!(out, "        return *this;")?;
+            writeln!(out, "    }}")?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    // Returns the C handle")?;
+        writeln!(out, "    {}* get() const {{ return m_handle; }}", c_type)?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "    // Returns the C handle and gives up the ownership of it"
+        )?;
+        writeln!(
+            out,
+            "    {}* release() {{ return std::exchange(m_handle, nullptr); }}",
+            c_type
+        )?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "    explicit operator bool() const {{ return m_handle != nullptr; }}"
+        )?;
+
+        for func in &s.functions {
+            if drop_func.is_some_and(|d| std::ptr::eq(d, func)) {
+                continue;
+            }
+
+            let (call, ret, is_const) = self.signature(func);
+
+            writeln!(out)?;
+            write_c_commments(out, &func.doc_comments, 4)?;
+            writeln!(
+                out,
+                "    {}{} {}({}){};",
+                if Self::has_self(func) { "" } else { "static " },
+                ret.0,
+                cpp_name(&func.name),
+                call.params.join(", "),
+                if is_const { " const" } else { "" }
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "private:")?;
+        writeln!(out, "    {}* m_handle = nullptr;", c_type)?;
+        writeln!(out, "}};")?;
+
+        Ok(())
+    }
+
+    /// The generator namespace followed by `namespace`
+    fn full_namespace(&self, namespace: &[String]) -> String {
+        let mut path = vec![self.namespace.as_str()];
+        path.extend(namespace.iter().map(String::as_str));
+        path.retain(|p| !p.is_empty());
+        path.join("::")
+    }
+
+    /// Name of the type of `var`. Types from other namespaces uses the full name
+    fn type_name(&self, var: &Variable) -> String {
+        if var.type_namespace == var.namespace {
+            return var.type_name.to_owned();
+        }
+
+        match self.full_namespace(&var.type_namespace) {
+            namespace if namespace.is_empty() => format!("::{}", var.type_name),
+            namespace => format!("::{}::{}", namespace, var.type_name),
+        }
+    }
+
+    fn has_self(func: &Function) -> bool {
+        func.function_args
+            .iter()
+            .any(|arg| arg.vtype == VariableType::SelfType)
+    }
+
+    /// Returns the call, the return type + conversion and if the method is const
+    fn signature(&self, func: &Function) -> (Call, (String, String), bool) {
+        let mut call = Call::default();
+
+        for arg in &func.function_args {
+            self.argument(&mut call, arg);
+        }
+
+        let ret = self.return_value(func);
+        let mut_ret = func
+            .return_val
+            .as_ref()
+            .is_some_and(|r| r.type_modifier == TypeModifier::MutPointer);
+        let getter = ["get_", "is_", "has_"]
+            .iter()
+            .any(|p| func.name.starts_with(p));
+
+        let is_const = Self::has_self(func) && getter && !mut_ret;
+
+        (call, ret, is_const)
+    }
+
+    /// C++ type without pointers
+    fn value_type(&self, var: &Variable) -> String {
+        match var.vtype {
+            VariableType::Primitive => var.get_c_primitive_type().into_owned(),
+            VariableType::Regular
+            | VariableType::Enum
+            | VariableType::Alias
+            | VariableType::Callback => self.type_name(var),
+            VariableType::Str => "std::string_view".to_owned(),
+            VariableType::Handle => self.type_name(var),
+            VariableType::SelfType | VariableType::None => "void".to_owned(),
+        }
+    }
+
+    /// C++ type for values that are passed to C as is (except for enums which are casted)
+    fn cpp_type(&self, var: &Variable) -> String {
+        if !is_plain(var) && matches!(var.vtype, VariableType::Handle | VariableType::Str) {
+            return self.c_type(var);
+        }
+
+        let t = self.value_type(var);
+
+        match var.type_modifier {
+            TypeModifier::None => t,
+            TypeModifier::ConstPointer | TypeModifier::Reference => format!("const {}*", t),
+            TypeModifier::MutPointer => format!("{}*", t),
+        }
+    }
+
+    /// Arrays of handles and strings uses the C types
+    fn element_type(&self, var: &Variable) -> String {
+        match var.vtype {
+            VariableType::Handle | VariableType::Str => self.c_type(var),
+            _ => self.cpp_type(var),
+        }
+    }
+
+    fn c_type(&self, var: &Variable) -> String {
+        var.c_type("", &self.c_prefix)
+    }
+
+    fn argument(&self, call: &mut Call, arg: &Variable) {
+        let name = cpp_name(&arg.name);
+
+        match (&arg.array, &arg.vtype) {
+            (_, VariableType::SelfType) => call.args.push("m_handle".to_owned()),
+            (Some(ArrayType::Unsized), _) => {
+                let element = Variable {
+                    array: None,
+                    ..arg.clone()
+                };
+                let element_type = self.element_type(&element);
+
+                // `const T*` would make the pointed to value const and not the pointer
+                if element_type.ends_with('*') {
+                    call.params
+                        .push(format!("std::span<{} const> {}", element_type, name));
+                } else {
+                    call.params
+                        .push(format!("std::span<const {}> {}", element_type, name));
+                }
+
+                let c_type = self.c_type(&element);
+
+                if c_type == element_type {
+                    call.args.push(format!("{}.data()", name));
+                } else {
+                    call.args.push(format!(
+                        "reinterpret_cast<{}>({}.data())",
+                        c_const_pointer(&c_type),
+                        name
+                    ));
+                }
+                call.args.push(format!("{}.size()", name));
+            }
+            (Some(ArrayType::SizedArray(size)), _) => {
+                let element = Variable {
+                    array: None,
+                    ..arg.clone()
+                };
+                let size = if size.starts_with(|c: char| c.is_ascii_digit()) {
+                    size.to_owned()
+                } else {
+                    format!(
+                        "{}{}",
+                        c_namespace_prefix(&self.c_prefix, &arg.namespace),
+                        size
+                    )
+                };
+                call.params.push(format!(
+                    "std::span<{}, {}> {}",
+                    self.element_type(&element),
+                    size,
+                    name
+                ));
+                let c_type = self.c_type(&element);
+
+                if c_type == self.element_type(&element) {
+                    call.args.push(format!("{}.data()", name));
+                } else {
+                    call.args
+                        .push(format!("reinterpret_cast<{}*>({}.data())", c_type, name));
+                }
+            }
+            (None, VariableType::Str) if is_wrapped(arg) => {
+                call.params.push(format!("std::string_view {}", name));
+                call.args.push(format!("std::string({}).c_str()", name));
+            }
+            (None, VariableType::Handle) if is_wrapped(arg) => {
+                call.params
+                    .push(format!("const {}& {}", self.type_name(arg), name));
+                call.args.push(format!("{}.get()", name));
+            }
+            (None, VariableType::Enum) => {
+                call.params.push(format!("{} {}", self.cpp_type(arg), name));
+
+                if is_plain(arg) {
+                    call.args
+                        .push(format!("static_cast<{}>({})", self.c_type(arg), name));
+                } else {
+                    call.args
+                        .push(format!("reinterpret_cast<{}>({})", self.c_type(arg), name));
+                }
+            }
+            _ => {
+                call.params.push(format!("{} {}", self.cpp_type(arg), name));
+                call.args.push(name);
+            }
+        }
+    }
+
+    /// Returns the return type and a statement where `{call}` is replaced with the C call
+    fn return_value(&self, func: &Function) -> (String, String) {
+        let ret = match func.return_val.as_ref() {
+            Some(ret)
+                if !(ret.vtype == VariableType::Primitive
+                    && ret.type_name == "void"
+                    && ret.type_modifier == TypeModifier::None) =>
+            {
+                ret
+            }
+            _ => return ("void".to_owned(), "{call};".to_owned()),
+        };
+
+        match ret.vtype {
+            VariableType::Handle if is_wrapped(ret) => {
+                let name = self.type_name(ret);
+
+                if ret.optional {
+                    let stmt = format!(
+                        "if (auto ret = {{call}}) {{\n    return {}(ret);\n}}\nreturn std::nullopt;",
+                        name
+                    );
+                    (format!("std::optional<{}>", name), stmt)
+                } else {
+                    let stmt = format!("return {}({{call}});", name);
+                    (name, stmt)
+                }
+            }
+            VariableType::Str if is_wrapped(ret) => {
+                if ret.optional {
+                    (
+                        "std::optional<std::string_view>".to_owned(),
+                        "if (auto ret = {call}) {\n    return std::string_view(ret);\n}\nreturn std::nullopt;"
+                            .to_owned(),
+                    )
+                } else {
+                    (
+                        "std::string_view".to_owned(),
+                        "auto ret = {call};\nreturn ret ? std::string_view(ret) : std::string_view();"
+                            .to_owned(),
+                    )
+                }
+            }
+            VariableType::Enum if ret.array.is_none() => {
+                let cast = if is_plain(ret) {
+                    "static_cast"
+                } else {
+                    "reinterpret_cast"
+                };
+                let t = self.cpp_type(ret);
+                let stmt = format!("return {}<{}>({{call}});", cast, t);
+                (t, stmt)
+            }
+            _ => (self.cpp_type(ret), "return {call};".to_owned()),
+        }
+    }
+
+    fn write_function<W: Write>(&self, out: &mut W, s: &Struct, func: &Function) -> Result<()> {
+        let (call, (ret_type, stmt), is_const) = self.signature(func);
+
+        let c_call = format!(
+            "{}({})",
+            func.get_c_name(&s.name, &self.c_prefix),
+            call.args.join(", ")
+        );
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "inline {} {}::{}({}){} {{",
+            ret_type,
+            s.name,
+            cpp_name(&func.name),
+            call.params.join(", "),
+            if is_const { " const" } else { "" }
+        )?;
+
+        for line in stmt.replace("{call}", &c_call).lines() {
+            writeln!(out, "    {}", line)?;
+        }
+
+        writeln!(out, "}}")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::{test_util, GeneratorOptions, MemorySink, Registry};
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+[enum_flags(LoadFlags)]
+enum LoadFlag {
+    Cache = 1,
+    Mips = 2,
+    Srgb = 4,
+}
+
+#[attributes(Handle, Drop)]
+struct Image {
+    [static] create_from_file(filename: String, flags: LoadFlags) -> Image?,
+    [static] create_from_memory(name: String, data: [u8]) -> Image?,
+    get_format() -> Format,
+    get_name() -> String,
+    destroy(),
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        CppGenerator::new("FL_", "fl")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_header() {
+        let h = generate();
+
+        assert!(h.contains("#include \"image.h\""));
+        assert!(h.contains("namespace fl {"));
+        assert!(h.contains("enum class Format {\n    Rgb = 0,\n    Rgba = 1,\n};"));
+        assert!(h.contains("enum class LoadFlag : uint32_t {\n    Cache = 0x1,"));
+        assert!(h.contains("constexpr LoadFlag operator|(LoadFlag a, LoadFlag b) {"));
+        assert!(h.contains("using LoadFlags = LoadFlag;"));
+        assert!(h.contains(
+            "    ~Image() {\n        if (m_handle) {\n            FL_Image_destroy(m_handle);"
+        ));
+        assert!(h.contains("    Image(const Image&) = delete;"));
+        assert!(h.contains(
+            "    static std::optional<Image> create_from_file(std::string_view filename, LoadFlags flags);"
+        ));
+        assert!(h.contains("std::span<const uint8_t> data"));
+        assert!(h.contains("    Format get_format() const;"));
+        assert!(h.contains(
+            "inline Format Image::get_format() const {\n    return static_cast<Format>(FL_Image_get_format(m_handle));\n}"
+        ));
+        assert!(h.contains("FL_Image_create_from_file(std::string(filename).c_str(), static_cast<FL_LoadFlags>(flags))"));
+        assert!(!h.contains("destroy();"));
+    }
+
+    const EDGE_CASES: &str = r#"
+[enum_flags(BigFlags)]
+enum BigFlag {
+    Low = 1,
+    High = 0x100000000,
+}
+
+enum Big {
+    Small = 3,
+    Large = 0x100000005,
+}
+
+enum Mode {
+    class,
+    new,
+}
+
+#[attributes(Handle, Drop)]
+struct Device {
+    [static] create() -> Device?,
+    set_flags(flags: BigFlags) -> Big,
+    get_mode() -> Mode,
+    upload(data: [u8], fixed: [f32; 4], other: Device),
+    upload_devices(devices: [Device]),
+    destroy(),
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("edge.def", EDGE_CASES)]);
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let registry = Registry::with_builtins();
+        let mut sink = MemorySink::default();
+
+        for name in ["c", "cpp"] {
+            registry
+                .get(name)
+                .unwrap()
+                .generate(&defs, &options, &mut sink)
+                .unwrap();
+        }
+
+        let h = String::from_utf8(sink.files["edge.hpp"].clone()).unwrap();
+
+        assert!(
+            h.contains("enum class BigFlag : uint64_t {\n    Low = 0x1,\n    High = 0x100000000,")
+        );
+        assert!(h.contains("    return static_cast<BigFlag>(~static_cast<uint64_t>(a));"));
+        assert!(h.contains("enum class Big : uint64_t {\n    Small = 3,\n    Large = 4294967301,"));
+        assert!(h.contains("enum class Mode {\n    class_ = 0,\n    new_ = 1,"));
+        assert!(h.contains("FL_Device_upload(m_handle, data.data(), data.size()"));
+        assert!(h.contains("std::span<FL_Device* const> devices"));
+
+        let main = "#include \"edge.hpp\"\nstatic_assert(sizeof(fl::BigFlags) == 8);\nint main() { return 0; }\n";
+        let mut files = sink
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect::<Vec<_>>();
+        files.push(("main.cpp", main.as_bytes()));
+
+        test_util::compiles(
+            "cpp",
+            &files,
+            "g++",
+            &[
+                "-std=c++20",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-fsyntax-only",
+                "main.cpp",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        // Types and consts from other files can be used without importing them
+        let defs = test_util::parse(&[
+            (
+                "image.def",
+                "struct ImageInfo {\n    name: [u8; MAX_NAME],\n    size: Size,\n}\n\n#[attributes(Handle, Drop)]\nstruct Image {\n    [static] create(size: Size) -> Image?,\n    get_device() -> Device,\n    destroy(),\n}",
+            ),
+            (
+                "types.def",
+                "const MAX_NAME = 64\nstruct Size { width: u32, height: u32 }",
+            ),
+            (
+                "device.def",
+                "#[attributes(Handle)]\nstruct Device { get_size() -> Size }",
+            ),
+        ]);
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let registry = Registry::with_builtins();
+        let mut sink = MemorySink::default();
+
+        for name in ["c", "cpp"] {
+            registry
+                .get(name)
+                .unwrap()
+                .generate(&defs, &options, &mut sink)
+                .unwrap();
+        }
+
+        let h = String::from_utf8(sink.files["image.hpp"].clone()).unwrap();
+        assert!(h.contains("#include \"image.h\"\n#include \"device.hpp\"\n"));
+
+        let main = "#include \"image.hpp\"\nint main() { return 0; }\n";
+        let mut files = sink
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect::<Vec<_>>();
+        files.push(("main.cpp", main.as_bytes()));
+
+        test_util::compiles(
+            "cpp_dependencies",
+            &files,
+            "g++",
+            &[
+                "-std=c++20",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-fsyntax-only",
+                "main.cpp",
+            ],
+        );
+    }
+}
@@ -0,0 +1,727 @@
+//! Generates C# P/Invoke bindings for each def file.
+//!
+//! Everything is put in the same namespace and the consts and functions are added to the partial
+//! class `Native`. Functions are named `<Struct>_<function>` and use the C name as entry point.
+//! Arguments are expanded the same way as in the C header so unsized arrays are passed as an array
+//! and a `<name>_size` argument. Handles, strings that are returned and other pointers that can't be
+//! passed as `ref`/`in` are `IntPtr`. C# doesn't have type aliases so `type` aliases and flag names
+//! are replaced with the type they refer to.
+use crate::api_parser::{
+    ApiDef, ArrayType, EnumType, Function, Result, Struct, TypeModifier, Variable, VariableType,
+};
+use crate::generators::c::is_opaque;
+use crate::generators::util::write_separator;
+use crate::{get_derived_structs, write_c_commments};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Keywords in C# that can't be used as names without `@`
+const CSHARP_KEYWORDS: &[&str] = &[
+    "abstract",
+    "as",
+    "base",
+    "bool",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "continue",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "event",
+    "explicit",
+    "extern",
+    "false",
+    "finally",
+    "fixed",
+    "float",
+    "for",
+    "foreach",
+    "goto",
+    "if",
+    "implicit",
+    "in",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "ref",
+    "return",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "static",
+    "string",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// Writes C# bindings
+#[derive(Debug, Clone, Default)]
+pub struct CSharpGenerator {
+    /// Has to match the prefix used for the C code
+    pub c_prefix: String,
+    /// Namespace for the generated code
+    pub namespace: String,
+    /// Name of the library used with `DllImport`
+    pub library: String,
+}
+
+/// Names that has to be replaced as C# doesn't have type aliases
+struct Aliases<'a> {
+    /// `type` aliases
+    types: HashMap<&'a str, &'a Variable>,
+    /// Flags name -> enum name
+    flags: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Aliases<'a> {
+    fn new(api_defs: &'a [ApiDef]) -> Aliases<'a> {
+        let mut types = HashMap::new();
+        let mut flags = HashMap::new();
+
+        for api_def in api_defs {
+            for t in &api_def.types {
+                types.insert(t.var.name.as_str(), &t.var);
+            }
+
+            for e in api_def.enums.iter().filter(|e| !e.flags_name.is_empty()) {
+                flags.insert(e.flags_name.as_str(), e.name.as_str());
+            }
+        }
+
+        Aliases { types, flags }
+    }
+
+    /// Replaces aliases in `var` with the types they refer to
+    fn resolve(&self, var: &Variable) -> Variable {
+        let mut var = var.clone();
+
+        // Limit the depth in case an alias refers to itself
+        for _ in 0..16 {
+            if var.vtype != VariableType::Alias {
+                break;
+            }
+
+            match self.types.get(var.type_name.as_str()) {
+                Some(target) => {
+                    var.vtype = target.vtype;
+                    var.type_name = target.type_name.to_owned();
+                    var.enum_type = target.enum_type;
+
+                    if var.type_modifier == TypeModifier::None {
+                        var.type_modifier = target.type_modifier.clone();
+                    }
+
+                    if var.array.is_none() {
+                        var.array = target.array.clone();
+                    }
+                }
+                None => break,
+            }
+        }
+
+        if let Some(name) = self.flags.get(var.type_name.as_str()) {
+            var.type_name = (*name).to_owned();
+        }
+
+        var
+    }
+}
+
+fn csharp_name(name: &str) -> String {
+    if CSHARP_KEYWORDS.contains(&name) {
+        format!("@{}", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn primitive(name: &str) -> &'static str {
+    match name {
+        "u8" => "byte",
+        "u16" => "ushort",
+        "u32" => "uint",
+        "u64" => "ulong",
+        "i8" => "sbyte",
+        "i16" => "short",
+        "i32" => "int",
+        "i64" => "long",
+        "f32" => "float",
+        "f64" => "double",
+        "bool" => "bool",
+        _ => "void",
+    }
+}
+
+/// Checks if `var` is a by value bool (which needs to be marshaled as one byte)
+fn is_bool(var: &Variable) -> bool {
+    var.vtype == VariableType::Primitive
+        && var.type_name == "bool"
+        && var.type_modifier == TypeModifier::None
+}
+
+impl CSharpGenerator {
+    pub fn new(c_prefix: &str, namespace: &str, library: &str) -> CSharpGenerator {
+        CSharpGenerator {
+            c_prefix: c_prefix.to_owned(),
+            namespace: namespace.to_owned(),
+            library: library.to_owned(),
+        }
+    }
+
+    /// Name of the file generated for `api_def`
+    pub fn file_name(api_def: &ApiDef) -> String {
+        format!("{}.cs", api_def.qualified_filename())
+    }
+
+    /// Writes the bindings for `api_def`. `api_defs` is all the def files and is used to look up
+    /// aliases and structs used with derive.
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let aliases = Aliases::new(api_defs);
+
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+        writeln!(out)?;
+        writeln!(out, "using System;")?;
+        writeln!(out, "using System.Runtime.InteropServices;")?;
+        writeln!(out)?;
+        writeln!(out, "namespace {}", self.namespace)?;
+        writeln!(out, "{{")?;
+
+        let mut first = true;
+
+        for e in &api_def.enums {
+            write_separator(out, &mut first)?;
+            write_c_commments(out, &e.doc_comments, 4)?;
+
+            // Same size as the C enum, which is 64 bit when the values doesn't fit in 32 bits
+            if e.enum_type == EnumType::Bitflags {
+                writeln!(out, "    [Flags]")?;
+                writeln!(
+                    out,
+                    "    public enum {} : {}",
+                    e.name,
+                    if e.is_64bit() { "ulong" } else { "uint" }
+                )?;
+            } else if e.is_64bit() {
+                writeln!(out, "    public enum {} : ulong", e.name)?;
+            } else {
+                writeln!(out, "    public enum {}", e.name)?;
+            }
+
+            writeln!(out, "    {{")?;
+
+            for entry in &e.entries {
+                write_c_commments(out, &entry.doc_comments, 8)?;
+
+                if e.enum_type == EnumType::Bitflags {
+                    writeln!(
+                        out,
+                        "        {} = 0x{:x},",
+                        csharp_name(&entry.name),
+                        entry.value
+                    )?;
+                } else {
+                    writeln!(
+                        out,
+                        "        {} = {},",
+                        csharp_name(&entry.name),
+                        entry.value
+                    )?;
+                }
+            }
+
+            writeln!(out, "    }}")?;
+        }
+
+        for c in &api_def.callbacks {
+            write_separator(out, &mut first)?;
+            write_c_commments(out, &c.doc_comments, 4)?;
+            writeln!(
+                out,
+                "    [UnmanagedFunctionPointer(CallingConvention.Cdecl)]"
+            )?;
+            writeln!(
+                out,
+                "    public delegate {} {}({});",
+                self.return_type(c, &aliases),
+                c.name,
+                self.arguments(c, &aliases).join(", ")
+            )?;
+        }
+
+        // Handles and structs without data are only used through pointers
+        let structs = api_def.structs.iter().filter(|s| !is_opaque(s));
+
+        for s in structs {
+            write_separator(out, &mut first)?;
+            self.write_struct(out, s, api_defs, &aliases)?;
+        }
+
+        for u in &api_def.unions {
+            write_separator(out, &mut first)?;
+            self.write_union(out, u, &aliases)?;
+        }
+
+        write_separator(out, &mut first)?;
+        self.write_native(out, api_def, &aliases)?;
+
+        writeln!(out, "}}")?;
+
+        Ok(())
+    }
+
+    /// Type of a field or argument without arrays
+    fn value_type(&self, var: &Variable) -> String {
+        let is_void = var.vtype == VariableType::Primitive && var.type_name == "void";
+
+        match var.vtype {
+            VariableType::Handle | VariableType::SelfType | VariableType::Str => {
+                return "IntPtr".to_owned()
+            }
+            _ if var.type_modifier != TypeModifier::None && is_void => return "IntPtr".to_owned(),
+            VariableType::None => return "IntPtr".to_owned(),
+            _ => (),
+        }
+
+        let t = match var.vtype {
+            VariableType::Primitive => primitive(&var.type_name).to_owned(),
+            _ => var.type_name.to_owned(),
+        };
+
+        match var.type_modifier {
+            TypeModifier::None => t,
+            TypeModifier::MutPointer => format!("ref {}", t),
+            TypeModifier::ConstPointer | TypeModifier::Reference => format!("in {}", t),
+        }
+    }
+
+    /// Pointers in fields are always `IntPtr`
+    fn field_type(&self, var: &Variable) -> String {
+        if var.type_modifier != TypeModifier::None {
+            "IntPtr".to_owned()
+        } else {
+            self.value_type(var)
+        }
+    }
+
+    /// Size of a sized array. Consts are in the `Native` class
+    fn array_size(size: &str) -> String {
+        if size.starts_with(|c: char| c.is_ascii_digit()) {
+            size.to_owned()
+        } else {
+            format!("Native.{}", size)
+        }
+    }
+
+    fn write_struct<W: Write>(
+        &self,
+        out: &mut W,
+        s: &Struct,
+        api_defs: &[ApiDef],
+        aliases: &Aliases,
+    ) -> Result<()> {
+        write_c_commments(out, &s.doc_comments, 4)?;
+        writeln!(out, "    [StructLayout(LayoutKind.Sequential)]")?;
+        writeln!(out, "    public struct {}", s.name)?;
+        writeln!(out, "    {{")?;
+
+        for derived in get_derived_structs(api_defs, s) {
+            writeln!(out, "        // From {}", derived.name)?;
+
+            for var in &derived.variables {
+                self.write_field(out, &aliases.resolve(var))?;
+            }
+        }
+
+        for var in &s.variables {
+            write_c_commments(out, &var.doc_comments, 8)?;
+            self.write_field(out, &aliases.resolve(var))?;
+        }
+
+        writeln!(out, "    }}")?;
+
+        Ok(())
+    }
+
+    fn write_field<W: Write>(&self, out: &mut W, var: &Variable) -> Result<()> {
+        let name = csharp_name(&var.name);
+
+        match var.array {
+            Some(ArrayType::Unsized) => {
+                writeln!(out, "        public IntPtr {};", name)?;
+                writeln!(out, "        public ulong {}_size;", var.name)?;
+            }
+            Some(ArrayType::SizedArray(ref size)) => {
+                writeln!(
+                    out,
+                    "        [MarshalAs(UnmanagedType.ByValArray, SizeConst = {})]",
+                    Self::array_size(size)
+                )?;
+                writeln!(out, "        public {}[] {};", self.field_type(var), name)?;
+            }
+            None => {
+                if is_bool(var) {
+                    writeln!(out, "        [MarshalAs(UnmanagedType.U1)]")?;
+                }
+
+                writeln!(out, "        public {} {};", self.field_type(var), name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unions use explicit layout. Arrays are fixed buffers as arrays (which are references) can't
+    /// overlap other fields
+    fn write_union<W: Write>(&self, out: &mut W, u: &Struct, aliases: &Aliases) -> Result<()> {
+        let has_arrays = u.variables.iter().any(|v| v.array.is_some());
+
+        write_c_commments(out, &u.doc_comments, 4)?;
+        writeln!(out, "    [StructLayout(LayoutKind.Explicit)]")?;
+        writeln!(
+            out,
+            "    public {}struct {}",
+            if has_arrays { "unsafe " } else { "" },
+            u.name
+        )?;
+        writeln!(out, "    {{")?;
+
+        for var in &u.variables {
+            let var = aliases.resolve(var);
+            let name = csharp_name(&var.name);
+
+            write_c_commments(out, &var.doc_comments, 8)?;
+
+            match var.array {
+                Some(ArrayType::Unsized) => {
+                    writeln!(out, "        [FieldOffset(0)] public IntPtr {};", name)?;
+                }
+                Some(ArrayType::SizedArray(ref size)) => {
+                    writeln!(
+                        out,
+                        "        [FieldOffset(0)] public fixed {} {}[{}];",
+                        self.field_type(&var),
+                        name,
+                        Self::array_size(size)
+                    )?;
+                }
+                None => {
+                    if is_bool(&var) {
+                        writeln!(out, "        [MarshalAs(UnmanagedType.U1)]")?;
+                    }
+
+                    writeln!(
+                        out,
+                        "        [FieldOffset(0)] public {} {};",
+                        self.field_type(&var),
+                        name
+                    )?;
+                }
+            }
+        }
+
+        writeln!(out, "    }}")?;
+
+        Ok(())
+    }
+
+    /// Writes the consts and the functions
+    fn write_native<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        aliases: &Aliases,
+    ) -> Result<()> {
+        writeln!(out, "    public static partial class Native")?;
+        writeln!(out, "    {{")?;
+
+        let mut first = true;
+
+        for c in api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+        {
+            let t = if c.value.starts_with('"') {
+                "string"
+            } else if let Some(value) = parse_number(&c.value) {
+                if value <= i32::MAX as u64 {
+                    "int"
+                } else {
+                    "ulong"
+                }
+            } else {
+                continue;
+            };
+
+            write_separator(out, &mut first)?;
+            write_c_commments(out, &c.doc_comments, 8)?;
+            writeln!(out, "        public const {} {} = {};", t, c.name, c.value)?;
+        }
+
+        for s in &api_def.structs {
+            for func in &s.functions {
+                write_separator(out, &mut first)?;
+                write_c_commments(out, &func.doc_comments, 8)?;
+                writeln!(
+                    out,
+                    "        [DllImport(\"{}\", CallingConvention = CallingConvention.Cdecl, EntryPoint = \"{}\")]",
+                    self.library,
+                    func.get_c_name(&s.name, &self.c_prefix)
+                )?;
+
+                if func.return_val.as_ref().is_some_and(is_bool) {
+                    writeln!(out, "        [return: MarshalAs(UnmanagedType.U1)]")?;
+                }
+
+                writeln!(
+                    out,
+                    "        public static extern {} {}_{}({});",
+                    self.return_type(func, aliases),
+                    s.name,
+                    func.name,
+                    self.arguments(func, aliases).join(", ")
+                )?;
+            }
+        }
+
+        writeln!(out, "    }}")?;
+
+        Ok(())
+    }
+
+    /// Return values that are pointers (including strings) are `IntPtr`
+    fn return_type(&self, func: &Function, aliases: &Aliases) -> String {
+        match func.return_val.as_ref().map(|r| aliases.resolve(r)) {
+            Some(ret) if ret.type_modifier != TypeModifier::None || ret.array.is_some() => {
+                "IntPtr".to_owned()
+            }
+            Some(ret) => self.value_type(&ret),
+            None => "void".to_owned(),
+        }
+    }
+
+    /// Arguments using the same rules as `Function::c_args`
+    fn arguments(&self, func: &Function, aliases: &Aliases) -> Vec<String> {
+        let mut args = Vec::with_capacity(func.function_args.len());
+
+        for arg in &func.function_args {
+            let arg = aliases.resolve(arg);
+            let name = csharp_name(&arg.name);
+
+            if arg.vtype == VariableType::Str {
+                args.push(format!(
+                    "[MarshalAs(UnmanagedType.LPUTF8Str)] string {}",
+                    name
+                ));
+                continue;
+            }
+
+            match arg.array {
+                None if is_bool(&arg) => {
+                    args.push(format!("[MarshalAs(UnmanagedType.U1)] bool {}", name))
+                }
+                None => args.push(format!("{} {}", self.value_type(&arg), name)),
+                Some(ArrayType::Unsized) => {
+                    args.push(format!("{}[] {}", self.field_type(&arg), name));
+                    args.push(format!("ulong {}_size", arg.name));
+                }
+                Some(ArrayType::SizedArray(_)) => {
+                    args.push(format!("{}[] {}", self.field_type(&arg), name))
+                }
+            }
+        }
+
+        args
+    }
+}
+
+/// Parses decimal and hex numbers
+fn parse_number(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse::<u64>().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::test_util;
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+const MAX_NAME = 32
+
+/// Info about an image
+struct ImageInfo {
+    width: u32,
+    name: [u8; MAX_NAME],
+    format: Format,
+    data: [u8],
+    id: ImageId,
+}
+
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+[enum_flags(LoadFlags)]
+enum LoadFlag {
+    Cache = 1,
+    Mips = 2,
+    Srgb = 4,
+}
+
+type ImageId: u64
+
+callback LoadCallback(image: Image, user_data: *void)
+
+#[attributes(Handle)]
+struct Image {
+    [static] create_from_memory(name: String, data: [u8], flags: LoadFlags) -> Image?,
+    get_info() -> *const ImageInfo?,
+    is_valid() -> bool,
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        CSharpGenerator::new("FL_", "Fl", "fl")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bindings() {
+        let cs = generate();
+
+        assert!(cs.contains("namespace Fl\n{"));
+        assert!(cs
+            .contains("    public enum Format\n    {\n        Rgb = 0,\n        Rgba = 1,\n    }"));
+        assert!(cs
+            .contains("    [Flags]\n    public enum LoadFlag : uint\n    {\n        Cache = 0x1,"));
+        assert!(cs.contains("    [UnmanagedFunctionPointer(CallingConvention.Cdecl)]\n    public delegate void LoadCallback(IntPtr image, IntPtr user_data);"));
+        assert!(
+            cs.contains("    [StructLayout(LayoutKind.Sequential)]\n    public struct ImageInfo\n")
+        );
+        assert!(cs.contains("        [MarshalAs(UnmanagedType.ByValArray, SizeConst = Native.MAX_NAME)]\n        public byte[] name;"));
+        assert!(cs.contains("        public IntPtr data;\n        public ulong data_size;"));
+        assert!(cs.contains("        public ulong id;"));
+        assert!(cs.contains("        public const int MAX_NAME = 32;"));
+        assert!(cs.contains("EntryPoint = \"FL_Image_create_from_memory\")]\n        public static extern IntPtr Image_create_from_memory([MarshalAs(UnmanagedType.LPUTF8Str)] string name, byte[] data, ulong data_size, LoadFlag flags);"));
+        assert!(cs.contains("        public static extern IntPtr Image_get_info(IntPtr self);"));
+        assert!(cs.contains("        [return: MarshalAs(UnmanagedType.U1)]\n        public static extern bool Image_is_valid(IntPtr self);"));
+    }
+
+    const EDGE_CASES: &str = r#"
+[enum_flags(BigFlags)]
+enum BigFlag {
+    Low = 1,
+    High = 0x100000000,
+}
+
+enum Big {
+    Small = 3,
+    Large = 0x100000005,
+}
+
+enum Mode {
+    object,
+    string,
+}
+
+struct Settings {
+    flags: BigFlags,
+    params: [u8],
+}
+
+#[attributes(Handle)]
+struct Device {
+    set_flags(flags: BigFlags) -> Big,
+    get_mode() -> Mode,
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("edge.def", EDGE_CASES)]);
+
+        let mut out = Vec::new();
+        CSharpGenerator::new("FL_", "Fl", "fl")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        let cs = String::from_utf8(out).unwrap();
+
+        assert!(cs.contains(
+            "    [Flags]\n    public enum BigFlag : ulong\n    {\n        Low = 0x1,\n        High = 0x100000000,"
+        ));
+        assert!(cs.contains(
+            "    public enum Big : ulong\n    {\n        Small = 3,\n        Large = 4294967301,"
+        ));
+        assert!(
+            cs.contains("    public enum Mode\n    {\n        @object = 0,\n        @string = 1,")
+        );
+        assert!(cs.contains("        public BigFlag flags;"));
+        assert!(cs.contains("        public IntPtr @params;\n        public ulong params_size;"));
+        assert!(
+            cs.contains("public static extern Big Device_set_flags(IntPtr self, BigFlag flags);")
+        );
+    }
+}
@@ -0,0 +1,752 @@
+//! Generates API reference documentation as Markdown or HTML.
+//!
+//! Each def file gets a page with its consts, types, enums, callbacks, structs and unions and
+//! `index` links to all pages and lists every type. Signatures are written with the def syntax and
+//! types are linked to where they are declared, also across pages.
+use crate::api_parser::{
+    ApiDef, ArrayType, Function, FunctionType, Ownership, Result, Struct, TypeModifier, Variable,
+    VariableType,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// Output format of the documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+/// Writes API documentation
+#[derive(Debug, Clone)]
+pub struct DocsGenerator {
+    pub format: DocFormat,
+    /// Show `[static]`, `[manual]`, `[drop]`, `[owned]` and `[borrowed]` in front of functions
+    pub show_function_types: bool,
+    /// Show `?` after optional values
+    pub show_optional: bool,
+}
+
+impl Default for DocsGenerator {
+    fn default() -> Self {
+        DocsGenerator::new(DocFormat::Markdown)
+    }
+}
+
+/// Where a type is declared
+struct Target {
+    /// Qualified filename of the def file
+    file: String,
+    anchor: String,
+    kind: &'static str,
+}
+
+/// Anchor of the heading for a type. The kind is added so it can't be the same as the anchor of
+/// the page title (the file name) or a section
+fn anchor(kind: &str, name: &str) -> String {
+    format!("{}-{}", kind, name).to_lowercase()
+}
+
+/// All types that can be linked to
+struct Links<'a> {
+    targets: HashMap<&'a str, Target>,
+}
+
+impl<'a> Links<'a> {
+    fn new(api_defs: &'a [ApiDef]) -> Links<'a> {
+        let mut targets = HashMap::new();
+
+        for api_def in api_defs {
+            let file = api_def.qualified_filename();
+            let mut add = |name: &'a str, anchor: String, kind| {
+                let file = file.clone();
+                targets.insert(name, Target { file, anchor, kind });
+            };
+
+            for t in &api_def.types {
+                add(&t.var.name, anchor("type", &t.var.name), "type");
+            }
+
+            for e in &api_def.enums {
+                add(&e.name, anchor("enum", &e.name), "enum");
+
+                if !e.flags_name.is_empty() {
+                    add(&e.flags_name, anchor("enum", &e.name), "flags");
+                }
+            }
+
+            for c in &api_def.callbacks {
+                add(&c.name, anchor("callback", &c.name), "callback");
+            }
+
+            for s in &api_def.structs {
+                add(&s.name, anchor("struct", &s.name), "struct");
+            }
+
+            for u in &api_def.unions {
+                add(&u.name, anchor("union", &u.name), "union");
+            }
+        }
+
+        Links { targets }
+    }
+}
+
+impl DocsGenerator {
+    /// Creates a generator that shows function types and optional values
+    pub fn new(format: DocFormat) -> DocsGenerator {
+        DocsGenerator {
+            format,
+            show_function_types: true,
+            show_optional: true,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+
+    /// Name of the page generated for `api_def`
+    pub fn file_name(&self, api_def: &ApiDef) -> String {
+        format!("{}.{}", api_def.qualified_filename(), self.extension())
+    }
+
+    /// Name of the index page
+    pub fn index_name(&self) -> String {
+        format!("index.{}", self.extension())
+    }
+
+    /// Writes the page for `api_def`. `api_defs` is all the def files and is used to link types
+    /// declared in other files
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let links = Links::new(api_defs);
+        let page = &api_def.qualified_filename();
+
+        self.write_begin(out, page, &api_def.filename)?;
+        self.heading(out, 1, page)?;
+        writeln!(
+            out,
+            "{}",
+            self.paragraph(&format!(
+                "Generated from {}. See the {} for all types.",
+                self.code(&api_def.filename),
+                self.link("index", &self.index_name())
+            ))
+        )?;
+
+        let consts = api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+            .collect::<Vec<_>>();
+
+        if !consts.is_empty() {
+            self.heading(out, 2, "Constants")?;
+
+            let rows = consts
+                .iter()
+                .map(|c| {
+                    vec![
+                        self.code(&c.name),
+                        self.code(&c.value),
+                        self.text(&c.doc_comments.join(" ")),
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            self.table(out, &["Name", "Value", "Description"], rows)?;
+        }
+
+        if !api_def.types.is_empty() {
+            self.heading(out, 2, "Types")?;
+
+            for t in &api_def.types {
+                self.anchored_heading(out, 3, &t.var.name, &anchor("type", &t.var.name))?;
+                self.docs(out, &t.var.doc_comments)?;
+                self.code_block(
+                    out,
+                    &format!("type {}: {}", t.var.name, self.type_name(&t.var)),
+                )?;
+                self.uses(out, std::slice::from_ref(&t.var), page, &links)?;
+            }
+        }
+
+        if !api_def.enums.is_empty() {
+            self.heading(out, 2, "Enums")?;
+
+            for e in &api_def.enums {
+                self.anchored_heading(out, 3, &e.name, &anchor("enum", &e.name))?;
+                self.docs(out, &e.doc_comments)?;
+
+                if !e.flags_name.is_empty() {
+                    writeln!(
+                        out,
+                        "{}",
+                        self.paragraph(&format!(
+                            "Combinations of the values are stored in {}.",
+                            self.code(&e.flags_name)
+                        ))
+                    )?;
+                }
+
+                let rows = e
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        vec![
+                            self.code(&entry.name),
+                            self.code(&entry.value.to_string()),
+                            self.text(&entry.doc_comments.join(" ")),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+
+                self.table(out, &["Name", "Value", "Description"], rows)?;
+            }
+        }
+
+        if !api_def.callbacks.is_empty() {
+            self.heading(out, 2, "Callbacks")?;
+
+            for c in &api_def.callbacks {
+                self.anchored_heading(out, 3, &c.name, &anchor("callback", &c.name))?;
+                self.docs(out, &c.doc_comments)?;
+                self.code_block(out, &format!("callback {}", self.signature(c, false)))?;
+                self.uses(out, &Self::function_vars(c), page, &links)?;
+            }
+        }
+
+        if !api_def.structs.is_empty() {
+            self.heading(out, 2, "Structs")?;
+
+            for s in &api_def.structs {
+                self.write_struct(out, "struct", s, page, &links)?;
+            }
+        }
+
+        if !api_def.unions.is_empty() {
+            self.heading(out, 2, "Unions")?;
+
+            for u in &api_def.unions {
+                self.write_struct(out, "union", u, page, &links)?;
+            }
+        }
+
+        self.write_end(out)?;
+
+        Ok(())
+    }
+
+    /// Writes the index page with links to all pages and all types sorted by name
+    pub fn generate_index<W: Write>(&self, out: &mut W, api_defs: &[ApiDef]) -> Result<()> {
+        let links = Links::new(api_defs);
+
+        self.write_begin(out, "API reference", "")?;
+        self.heading(out, 1, "API reference")?;
+        self.heading(out, 2, "Files")?;
+
+        let mut files = api_defs.iter().collect::<Vec<_>>();
+        files.sort_by_key(|api_def| api_def.qualified_filename());
+
+        let rows = files
+            .iter()
+            .map(|api_def| {
+                vec![
+                    self.link(&api_def.qualified_filename(), &self.file_name(api_def)),
+                    self.code(&api_def.filename),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        self.table(out, &["Page", "Def file"], rows)?;
+        self.heading(out, 2, "Types")?;
+
+        let sorted = links.targets.iter().collect::<BTreeMap<_, _>>();
+
+        let rows = sorted
+            .iter()
+            .map(|(name, target)| {
+                vec![
+                    self.link(&self.code(name), &self.href(target, "")),
+                    target.kind.to_owned(),
+                    target.file.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        self.table(out, &["Name", "Kind", "Page"], rows)?;
+        self.write_end(out)?;
+
+        Ok(())
+    }
+
+    fn write_struct<W: Write>(
+        &self,
+        out: &mut W,
+        kind: &str,
+        s: &Struct,
+        page: &str,
+        links: &Links,
+    ) -> Result<()> {
+        self.anchored_heading(out, 3, &s.name, &anchor(kind, &s.name))?;
+        self.docs(out, &s.doc_comments)?;
+
+        if !s.attributes.is_empty() {
+            let attributes = s
+                .attributes
+                .iter()
+                .map(|a| self.code(a))
+                .collect::<Vec<_>>();
+
+            writeln!(
+                out,
+                "{}",
+                self.paragraph(&format!("Attributes: {}", attributes.join(", ")))
+            )?;
+        }
+
+        if !s.derives.is_empty() {
+            let derives = s
+                .derives
+                .iter()
+                .map(|d| match links.targets.get(d.as_str()) {
+                    Some(target) => self.link(&self.code(d), &self.href(target, page)),
+                    None => self.code(d),
+                })
+                .collect::<Vec<_>>();
+
+            writeln!(
+                out,
+                "{}",
+                self.paragraph(&format!("Derives: {}", derives.join(", ")))
+            )?;
+        }
+
+        if !s.variables.is_empty() {
+            let rows = s
+                .variables
+                .iter()
+                .map(|var| {
+                    vec![
+                        self.code(&var.name),
+                        self.type_link(var, page, links),
+                        self.text(&var.doc_comments.join(" ")),
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            self.table(out, &["Field", "Type", "Description"], rows)?;
+        }
+
+        for func in &s.functions {
+            self.anchored_heading(
+                out,
+                4,
+                &format!("{}::{}", s.name, func.name),
+                &anchor("fn", &format!("{}-{}", s.name, func.name)),
+            )?;
+            self.docs(out, &func.doc_comments)?;
+            self.code_block(out, &self.signature(func, true))?;
+            self.uses(out, &Self::function_vars(func), page, links)?;
+        }
+
+        Ok(())
+    }
+
+    /// Arguments (without `self`) and the return value
+    fn function_vars(func: &Function) -> Vec<Variable> {
+        func.function_args
+            .iter()
+            .chain(func.return_val.iter())
+            .filter(|var| var.vtype != VariableType::SelfType)
+            .cloned()
+            .collect()
+    }
+
+    /// Function signature using the def syntax. Callbacks are parsed as `[static]` so they don't
+    /// show the function type
+    fn signature(&self, func: &Function, with_type: bool) -> String {
+        let mut output = String::with_capacity(128);
+
+        if with_type && self.show_function_types {
+            match func.func_type {
+                FunctionType::Regular => (),
+                FunctionType::Static => output.push_str("[static] "),
+                FunctionType::Manual => output.push_str("[manual] "),
+                FunctionType::Drop => output.push_str("[drop] "),
+            }
+
+            match func.ownership {
+                Ownership::Default => (),
+                Ownership::Owned => output.push_str("[owned] "),
+                Ownership::Borrowed => output.push_str("[borrowed] "),
+            }
+        }
+
+        let args = func
+            .function_args
+            .iter()
+            .filter(|arg| arg.vtype != VariableType::SelfType)
+            .map(|arg| format!("{}: {}", arg.name, self.type_name(arg)))
+            .collect::<Vec<_>>();
+
+        output.push_str(&format!("{}({})", func.name, args.join(", ")));
+
+        if let Some(ret) = func.return_val.as_ref() {
+            output.push_str(&format!(" -> {}", self.type_name(ret)));
+        }
+
+        output
+    }
+
+    /// Type of `var` using the def syntax
+    fn type_name(&self, var: &Variable) -> String {
+        let name = match var.vtype {
+            VariableType::Str => "String",
+            VariableType::SelfType => "Self",
+            _ => var.type_name.as_str(),
+        };
+
+        let name = match var.type_modifier {
+            TypeModifier::None => name.to_owned(),
+            TypeModifier::ConstPointer => format!("*const {}", name),
+            TypeModifier::MutPointer => format!("*{}", name),
+            TypeModifier::Reference => format!("&{}", name),
+        };
+
+        let name = match var.array {
+            None => name,
+            Some(ArrayType::Unsized) => format!("[{}]", name),
+            Some(ArrayType::SizedArray(ref size)) => format!("[{}; {}]", name, size),
+        };
+
+        if var.optional && self.show_optional {
+            format!("{}?", name)
+        } else {
+            name
+        }
+    }
+
+    /// Type of `var` linked to where the type is declared
+    fn type_link(&self, var: &Variable, page: &str, links: &Links) -> String {
+        let code = self.code(&self.type_name(var));
+
+        match links.targets.get(var.type_name.as_str()) {
+            Some(target) => self.link(&code, &self.href(target, page)),
+            None => code,
+        }
+    }
+
+    /// Links to the types used in a signature
+    fn uses<W: Write>(
+        &self,
+        out: &mut W,
+        vars: &[Variable],
+        page: &str,
+        links: &Links,
+    ) -> Result<()> {
+        let mut used = Vec::new();
+
+        for var in vars {
+            if let Some(target) = links.targets.get(var.type_name.as_str()) {
+                let link = self.link(&self.code(&var.type_name), &self.href(target, page));
+
+                if !used.contains(&link) {
+                    used.push(link);
+                }
+            }
+        }
+
+        if !used.is_empty() {
+            writeln!(
+                out,
+                "{}",
+                self.paragraph(&format!("Uses: {}", used.join(", ")))
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Link to a type. Types on the current page only use the anchor
+    fn href(&self, target: &Target, page: &str) -> String {
+        if target.file == page {
+            format!("#{}", target.anchor)
+        } else {
+            format!("{}.{}#{}", target.file, self.extension(), target.anchor)
+        }
+    }
+
+    fn write_begin<W: Write>(&self, out: &mut W, title: &str, filename: &str) -> Result<()> {
+        let generated = if filename.is_empty() {
+            "This file is generated by apigen. Do not edit.".to_owned()
+        } else {
+            format!(
+                "This file is generated by apigen from {}. Do not edit.",
+                filename
+            )
+        };
+
+        match self.format {
+            DocFormat::Markdown => writeln!(out, "<!-- {} -->", generated)?,
+            DocFormat::Html => {
+                writeln!(out, "<!DOCTYPE html>")?;
+                writeln!(out, "<!-- {} -->", generated)?;
+                writeln!(out, "<html>")?;
+                writeln!(out, "<head>")?;
+                writeln!(out, "<meta charset=\"utf-8\">")?;
+                writeln!(out, "<title>{}</title>", escape_html(title))?;
+                writeln!(out, "</head>")?;
+                writeln!(out, "<body>")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_end<W: Write>(&self, out: &mut W) -> Result<()> {
+        if self.format == DocFormat::Html {
+            writeln!(out, "</body>")?;
+            writeln!(out, "</html>")?;
+        }
+
+        Ok(())
+    }
+
+    /// Heading with an explicit anchor. Markdown has no syntax for it so an empty `<a>` with the
+    /// id is put before the heading
+    fn anchored_heading<W: Write>(
+        &self,
+        out: &mut W,
+        level: usize,
+        text: &str,
+        anchor: &str,
+    ) -> Result<()> {
+        match self.format {
+            DocFormat::Markdown => {
+                writeln!(out)?;
+                writeln!(out, "<a id=\"{}\"></a>", escape_html(anchor))?;
+                writeln!(out)?;
+                writeln!(out, "{} {}", "#".repeat(level), text)?;
+            }
+            DocFormat::Html => writeln!(
+                out,
+                "<h{} id=\"{}\">{}</h{}>",
+                level,
+                escape_html(anchor),
+                escape_html(text),
+                level
+            )?,
+        }
+
+        Ok(())
+    }
+
+    /// Page titles and sections use the text as is so Markdown renderers gives them the lower
+    /// case text as anchor. HTML uses the same anchor as id
+    fn heading<W: Write>(&self, out: &mut W, level: usize, text: &str) -> Result<()> {
+        match self.format {
+            DocFormat::Markdown => {
+                writeln!(out)?;
+                writeln!(out, "{} {}", "#".repeat(level), text)?;
+            }
+            DocFormat::Html => writeln!(
+                out,
+                "<h{} id=\"{}\">{}</h{}>",
+                level,
+                escape_html(&text.to_lowercase()),
+                escape_html(text),
+                level
+            )?,
+        }
+
+        Ok(())
+    }
+
+    fn docs<W: Write>(&self, out: &mut W, comments: &[String]) -> Result<()> {
+        if comments.iter().all(|c| c.is_empty()) {
+            return Ok(());
+        }
+
+        match self.format {
+            DocFormat::Markdown => {
+                writeln!(out)?;
+
+                for c in comments {
+                    writeln!(out, "{}", c)?;
+                }
+            }
+            DocFormat::Html => writeln!(out, "<p>{}</p>", escape_html(&comments.join("\n")))?,
+        }
+
+        Ok(())
+    }
+
+    /// Paragraph with text that is already formatted
+    fn paragraph(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => format!("\n{}", text),
+            DocFormat::Html => format!("<p>{}</p>", text),
+        }
+    }
+
+    fn code_block<W: Write>(&self, out: &mut W, code: &str) -> Result<()> {
+        match self.format {
+            DocFormat::Markdown => {
+                writeln!(out)?;
+                writeln!(out, "```")?;
+                writeln!(out, "{}", code)?;
+                writeln!(out, "```")?;
+            }
+            DocFormat::Html => writeln!(out, "<pre><code>{}</code></pre>", escape_html(code))?,
+        }
+
+        Ok(())
+    }
+
+    /// Table where the cells are already formatted
+    fn table<W: Write>(&self, out: &mut W, headers: &[&str], rows: Vec<Vec<String>>) -> Result<()> {
+        match self.format {
+            DocFormat::Markdown => {
+                writeln!(out)?;
+                writeln!(out, "| {} |", headers.join(" | "))?;
+                writeln!(out, "|{}", "---|".repeat(headers.len()))?;
+
+                for row in rows {
+                    writeln!(out, "| {} |", row.join(" | "))?;
+                }
+            }
+            DocFormat::Html => {
+                writeln!(out, "<table>")?;
+                writeln!(out, "<tr><th>{}</th></tr>", headers.join("</th><th>"))?;
+
+                for row in rows {
+                    writeln!(out, "<tr><td>{}</td></tr>", row.join("</td><td>"))?;
+                }
+
+                writeln!(out, "</table>")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plain text in a table cell
+    fn text(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => text.replace('|', "\\|"),
+            DocFormat::Html => escape_html(text),
+        }
+    }
+
+    fn code(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => format!("`{}`", text.replace('|', "\\|")),
+            DocFormat::Html => format!("<code>{}</code>", escape_html(text)),
+        }
+    }
+
+    /// Link where `text` is already formatted
+    fn link(&self, text: &str, href: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => format!("[{}]({})", text, href),
+            DocFormat::Html => format!("<a href=\"{}\">{}</a>", escape_html(href), text),
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+/// Pixel format
+enum Format {
+    /// 8 bits per channel
+    Rgb,
+    Rgba,
+}
+
+#[attributes(Handle)]
+struct Image {
+    /// Loads an image
+    [static] create(name: String) -> Image?,
+    get_format() -> Format,
+}
+"#;
+
+    const RENDER: &str = r#"
+struct Target {
+    image: *Image,
+}
+"#;
+
+    fn defs() -> Vec<ApiDef> {
+        let mut defs = vec![
+            ApiParser::parse_string(IMAGE, "defs/image.def").unwrap(),
+            ApiParser::parse_string(RENDER, "defs/render.def").unwrap(),
+        ];
+        resolve(&mut defs);
+        defs
+    }
+
+    fn generate(generator: &DocsGenerator, index: usize) -> String {
+        let defs = defs();
+        let mut out = Vec::new();
+        generator.generate(&mut out, &defs[index], &defs).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_markdown() {
+        let mut generator = DocsGenerator::new(DocFormat::Markdown);
+        let md = generate(&generator, 0);
+
+        assert!(md.contains("# image\n"));
+        assert!(md.contains("<a id=\"struct-image\"></a>\n\n### Image\n"));
+        assert!(md.contains("<a id=\"enum-format\"></a>\n\n### Format\n\nPixel format\n\n| Name | Value | Description |\n|---|---|---|\n| `Rgb` | `0` | 8 bits per channel |"));
+        assert!(md.contains("<a id=\"fn-image-create\"></a>\n\n#### Image::create\n\nLoads an image\n\n```\n[static] create(name: String) -> Image?\n```\n\nUses: [`Image`](#struct-image)"));
+        assert!(md.contains("Uses: [`Format`](#enum-format)"));
+
+        let md = generate(&generator, 1);
+        assert!(md.contains("| `image` | [`*Image`](image.md#struct-image) |  |"));
+
+        generator.show_function_types = false;
+        generator.show_optional = false;
+        assert!(generate(&generator, 0).contains("```\ncreate(name: String) -> Image\n```"));
+
+        let mut out = Vec::new();
+        generator.generate_index(&mut out, &defs()).unwrap();
+        let index = String::from_utf8(out).unwrap();
+        assert!(index.contains("| [`Target`](render.md#struct-target) | struct | render |"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = generate(&DocsGenerator::new(DocFormat::Html), 1);
+
+        assert!(html.contains("<h1 id=\"render\">render</h1>"));
+        assert!(html.contains("<h3 id=\"struct-target\">Target</h3>"));
+        assert!(html.contains(
+            "<tr><td><code>image</code></td><td><a href=\"image.html#struct-image\"><code>*Image</code></a></td><td></td></tr>"
+        ));
+    }
+}
@@ -0,0 +1,1000 @@
+//! Generates a C source file per def file that exposes the C API to Lua.
+//!
+//! Every struct and union gets a metatable named after the C type (`<prefix><Struct>`) that has
+//! the functions that takes `self` as methods. `[static]` functions are added to a table in the
+//! module instead. Handles (and other opaque structs) are userdata holding the pointer and other
+//! structs are userdata holding a copy of the data. The fields of those can be read and written
+//! through `__index`/`__newindex` and `<Struct>.new()` creates one with all fields set to zero.
+//! Structs with a `[drop]` function (or `destroy` with the `Drop` attribute) also uses it as
+//! `__gc`. Only handles the caller owns (see `Function::returns_owned`) gets that metatable.
+//! Borrowed handles uses `<prefix><Struct>_ref` that has the same methods but no `__gc` or drop
+//! function. Functions with arguments or return values that
+//! can't be converted (callbacks, arrays with a size and pointers to primitives) are left out.
+//! The module is opened with `luaopen_<file>` (see `ApiDef::qualified_filename`) so it can be loaded
+//! with `require`.
+use crate::api_parser::{
+    c_const_pointer, c_namespace_prefix, ApiDef, ArrayType, Function, Result, Struct, TypeModifier,
+    Variable, VariableType,
+};
+use crate::generators::c::is_opaque;
+use crate::{get_derived_structs, write_c_commments};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Helpers shared by all functions in the file. They are `inline` so unused ones doesn't warn
+const HELPERS: &str = r#"static inline void* check_handle(lua_State* L, int index, const char* name, const char* ref_name) {
+    void** handle = (void**)luaL_testudata(L, index, name);
+    if (handle == NULL) {
+        handle = (void**)luaL_testudata(L, index, ref_name);
+    }
+    if (handle == NULL) {
+        luaL_argerror(L, index, lua_pushfstring(L, "%s expected", name));
+        return NULL;
+    }
+    if (*handle == NULL) {
+        luaL_argerror(L, index, "handle has been destroyed");
+    }
+    return *handle;
+}
+
+static inline void push_handle(lua_State* L, void* handle, const char* name) {
+    if (handle == NULL) {
+        lua_pushnil(L);
+        return;
+    }
+    *(void**)lua_newuserdata(L, sizeof(void*)) = handle;
+    luaL_setmetatable(L, name);
+}
+
+static inline void push_value(lua_State* L, const void* data, size_t size, const char* name) {
+    if (data == NULL) {
+        lua_pushnil(L);
+        return;
+    }
+    memcpy(lua_newuserdata(L, size), data, size);
+    luaL_setmetatable(L, name);
+}
+"#;
+
+/// Writes Lua bindings
+#[derive(Debug, Clone, Default)]
+pub struct LuaGenerator {
+    /// Has to match the prefix used for the C code
+    pub c_prefix: String,
+}
+
+/// Types from all def files needed to pick how a value is converted
+struct Types<'a> {
+    /// `type` aliases
+    aliases: HashMap<&'a str, &'a Variable>,
+    /// Structs and unions that are used through a pointer
+    opaque: HashMap<&'a str, bool>,
+}
+
+impl<'a> Types<'a> {
+    fn new(api_defs: &'a [ApiDef]) -> Types<'a> {
+        let mut aliases = HashMap::new();
+        let mut opaque = HashMap::new();
+
+        for api_def in api_defs {
+            for t in &api_def.types {
+                aliases.insert(t.var.name.as_str(), &t.var);
+            }
+
+            for s in &api_def.structs {
+                opaque.insert(s.name.as_str(), is_opaque(s));
+            }
+
+            for u in &api_def.unions {
+                opaque.insert(u.name.as_str(), false);
+            }
+        }
+
+        Types { aliases, opaque }
+    }
+
+    /// Replaces aliases in `var` with the types they refer to
+    fn resolve(&self, var: &Variable) -> Variable {
+        let mut var = var.clone();
+
+        // Limit the depth in case an alias refers to itself
+        for _ in 0..16 {
+            if var.vtype != VariableType::Alias {
+                break;
+            }
+
+            match self.aliases.get(var.type_name.as_str()) {
+                Some(target) => {
+                    var.vtype = target.vtype;
+                    var.type_name = target.type_name.to_owned();
+                    var.type_namespace = target.type_namespace.to_owned();
+
+                    if var.type_modifier == TypeModifier::None {
+                        var.type_modifier = target.type_modifier.clone();
+                    }
+
+                    if var.array.is_none() {
+                        var.array = target.array.clone();
+                    }
+                }
+                None => break,
+            }
+        }
+
+        var
+    }
+
+    fn is_opaque(&self, name: &str) -> bool {
+        self.opaque.get(name).copied().unwrap_or(false)
+    }
+}
+
+/// Conversion of a value between C and Lua
+enum Value {
+    Bool,
+    Number,
+    Integer,
+    /// `void` pointer
+    Pointer,
+    Str,
+    /// Unsized array of bytes, passed as a Lua string
+    Bytes,
+    /// Pointer to an opaque struct. Borrowed handles uses the `_ref` metatable
+    Handle(String),
+    /// Copy of a struct
+    Struct(String),
+    /// Pointer to a struct
+    StructPointer(String),
+}
+
+/// A function that can be called from Lua
+struct Wrapper {
+    /// Statements that reads the arguments
+    args: Vec<String>,
+    /// Expressions passed to the C function
+    call_args: Vec<String>,
+    ret: Option<(String, Value)>,
+}
+
+/// Function registered in Lua
+struct LuaFunction {
+    /// Name used from Lua
+    name: String,
+    /// Name of the C function that Lua calls
+    c_name: String,
+    is_static: bool,
+    /// Also used as `__gc`
+    is_drop: bool,
+}
+
+fn is_void(var: &Variable) -> bool {
+    var.vtype == VariableType::Primitive && var.type_name == "void"
+}
+
+fn is_pointer(var: &Variable) -> bool {
+    var.type_modifier != TypeModifier::None
+}
+
+impl LuaGenerator {
+    pub fn new(c_prefix: &str) -> LuaGenerator {
+        LuaGenerator {
+            c_prefix: c_prefix.to_owned(),
+        }
+    }
+
+    /// Name of the file generated for `api_def`
+    pub fn file_name(api_def: &ApiDef) -> String {
+        format!("{}_lua.c", api_def.qualified_filename())
+    }
+
+    /// Writes the bindings for `api_def`. `api_defs` is all the def files and is used to look up
+    /// aliases and structs from other files
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let types = Types::new(api_defs);
+
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+        writeln!(out)?;
+        writeln!(out, "#include \"{}.h\"", api_def.qualified_filename())?;
+        writeln!(out)?;
+        writeln!(out, "#include <lua.h>")?;
+        writeln!(out, "#include <lauxlib.h>")?;
+        writeln!(out, "#include <string.h>")?;
+        writeln!(out)?;
+        write!(out, "{}", HELPERS)?;
+
+        let mut exported = HashMap::new();
+
+        for s in &api_def.structs {
+            let mut funcs = Vec::new();
+
+            for func in &s.functions {
+                if let Some(lua_func) = self.write_function(out, s, func, &types)? {
+                    funcs.push(lua_func);
+                }
+            }
+
+            exported.insert(s.name.as_str(), funcs);
+        }
+
+        for s in api_def.structs.iter().chain(&api_def.unions) {
+            let funcs = self.write_accessors(out, s, api_defs, &types)?;
+            exported.entry(s.name.as_str()).or_default().extend(funcs);
+        }
+
+        for s in api_def.structs.iter().chain(&api_def.unions) {
+            let funcs = exported.get(s.name.as_str()).map_or(&[][..], |f| &f[..]);
+            self.write_function_tables(out, s, funcs)?;
+        }
+
+        self.write_open(out, api_def, &exported)?;
+
+        Ok(())
+    }
+
+    /// Name of the metatable for a struct. This is the name of the C type
+    fn metatable(&self, namespace: &[String], name: &str) -> String {
+        format!("{}{}", c_namespace_prefix(&self.c_prefix, namespace), name)
+    }
+
+    /// Name of the metatable for handles that are borrowed and mustn't be destroyed by Lua
+    fn ref_metatable(metatable: &str) -> String {
+        format!("{}_ref", metatable)
+    }
+
+    /// How `var` is converted or the reason it can't be
+    fn value(&self, var: &Variable, types: &Types) -> std::result::Result<Value, &'static str> {
+        let var = types.resolve(var);
+
+        match var.array {
+            Some(ArrayType::SizedArray(_)) => {
+                return Err("arrays with a size can't be passed from Lua")
+            }
+            Some(ArrayType::Unsized)
+                if var.vtype == VariableType::Primitive
+                    && (var.type_name == "u8" || var.type_name == "i8")
+                    && !is_pointer(&var) =>
+            {
+                return Ok(Value::Bytes)
+            }
+            Some(ArrayType::Unsized) => return Err("only arrays of bytes can be passed from Lua"),
+            None => (),
+        }
+
+        match var.vtype {
+            VariableType::Primitive if is_void(&var) && is_pointer(&var) => Ok(Value::Pointer),
+            VariableType::Primitive if is_pointer(&var) => {
+                Err("pointers to primitives can't be passed from Lua")
+            }
+            VariableType::Primitive if var.type_name == "bool" => Ok(Value::Bool),
+            VariableType::Primitive if var.type_name.starts_with('f') => Ok(Value::Number),
+            VariableType::Primitive => Ok(Value::Integer),
+            VariableType::Enum if !is_pointer(&var) => Ok(Value::Integer),
+            VariableType::Str if !is_pointer(&var) => Ok(Value::Str),
+            VariableType::Handle if !is_pointer(&var) => Ok(Value::Handle(
+                self.metatable(&var.type_namespace, &var.type_name),
+            )),
+            VariableType::Regular if types.is_opaque(&var.type_name) => {
+                Err("opaque structs can only be used as handles")
+            }
+            VariableType::Regular if is_pointer(&var) => Ok(Value::StructPointer(
+                self.metatable(&var.type_namespace, &var.type_name),
+            )),
+            VariableType::Regular => Ok(Value::Struct(
+                self.metatable(&var.type_namespace, &var.type_name),
+            )),
+            VariableType::Callback => Err("callbacks can't be passed from Lua"),
+            _ => Err("the type isn't supported"),
+        }
+    }
+
+    /// Reads the arguments from the Lua stack and picks the conversion of the return value
+    fn wrapper(
+        &self,
+        s: &Struct,
+        func: &Function,
+        types: &Types,
+    ) -> std::result::Result<Wrapper, &'static str> {
+        let mut wrapper = Wrapper {
+            args: Vec::new(),
+            call_args: Vec::new(),
+            ret: None,
+        };
+
+        for (i, arg) in func.function_args.iter().enumerate() {
+            let index = i + 1;
+            let name = &arg.name;
+
+            if arg.name == "va_args" || arg.type_name == "VA_ARGS" {
+                return Err("variadic functions can't be called from Lua");
+            }
+
+            if arg.vtype == VariableType::SelfType {
+                let c_type = self.metatable(&s.namespace, &s.name);
+
+                wrapper.args.push(if is_opaque(s) {
+                    format!(
+                        "{}* {} = ({}*)check_handle(L, {}, \"{}\", \"{}\");",
+                        c_type,
+                        name,
+                        c_type,
+                        index,
+                        c_type,
+                        Self::ref_metatable(&c_type)
+                    )
+                } else {
+                    format!(
+                        "{}* {} = ({}*)luaL_checkudata(L, {}, \"{}\");",
+                        c_type, name, c_type, index, c_type
+                    )
+                });
+                wrapper.call_args.push(name.to_owned());
+                continue;
+            }
+
+            let c_type = arg.c_type(&s.name, &self.c_prefix);
+
+            let read = match self.value(arg, types)? {
+                Value::Bool => format!("lua_toboolean(L, {})", index),
+                Value::Number => format!("({})luaL_checknumber(L, {})", c_type, index),
+                Value::Integer => format!("({})luaL_checkinteger(L, {})", c_type, index),
+                Value::Pointer => format!("lua_touserdata(L, {})", index),
+                Value::Str => format!("luaL_checkstring(L, {})", index),
+                Value::Bytes => {
+                    wrapper.args.push(format!("size_t {}_size;", name));
+                    wrapper.args.push(format!(
+                        "const char* {} = luaL_checklstring(L, {}, &{}_size);",
+                        name, index, name
+                    ));
+                    wrapper
+                        .call_args
+                        .push(format!("({}){}", c_const_pointer(&c_type), name));
+                    wrapper.call_args.push(format!("{}_size", name));
+                    continue;
+                }
+                Value::Handle(mt) => format!(
+                    "({})check_handle(L, {}, \"{}\", \"{}\")",
+                    c_type,
+                    index,
+                    mt,
+                    Self::ref_metatable(&mt)
+                ),
+                Value::Struct(mt) => {
+                    format!("*({}*)luaL_checkudata(L, {}, \"{}\")", mt, index, mt)
+                }
+                Value::StructPointer(mt) => {
+                    format!("({}*)luaL_checkudata(L, {}, \"{}\")", mt, index, mt)
+                }
+            };
+
+            wrapper
+                .args
+                .push(format!("{} {} = {};", c_type, name, read));
+            wrapper.call_args.push(name.to_owned());
+        }
+
+        if let Some(ret) = func.return_val.as_ref() {
+            if !is_void(ret) || is_pointer(ret) {
+                let value = match self.value(ret, types)? {
+                    Value::Bytes => return Err("arrays can't be returned to Lua"),
+                    value => value,
+                };
+
+                wrapper.ret = Some((ret.c_type("", &self.c_prefix), value));
+            }
+        }
+
+        Ok(wrapper)
+    }
+
+    /// Writes the C function called by Lua and returns the name it's exported as. Functions
+    /// that can't be called from Lua are written as a comment
+    fn write_function<W: Write>(
+        &self,
+        out: &mut W,
+        s: &Struct,
+        func: &Function,
+        types: &Types,
+    ) -> Result<Option<LuaFunction>> {
+        let c_name = func.get_c_name(&s.name, &self.c_prefix);
+        let lua_name = format!("l_{}", c_name);
+
+        // The handle is cleared when destroyed so `__gc` doesn't destroy it again
+        if s.is_drop_function(func)
+            && is_opaque(s)
+            && func.function_args.len() == 1
+            && func.return_val.is_none()
+        {
+            let c_type = self.metatable(&s.namespace, &s.name);
+
+            writeln!(out)?;
+            write_c_commments(out, &func.doc_comments, 0)?;
+            writeln!(out, "static int {}(lua_State* L) {{", lua_name)?;
+            writeln!(
+                out,
+                "    {}** handle = ({}**)luaL_checkudata(L, 1, \"{}\");",
+                c_type, c_type, c_type
+            )?;
+            writeln!(out, "    if (*handle != NULL) {{")?;
+            writeln!(out, "        {}(*handle);", c_name)?;
+            writeln!(out, "        *handle = NULL;")?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "    return 0;")?;
+            writeln!(out, "}}")?;
+
+            return Ok(Some(LuaFunction {
+                name: func.name.to_owned(),
+                c_name: lua_name,
+                is_static: false,
+                is_drop: true,
+            }));
+        }
+
+        let wrapper = match self.wrapper(s, func, types) {
+            Ok(wrapper) => wrapper,
+            Err(reason) => {
+                writeln!(out)?;
+                writeln!(out, "// {} is not exported: {}", c_name, reason)?;
+                return Ok(None);
+            }
+        };
+
+        writeln!(out)?;
+        write_c_commments(out, &func.doc_comments, 0)?;
+        writeln!(out, "static int {}(lua_State* L) {{", lua_name)?;
+
+        for arg in &wrapper.args {
+            writeln!(out, "    {}", arg)?;
+        }
+
+        let call = format!("{}({})", c_name, wrapper.call_args.join(", "));
+
+        match wrapper.ret {
+            None => {
+                writeln!(out, "    {};", call)?;
+                writeln!(out, "    return 0;")?;
+            }
+            Some((c_type, value)) => {
+                writeln!(out, "    {} ret = {};", c_type, call)?;
+
+                match value {
+                    Value::Bool => writeln!(out, "    lua_pushboolean(L, ret);")?,
+                    Value::Number => writeln!(out, "    lua_pushnumber(L, (lua_Number)ret);")?,
+                    Value::Integer => writeln!(out, "    lua_pushinteger(L, (lua_Integer)ret);")?,
+                    Value::Pointer => writeln!(out, "    lua_pushlightuserdata(L, (void*)ret);")?,
+                    Value::Str => writeln!(out, "    lua_pushstring(L, ret);")?,
+                    Value::Bytes => unreachable!("arrays are never returned"),
+                    Value::Handle(mt) if func.returns_owned() => {
+                        writeln!(out, "    push_handle(L, (void*)ret, \"{}\");", mt)?
+                    }
+                    Value::Handle(mt) => writeln!(
+                        out,
+                        "    push_handle(L, (void*)ret, \"{}\");",
+                        Self::ref_metatable(&mt)
+                    )?,
+                    Value::Struct(mt) => {
+                        writeln!(out, "    push_value(L, &ret, sizeof(ret), \"{}\");", mt)?
+                    }
+                    Value::StructPointer(mt) => {
+                        writeln!(out, "    push_value(L, ret, sizeof(*ret), \"{}\");", mt)?
+                    }
+                }
+
+                writeln!(out, "    return 1;")?;
+            }
+        }
+
+        writeln!(out, "}}")?;
+
+        Ok(Some(LuaFunction {
+            name: func.name.to_owned(),
+            c_name: lua_name,
+            is_static: func.is_type_static(),
+            is_drop: false,
+        }))
+    }
+
+    /// Writes `__index` and `__newindex` for the fields of a struct that is held by value and
+    /// `new` that creates one. Fields that can't be converted are left out
+    fn write_accessors<W: Write>(
+        &self,
+        out: &mut W,
+        s: &Struct,
+        api_defs: &[ApiDef],
+        types: &Types,
+    ) -> Result<Vec<LuaFunction>> {
+        let derived = get_derived_structs(api_defs, s);
+        let vars = derived
+            .iter()
+            .flat_map(|d| d.variables.iter())
+            .chain(s.variables.iter())
+            .collect::<Vec<_>>();
+
+        if is_opaque(s) || vars.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let c_type = self.metatable(&s.namespace, &s.name);
+        let mut fields = Vec::with_capacity(vars.len());
+        let count = vars.len();
+
+        writeln!(out)?;
+
+        for var in vars {
+            match self.value(var, types) {
+                // Handles would be destroyed when the copy is collected and strings would point
+                // to memory owned by Lua so they can't be fields
+                Ok(Value::Handle(_)) => writeln!(
+                    out,
+                    "// {}.{} is not exported: handles can't be fields",
+                    c_type, var.name
+                )?,
+                Ok(Value::Str) | Ok(Value::Bytes) | Ok(Value::StructPointer(_)) => writeln!(
+                    out,
+                    "// {}.{} is not exported: pointers can't be fields",
+                    c_type, var.name
+                )?,
+                Ok(value) => fields.push((var, value)),
+                Err(reason) => writeln!(
+                    out,
+                    "// {}.{} is not exported: {}",
+                    c_type, var.name, reason
+                )?,
+            }
+        }
+
+        if fields.len() < count {
+            writeln!(out)?;
+        }
+
+        let check_self = format!(
+            "    {}* self = ({}*)luaL_checkudata(L, 1, \"{}\");",
+            c_type, c_type, c_type
+        );
+
+        writeln!(out, "static int l_{}_index(lua_State* L) {{", c_type)?;
+        writeln!(out, "{}", check_self)?;
+        writeln!(out, "    const char* key = luaL_checkstring(L, 2);")?;
+
+        for (var, value) in &fields {
+            let push = match value {
+                Value::Bool => format!("lua_pushboolean(L, self->{});", var.name),
+                Value::Number => format!("lua_pushnumber(L, (lua_Number)self->{});", var.name),
+                Value::Integer => format!("lua_pushinteger(L, (lua_Integer)self->{});", var.name),
+                Value::Pointer => format!("lua_pushlightuserdata(L, (void*)self->{});", var.name),
+                Value::Struct(mt) => format!(
+                    "push_value(L, &self->{}, sizeof(self->{}), \"{}\");",
+                    var.name, var.name, mt
+                ),
+                _ => unreachable!("only values are fields"),
+            };
+
+            writeln!(out, "    if (strcmp(key, \"{}\") == 0) {{", var.name)?;
+            writeln!(out, "        {}", push)?;
+            writeln!(out, "        return 1;")?;
+            writeln!(out, "    }}")?;
+        }
+
+        writeln!(out, "    luaL_getmetatable(L, \"{}\");", c_type)?;
+        writeln!(out, "    lua_getfield(L, -1, key);")?;
+        writeln!(out, "    return 1;")?;
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "static int l_{}_newindex(lua_State* L) {{", c_type)?;
+        writeln!(out, "{}", check_self)?;
+        writeln!(out, "    const char* key = luaL_checkstring(L, 2);")?;
+
+        for (var, value) in &fields {
+            let field_type = var.c_type("", &self.c_prefix);
+
+            let read = match value {
+                Value::Bool => "lua_toboolean(L, 3)".to_owned(),
+                Value::Number => format!("({})luaL_checknumber(L, 3)", field_type),
+                Value::Integer => format!("({})luaL_checkinteger(L, 3)", field_type),
+                Value::Pointer => "lua_touserdata(L, 3)".to_owned(),
+                Value::Struct(mt) => format!("*({}*)luaL_checkudata(L, 3, \"{}\")", mt, mt),
+                _ => unreachable!("only values are fields"),
+            };
+
+            writeln!(out, "    if (strcmp(key, \"{}\") == 0) {{", var.name)?;
+            writeln!(out, "        self->{} = {};", var.name, read)?;
+            writeln!(out, "        return 0;")?;
+            writeln!(out, "    }}")?;
+        }
+
+        writeln!(
+            out,
+            "    return luaL_error(L, \"{} has no field '%s'\", key);",
+            c_type
+        )?;
+        writeln!(out, "}}")?;
+        let mut funcs = vec![
+            LuaFunction {
+                name: "__index".to_owned(),
+                c_name: format!("l_{}_index", c_type),
+                is_static: false,
+                is_drop: false,
+            },
+            LuaFunction {
+                name: "__newindex".to_owned(),
+                c_name: format!("l_{}_newindex", c_type),
+                is_static: false,
+                is_drop: false,
+            },
+        ];
+
+        // A static function can already be called `new`
+        if s.functions.iter().any(|f| f.name == "new") {
+            return Ok(funcs);
+        }
+
+        writeln!(out)?;
+        writeln!(out, "static int l_{}_new(lua_State* L) {{", c_type)?;
+        writeln!(
+            out,
+            "    memset(lua_newuserdata(L, sizeof({})), 0, sizeof({}));",
+            c_type, c_type
+        )?;
+        writeln!(out, "    luaL_setmetatable(L, \"{}\");", c_type)?;
+        writeln!(out, "    return 1;")?;
+        writeln!(out, "}}")?;
+
+        funcs.push(LuaFunction {
+            name: "new".to_owned(),
+            c_name: format!("l_{}_new", c_type),
+            is_static: true,
+            is_drop: false,
+        });
+
+        Ok(funcs)
+    }
+
+    /// Methods for the metatable and the `[static]` functions for the table in the module
+    fn write_function_tables<W: Write>(
+        &self,
+        out: &mut W,
+        s: &Struct,
+        funcs: &[LuaFunction],
+    ) -> Result<()> {
+        let c_type = self.metatable(&s.namespace, &s.name);
+
+        writeln!(out)?;
+        writeln!(out, "static const luaL_Reg l_{}_methods[] = {{", c_type)?;
+
+        for func in funcs.iter().filter(|f| !f.is_static) {
+            writeln!(out, "    {{\"{}\", {}}},", func.name, func.c_name)?;
+
+            if func.is_drop {
+                writeln!(out, "    {{\"__gc\", {}}},", func.c_name)?;
+            }
+        }
+
+        writeln!(out, "    {{NULL, NULL}},")?;
+        writeln!(out, "}};")?;
+
+        // Borrowed handles can call the same methods but can't be destroyed
+        if is_opaque(s) {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "static const luaL_Reg l_{}_methods[] = {{",
+                Self::ref_metatable(&c_type)
+            )?;
+
+            for func in funcs.iter().filter(|f| !f.is_static && !f.is_drop) {
+                writeln!(out, "    {{\"{}\", {}}},", func.name, func.c_name)?;
+            }
+
+            writeln!(out, "    {{NULL, NULL}},")?;
+            writeln!(out, "}};")?;
+        }
+
+        if funcs.iter().any(|f| f.is_static) {
+            writeln!(out)?;
+            writeln!(out, "static const luaL_Reg l_{}_functions[] = {{", c_type)?;
+
+            for func in funcs.iter().filter(|f| f.is_static) {
+                writeln!(out, "    {{\"{}\", {}}},", func.name, func.c_name)?;
+            }
+
+            writeln!(out, "    {{NULL, NULL}},")?;
+            writeln!(out, "}};")?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the module table with consts, enums and `[static]` functions and registers the
+    /// metatables
+    fn write_open<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        exported: &HashMap<&str, Vec<LuaFunction>>,
+    ) -> Result<()> {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "int luaopen_{}(lua_State* L) {{",
+            api_def.qualified_filename()
+        )?;
+        writeln!(out, "    lua_newtable(L);")?;
+
+        for c in api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+        {
+            let push = if c.value.starts_with('"') {
+                "lua_pushstring"
+            } else if c.value.contains('.') {
+                "lua_pushnumber"
+            } else if c.value.starts_with(|c: char| c.is_ascii_digit()) {
+                "lua_pushinteger"
+            } else {
+                continue;
+            };
+
+            writeln!(out)?;
+            writeln!(
+                out,
+                "    {}(L, {}{});",
+                push,
+                c_namespace_prefix(&self.c_prefix, &c.namespace),
+                c.name
+            )?;
+            writeln!(out, "    lua_setfield(L, -2, \"{}\");", c.name)?;
+        }
+
+        for e in &api_def.enums {
+            writeln!(out)?;
+            writeln!(out, "    lua_newtable(L);")?;
+
+            for entry in &e.entries {
+                writeln!(
+                    out,
+                    "    lua_pushinteger(L, {}{}_{});",
+                    c_namespace_prefix(&self.c_prefix, &e.namespace),
+                    e.name,
+                    entry.name
+                )?;
+                writeln!(out, "    lua_setfield(L, -2, \"{}\");", entry.name)?;
+            }
+
+            writeln!(out, "    lua_setfield(L, -2, \"{}\");", e.name)?;
+        }
+
+        for s in api_def.structs.iter().chain(&api_def.unions) {
+            let c_type = self.metatable(&s.namespace, &s.name);
+            let funcs = exported.get(s.name.as_str()).map_or(&[][..], |f| &f[..]);
+
+            writeln!(out)?;
+            writeln!(out, "    luaL_newmetatable(L, \"{}\");", c_type)?;
+
+            // Structs with fields has an `__index` function that also looks up the methods
+            if !funcs.iter().any(|f| f.name == "__index") {
+                writeln!(out, "    lua_pushvalue(L, -1);")?;
+                writeln!(out, "    lua_setfield(L, -2, \"__index\");")?;
+            }
+
+            writeln!(out, "    luaL_setfuncs(L, l_{}_methods, 0);", c_type)?;
+            writeln!(out, "    lua_pop(L, 1);")?;
+
+            if is_opaque(s) {
+                let ref_type = Self::ref_metatable(&c_type);
+
+                writeln!(out)?;
+                writeln!(out, "    luaL_newmetatable(L, \"{}\");", ref_type)?;
+                writeln!(out, "    lua_pushvalue(L, -1);")?;
+                writeln!(out, "    lua_setfield(L, -2, \"__index\");")?;
+                writeln!(out, "    luaL_setfuncs(L, l_{}_methods, 0);", ref_type)?;
+                writeln!(out, "    lua_pop(L, 1);")?;
+            }
+
+            let has_static = funcs.iter().any(|f| f.is_static);
+
+            if has_static {
+                writeln!(out)?;
+                writeln!(out, "    lua_newtable(L);")?;
+                writeln!(out, "    luaL_setfuncs(L, l_{}_functions, 0);", c_type)?;
+                writeln!(out, "    lua_setfield(L, -2, \"{}\");", s.name)?;
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    return 1;")?;
+        writeln!(out, "}}")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::{test_util, GeneratorOptions, MemorySink, Registry};
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+struct ImageInfo {
+    width: u32,
+    format: Format,
+}
+
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+callback LoadCallback(image: Image)
+
+#[attributes(Handle, Drop)]
+struct Image {
+    [static] create_from_memory(name: String, data: [u8], format: Format) -> Image?,
+    get_info() -> *const ImageInfo?,
+    get_parent() -> Image,
+    [owned] clone() -> Image,
+    set_scale(scale: f32),
+    set_callback(callback: LoadCallback),
+    destroy(),
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        LuaGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bindings() {
+        let c = generate();
+
+        assert!(c.contains(
+            "static int l_FL_Image_create_from_memory(lua_State* L) {\n    const char* name = luaL_checkstring(L, 1);\n    size_t data_size;\n    const char* data = luaL_checklstring(L, 2, &data_size);\n    FL_Format format = (FL_Format)luaL_checkinteger(L, 3);\n    FL_Image* ret = FL_Image_create_from_memory(name, (const uint8_t*)data, data_size, format);\n    push_handle(L, (void*)ret, \"FL_Image\");\n    return 1;\n}"
+        ));
+        assert!(c.contains(
+            "    const FL_ImageInfo* ret = FL_Image_get_info(self);\n    push_value(L, ret, sizeof(*ret), \"FL_ImageInfo\");"
+        ));
+        assert!(c.contains("    float scale = (float)luaL_checknumber(L, 2);"));
+        assert!(c.contains(
+            "// FL_Image_set_callback is not exported: callbacks can't be passed from Lua"
+        ));
+        assert!(c.contains(
+            "    {\"destroy\", l_FL_Image_destroy},\n    {\"__gc\", l_FL_Image_destroy},"
+        ));
+        assert!(c.contains("    {\"create_from_memory\", l_FL_Image_create_from_memory},"));
+        assert!(c.contains(
+            "    lua_pushinteger(L, FL_Format_Rgba);\n    lua_setfield(L, -2, \"Rgba\");"
+        ));
+        assert!(c.contains("    luaL_newmetatable(L, \"FL_ImageInfo\");"));
+
+        // Borrowed handles gets a metatable without `__gc` so Lua doesn't destroy them
+        assert!(c.contains(
+            "    FL_Image* ret = FL_Image_get_parent(self);\n    push_handle(L, (void*)ret, \"FL_Image_ref\");"
+        ));
+        assert!(c.contains(
+            "    FL_Image* ret = FL_Image_clone(self);\n    push_handle(L, (void*)ret, \"FL_Image\");"
+        ));
+        assert!(c.contains(
+            "    FL_Image* self = (FL_Image*)check_handle(L, 1, \"FL_Image\", \"FL_Image_ref\");"
+        ));
+        assert!(c.contains("static const luaL_Reg l_FL_Image_ref_methods[] = {\n    {\"get_info\", l_FL_Image_get_info},\n    {\"get_parent\", l_FL_Image_get_parent},\n    {\"clone\", l_FL_Image_clone},\n    {\"set_scale\", l_FL_Image_set_scale},\n    {NULL, NULL},\n};"));
+        assert!(c.contains("    luaL_newmetatable(L, \"FL_Image_ref\");\n    lua_pushvalue(L, -1);\n    lua_setfield(L, -2, \"__index\");\n    luaL_setfuncs(L, l_FL_Image_ref_methods, 0);"));
+    }
+
+    const POD: &str = r#"
+struct Color {
+    r: f32,
+    g: f32,
+    b: f32,
+}
+
+struct Base {
+    id: u64,
+}
+
+struct ImageInfo : Base {
+    width: u32,
+    visible: bool,
+    user_data: *void,
+    color: Color,
+    name: [u8; 16],
+    owner: Image,
+}
+
+#[attributes(Handle)]
+struct Image {
+    [static] create(info: ImageInfo) -> Image,
+    get_info() -> ImageInfo,
+    get_parent() -> Image?,
+}
+"#;
+
+    /// Declarations of the parts of the Lua API the bindings use so they can be compiled without
+    /// Lua installed
+    const LUA_STUBS: &str = "typedef struct lua_State lua_State;\ntypedef double lua_Number;\ntypedef long long lua_Integer;\ntypedef int (*lua_CFunction)(lua_State* L);\nvoid lua_pushnil(lua_State* L);\nvoid lua_pushboolean(lua_State* L, int b);\nvoid lua_pushnumber(lua_State* L, lua_Number n);\nvoid lua_pushinteger(lua_State* L, lua_Integer n);\nvoid lua_pushlightuserdata(lua_State* L, void* p);\nconst char* lua_pushstring(lua_State* L, const char* s);\nconst char* lua_pushfstring(lua_State* L, const char* fmt, ...);\nvoid lua_pushvalue(lua_State* L, int index);\nint lua_toboolean(lua_State* L, int index);\nvoid* lua_touserdata(lua_State* L, int index);\nvoid* lua_newuserdata(lua_State* L, size_t size);\nvoid lua_newtable(lua_State* L);\nint lua_getfield(lua_State* L, int index, const char* k);\nvoid lua_setfield(lua_State* L, int index, const char* k);\nvoid lua_pop(lua_State* L, int n);\n";
+    const LAUXLIB_STUBS: &str = "typedef struct luaL_Reg {\n    const char* name;\n    lua_CFunction func;\n} luaL_Reg;\nvoid* luaL_checkudata(lua_State* L, int arg, const char* tname);\nvoid* luaL_testudata(lua_State* L, int arg, const char* tname);\nint luaL_argerror(lua_State* L, int arg, const char* extramsg);\nvoid luaL_setmetatable(lua_State* L, const char* tname);\nint luaL_getmetatable(lua_State* L, const char* tname);\nint luaL_newmetatable(lua_State* L, const char* tname);\nvoid luaL_setfuncs(lua_State* L, const luaL_Reg* l, int nup);\nconst char* luaL_checkstring(lua_State* L, int arg);\nconst char* luaL_checklstring(lua_State* L, int arg, size_t* l);\nlua_Number luaL_checknumber(lua_State* L, int arg);\nlua_Integer luaL_checkinteger(lua_State* L, int arg);\nint luaL_error(lua_State* L, const char* fmt, ...);\n";
+
+    #[test]
+    fn test_pod_fields() {
+        let defs = test_util::parse(&[("image.def", POD)]);
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let registry = Registry::with_builtins();
+        let mut sink = MemorySink::default();
+
+        for name in ["c", "lua"] {
+            registry
+                .get(name)
+                .unwrap()
+                .generate(&defs, &options, &mut sink)
+                .unwrap();
+        }
+
+        let c = String::from_utf8(sink.files["image_lua.c"].clone()).unwrap();
+
+        assert!(c.contains("static int l_FL_Color_index(lua_State* L) {\n    FL_Color* self = (FL_Color*)luaL_checkudata(L, 1, \"FL_Color\");\n    const char* key = luaL_checkstring(L, 2);\n    if (strcmp(key, \"r\") == 0) {\n        lua_pushnumber(L, (lua_Number)self->r);\n        return 1;\n    }"));
+        assert!(c.contains("    if (strcmp(key, \"b\") == 0) {\n        self->b = (float)luaL_checknumber(L, 3);\n        return 0;\n    }\n    return luaL_error(L, \"FL_Color has no field '%s'\", key);"));
+        assert!(c.contains("        lua_pushinteger(L, (lua_Integer)self->id);"));
+        assert!(c.contains("        self->visible = lua_toboolean(L, 3);"));
+        assert!(c.contains("        self->user_data = lua_touserdata(L, 3);"));
+        assert!(
+            c.contains("        push_value(L, &self->color, sizeof(self->color), \"FL_Color\");")
+        );
+        assert!(
+            c.contains("        self->color = *(FL_Color*)luaL_checkudata(L, 3, \"FL_Color\");")
+        );
+        assert!(c.contains(
+            "// FL_ImageInfo.name is not exported: arrays with a size can't be passed from Lua"
+        ));
+        assert!(c.contains("// FL_ImageInfo.owner is not exported: handles can't be fields"));
+        assert!(c.contains("    {\"__index\", l_FL_ImageInfo_index},\n    {\"__newindex\", l_FL_ImageInfo_newindex},"));
+        assert!(c.contains("    {\"new\", l_FL_ImageInfo_new},"));
+        assert!(c.contains("    luaL_newmetatable(L, \"FL_ImageInfo\");\n    luaL_setfuncs(L, l_FL_ImageInfo_methods, 0);"));
+        assert!(c.contains("    luaL_newmetatable(L, \"FL_Image\");\n    lua_pushvalue(L, -1);\n    lua_setfield(L, -2, \"__index\");"));
+        assert!(!c.contains("l_FL_Image_index"));
+
+        let mut files = sink
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect::<Vec<_>>();
+        let lua = format!("#include <stddef.h>\n{}", LUA_STUBS);
+        let lauxlib = format!("#include <lua.h>\n{}", LAUXLIB_STUBS);
+        files.push(("lua.h", lua.as_bytes()));
+        files.push(("lauxlib.h", lauxlib.as_bytes()));
+
+        test_util::compiles(
+            "lua",
+            &files,
+            "cc",
+            &[
+                "-std=c11",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-I.",
+                "-fsyntax-only",
+                "image_lua.c",
+            ],
+        );
+    }
+}
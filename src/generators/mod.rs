@@ -0,0 +1,321 @@
+//! Code generators that writes out the parsed data for different languages
+//!
+//! Each backend implements [`Generator`] and is looked up by name in a [`Registry`]. The generator
+//! gets all the resolved def files and the [`GeneratorOptions`] and writes its files to an
+//! [`OutputSink`]. Other crates can add their own backends by implementing [`Generator`] and
+//! registering it.
+use crate::api_parser::{ApiDef, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub mod backends;
+pub mod c;
+pub mod cpp;
+pub mod csharp;
+pub mod docs;
+pub mod lua;
+pub mod python;
+pub mod rust;
+pub mod rust_ffi;
+#[cfg(feature = "template")]
+pub mod template;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod util;
+pub mod zig;
+
+/// Options for all generators. Generators only use the ones that applies to them
+#[derive(Debug, Clone, Default)]
+pub struct GeneratorOptions {
+    /// Added in front of all C types and functions
+    pub c_prefix: String,
+    /// C++/C# namespace or Python module name. See [`GeneratorOptions::namespace`]
+    pub namespace: Option<String>,
+    /// Library to load the functions from. See [`GeneratorOptions::library`]
+    pub library: Option<String>,
+    /// Module with the `rust-ffi` code used by the `rust` generator (default `crate::ffi`)
+    pub ffi_module: Option<String>,
+    /// Don't show `[static]`, `[manual]`, `[drop]`, `[owned]` and `[borrowed]` in docs
+    pub hide_function_types: bool,
+    /// Don't show `?` on optional values in docs
+    pub hide_optional: bool,
+    /// Template used by the `template` generator
+    pub template: Option<PathBuf>,
+    /// Options for generators that isn't part of apigen
+    pub extra: BTreeMap<String, String>,
+}
+
+impl GeneratorOptions {
+    /// The namespace or the C prefix in lower case (`FL_` becomes `fl`) if it isn't set. `api` is
+    /// used if both are empty
+    pub fn namespace(&self) -> String {
+        match self.namespace.as_ref() {
+            Some(namespace) => namespace.to_owned(),
+            None => match self.c_prefix.trim_matches('_').to_lowercase() {
+                prefix if prefix.is_empty() => "api".to_owned(),
+                prefix => prefix,
+            },
+        }
+    }
+
+    /// Name of the C library. Defaults to the namespace
+    pub fn library(&self) -> String {
+        self.library.clone().unwrap_or_else(|| self.namespace())
+    }
+
+    pub fn ffi_module(&self) -> &str {
+        self.ffi_module.as_deref().unwrap_or("crate::ffi")
+    }
+}
+
+/// Where generators write their files
+pub trait OutputSink {
+    /// Writes the file `name`. The name is relative to the output (such as `image.h`)
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()>;
+}
+
+/// Writes files to a directory. The directory is created if it doesn't exist
+#[derive(Debug, Clone)]
+pub struct DirSink {
+    pub dir: PathBuf,
+}
+
+impl DirSink {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> DirSink {
+        DirSink { dir: dir.into() }
+    }
+}
+
+impl OutputSink for DirSink {
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.dir.join(name);
+
+        let write = || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(&path, data)
+        };
+
+        write().map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("unable to write {}: {}", path.display(), e),
+            )
+            .into()
+        })
+    }
+}
+
+/// Keeps the files in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl OutputSink for MemorySink {
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.files.insert(name.to_owned(), data.to_vec());
+        Ok(())
+    }
+}
+
+/// A backend that writes files for the def files
+pub trait Generator: Send + Sync {
+    /// Name used to select the generator (such as `c` or `rust-ffi`)
+    fn name(&self) -> &str;
+
+    /// One line description of the output
+    fn description(&self) -> &str;
+
+    /// Writes all files for `api_defs` to `sink`. The def files has been resolved
+    fn generate(
+        &self,
+        api_defs: &[ApiDef],
+        options: &GeneratorOptions,
+        sink: &mut dyn OutputSink,
+    ) -> Result<()>;
+}
+
+/// Generators by name
+#[derive(Default)]
+pub struct Registry {
+    generators: BTreeMap<String, Box<dyn Generator>>,
+}
+
+impl Registry {
+    /// Registry without any generators
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Registry with all generators that are part of apigen
+    pub fn with_builtins() -> Registry {
+        let mut registry = Registry::new();
+        backends::register_builtins(&mut registry);
+        registry
+    }
+
+    /// Adds a generator. A generator with the same name is replaced
+    pub fn register(&mut self, generator: Box<dyn Generator>) {
+        self.generators
+            .insert(generator.name().to_owned(), generator);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Generator> {
+        self.generators.get(name).map(|g| g.as_ref())
+    }
+
+    /// All generators sorted by name
+    pub fn generators(&self) -> impl Iterator<Item = &dyn Generator> {
+        self.generators.values().map(|g| g.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::resolver::resolve;
+
+    struct Names;
+
+    impl Generator for Names {
+        fn name(&self) -> &str {
+            "names"
+        }
+
+        fn description(&self) -> &str {
+            "Names of the def files"
+        }
+
+        fn generate(
+            &self,
+            api_defs: &[ApiDef],
+            options: &GeneratorOptions,
+            sink: &mut dyn OutputSink,
+        ) -> Result<()> {
+            for api_def in api_defs {
+                let text = format!("{}{}", options.c_prefix, api_def.base_filename);
+                sink.write_file(&format!("{}.txt", api_def.base_filename), text.as_bytes())?;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut defs = vec![ApiParser::parse_string(
+            "#[attributes(Handle)]\nstruct Image {\n destroy(),\n}\n",
+            "defs/image.def",
+        )
+        .unwrap()];
+        resolve(&mut defs);
+
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let mut registry = Registry::with_builtins();
+        registry.register(Box::new(Names));
+
+        let names = registry.generators().map(|g| g.name()).collect::<Vec<_>>();
+        assert!(names.contains(&"c") && names.contains(&"rust-ffi") && names.contains(&"names"));
+
+        let mut sink = MemorySink::default();
+        registry
+            .get("names")
+            .unwrap()
+            .generate(&defs, &options, &mut sink)
+            .unwrap();
+        assert_eq!(sink.files["image.txt"], b"FL_image");
+
+        let mut sink = MemorySink::default();
+        registry
+            .get("rust")
+            .unwrap()
+            .generate(&defs, &options, &mut sink)
+            .unwrap();
+        assert_eq!(
+            sink.files.keys().collect::<Vec<_>>(),
+            ["image.rs", "mod.rs"]
+        );
+    }
+
+    #[test]
+    fn test_same_file_names() {
+        let defs = test_util::parse(&[
+            (
+                "render/gl/texture.def",
+                "namespace render::gl\nstruct Texture {\n    width: u32,\n}",
+            ),
+            (
+                "ui/texture.def",
+                "import \"../render/gl/texture.def\"\nstruct Widget {\n    texture: render::gl::Texture,\n}",
+            ),
+        ]);
+        assert_eq!(
+            defs[1].structs[0].variables[0].type_def_file,
+            "render_gl_texture"
+        );
+
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let registry = Registry::with_builtins();
+
+        for (name, expected) in [
+            ("c", ["render_gl_texture.h", "texture.h"]),
+            ("cpp", ["render_gl_texture.hpp", "texture.hpp"]),
+            ("csharp", ["render_gl_texture.cs", "texture.cs"]),
+            ("lua", ["render_gl_texture_lua.c", "texture_lua.c"]),
+            ("markdown", ["render_gl_texture.md", "texture.md"]),
+            ("rust-ffi", ["render/gl/texture.rs", "texture.rs"]),
+            ("zig", ["render_gl_texture.zig", "texture.zig"]),
+        ] {
+            let mut sink = MemorySink::default();
+            registry
+                .get(name)
+                .unwrap()
+                .generate(&defs, &options, &mut sink)
+                .unwrap();
+
+            for file in expected {
+                assert!(
+                    sink.files.contains_key(file),
+                    "{} didn't write {}",
+                    name,
+                    file
+                );
+            }
+        }
+
+        let mut sink = MemorySink::default();
+        registry
+            .get("c")
+            .unwrap()
+            .generate(&defs, &options, &mut sink)
+            .unwrap();
+        let header = String::from_utf8(sink.files["texture.h"].clone()).unwrap();
+        assert!(header.contains("#include \"render_gl_texture.h\""));
+
+        let mut files = sink
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect::<Vec<_>>();
+        let main = b"#include \"texture.h\"\n#include \"render_gl_texture.h\"\n";
+        files.push(("main.c", main));
+        test_util::compiles(
+            "same_file_names",
+            &files,
+            "cc",
+            &["-std=c11", "-Wall", "-Werror", "-fsyntax-only", "main.c"],
+        );
+    }
+}
@@ -0,0 +1,682 @@
+//! Generates a Python module that uses `ctypes` to call the C library.
+//!
+//! All def files are written to one module. Structs and unions become `ctypes.Structure` and
+//! `ctypes.Union` subclasses (with `_fields_` set after all classes are declared so they can refer
+//! to each other). ctypes doesn't allow `_fields_` to be set after the class has been used by value
+//! in another class so they are set in dependency order across all files. Enums become `IntEnum` or `IntFlag` and callbacks `CFUNCTYPE` types. The
+//! functions use the same C ABI as the C header, so unsized arrays are passed as a pointer and a
+//! `<name>_size` argument. `load(path)` loads the library and sets `argtypes` and `restype` on all
+//! functions. Names that are keywords in Python gets a `_` suffix.
+use crate::api_parser::{
+    ApiDef, ArrayType, EnumType, Function, Result, Struct, TypeModifier, Variable, VariableType,
+};
+use crate::generators::util::{write_comments, CommentStyle};
+use crate::get_derived_structs;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+/// argtypes longer than this are split into one argument per line
+const MAX_LINE_LENGTH: usize = 99;
+
+/// Keywords in Python. Names that match these gets a `_` suffix
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Writes Python ctypes bindings
+#[derive(Debug, Clone, Default)]
+pub struct PythonGenerator {
+    /// Has to match the prefix used for the C code
+    pub c_prefix: String,
+    /// Name of the generated module
+    pub module_name: String,
+}
+
+fn py_name(name: &str) -> String {
+    if PYTHON_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Writes `"""doc"""` for a class
+fn write_docstring<W: Write>(out: &mut W, comments: &[String]) -> Result<bool> {
+    let comments = comments
+        .iter()
+        .map(|c| c.trim_end().replace("\"\"\"", "\\\"\\\"\\\""))
+        .collect::<Vec<_>>();
+
+    match comments.len() {
+        0 => return Ok(false),
+        1 => writeln!(out, "    \"\"\"{}\"\"\"", comments[0])?,
+        _ => {
+            writeln!(out, "    \"\"\"{}", comments[0])?;
+
+            for c in &comments[1..] {
+                if c.is_empty() {
+                    writeln!(out)?;
+                } else {
+                    writeln!(out, "    {}", c)?;
+                }
+            }
+
+            writeln!(out, "    \"\"\"")?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Top level statements are separated with two blank lines (PEP 8)
+fn write_separator<W: Write>(out: &mut W) -> Result<()> {
+    writeln!(out)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Structs and unions in the order their `_fields_` has to be set. A struct that has another one
+/// by value (directly, through an alias or an array with a size) comes after it
+fn fields_order(api_defs: &[ApiDef]) -> Vec<&Struct> {
+    let structs = api_defs
+        .iter()
+        .flat_map(|api_def| api_def.structs.iter().chain(api_def.unions.iter()))
+        .collect::<Vec<_>>();
+    let by_name = structs
+        .iter()
+        .map(|s| (s.name.as_str(), *s))
+        .collect::<HashMap<_, _>>();
+    let aliases = api_defs
+        .iter()
+        .flat_map(|api_def| api_def.types.iter())
+        .map(|t| (t.var.name.as_str(), &t.var))
+        .collect::<HashMap<_, _>>();
+
+    fn visit<'a>(
+        s: &'a Struct,
+        api_defs: &'a [ApiDef],
+        by_name: &HashMap<&str, &'a Struct>,
+        aliases: &HashMap<&str, &'a Variable>,
+        visited: &mut BTreeSet<&'a str>,
+        order: &mut Vec<&'a Struct>,
+    ) {
+        if !visited.insert(&s.name) {
+            return;
+        }
+
+        let derived = get_derived_structs(api_defs, s);
+
+        for var in derived
+            .iter()
+            .flat_map(|d| d.variables.iter())
+            .chain(s.variables.iter())
+        {
+            if var.type_modifier != TypeModifier::None {
+                continue;
+            }
+
+            let mut var = var;
+
+            // Limit the depth in case an alias refers to itself
+            for _ in 0..16 {
+                match aliases.get(var.type_name.as_str()) {
+                    Some(target) if var.vtype == VariableType::Alias => var = target,
+                    _ => break,
+                }
+            }
+
+            if var.vtype == VariableType::Regular && var.type_modifier == TypeModifier::None {
+                if let Some(dep) = by_name.get(var.type_name.as_str()) {
+                    visit(dep, api_defs, by_name, aliases, visited, order);
+                }
+            }
+        }
+
+        order.push(s);
+    }
+
+    let mut visited = BTreeSet::new();
+    let mut order = Vec::with_capacity(structs.len());
+
+    for s in &structs {
+        visit(s, api_defs, &by_name, &aliases, &mut visited, &mut order);
+    }
+
+    order
+}
+
+/// Checks if the function has variable arguments (which can't be described with `argtypes`)
+fn is_variadic(func: &Function) -> bool {
+    func.function_args
+        .iter()
+        .any(|arg| arg.name == "va_args" || arg.type_name == "VA_ARGS")
+}
+
+impl PythonGenerator {
+    pub fn new(c_prefix: &str, module_name: &str) -> PythonGenerator {
+        PythonGenerator {
+            c_prefix: c_prefix.to_owned(),
+            module_name: module_name.to_owned(),
+        }
+    }
+
+    /// Name of the generated file
+    pub fn file_name(&self) -> String {
+        format!("{}.py", self.module_name)
+    }
+
+    /// Writes the module for all `api_defs`
+    pub fn generate<W: Write>(&self, out: &mut W, api_defs: &[ApiDef]) -> Result<()> {
+        // Enums (and their flags types) with values that doesn't fit in 32 bits are 64 bit in C
+        let wide_enums = api_defs
+            .iter()
+            .flat_map(|api_def| api_def.enums.iter())
+            .filter(|e| e.is_64bit())
+            .flat_map(|e| [e.name.to_owned(), e.flags_name.to_owned()])
+            .collect::<BTreeSet<_>>();
+
+        writeln!(out, "# This file is generated by apigen. Do not edit.")?;
+        writeln!(out)?;
+        writeln!(out, "import ctypes")?;
+        writeln!(out, "import enum")?;
+
+        for api_def in api_defs {
+            self.write_consts(out, api_def)?;
+        }
+
+        for api_def in api_defs {
+            self.write_enums(out, api_def)?;
+        }
+
+        // All classes are declared first so fields and callbacks can use them
+        for api_def in api_defs {
+            for s in &api_def.structs {
+                self.write_class(out, "Structure", s)?;
+            }
+
+            for u in &api_def.unions {
+                self.write_class(out, "Union", u)?;
+            }
+        }
+
+        for api_def in api_defs {
+            for t in &api_def.types {
+                write_separator(out)?;
+                write_comments(out, &t.var.doc_comments, CommentStyle::Hash, 0)?;
+                writeln!(
+                    out,
+                    "{} = {}",
+                    t.var.name,
+                    Self::ctype(&t.var, "", &wide_enums)
+                )?;
+            }
+        }
+
+        for api_def in api_defs {
+            for c in &api_def.callbacks {
+                write_separator(out)?;
+                write_comments(out, &c.doc_comments, CommentStyle::Hash, 0)?;
+                writeln!(
+                    out,
+                    "{} = ctypes.CFUNCTYPE({})",
+                    c.name,
+                    Self::cfunctype_args(c, &wide_enums).join(", ")
+                )?;
+            }
+        }
+
+        for s in fields_order(api_defs) {
+            self.write_fields(out, s, api_defs, &wide_enums)?;
+        }
+
+        self.write_load(out, api_defs, &wide_enums)
+    }
+
+    /// Numbers and strings are written as is
+    fn write_consts<W: Write>(&self, out: &mut W, api_def: &ApiDef) -> Result<()> {
+        let consts = api_def
+            .consts
+            .iter()
+            .filter(|c| {
+                !c.name.starts_with("_MANUAL")
+                    && (c.value.starts_with('"')
+                        || c.value.starts_with(|c: char| c.is_ascii_digit()))
+            })
+            .collect::<Vec<_>>();
+
+        if consts.is_empty() {
+            return Ok(());
+        }
+
+        write_separator(out)?;
+
+        for c in consts {
+            write_comments(out, &c.doc_comments, CommentStyle::Hash, 0)?;
+            writeln!(out, "{} = {}", py_name(&c.name), c.value)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_enums<W: Write>(&self, out: &mut W, api_def: &ApiDef) -> Result<()> {
+        for e in &api_def.enums {
+            let bitflags = e.enum_type == EnumType::Bitflags;
+
+            write_separator(out)?;
+            writeln!(
+                out,
+                "class {}(enum.{}):",
+                e.name,
+                if bitflags { "IntFlag" } else { "IntEnum" }
+            )?;
+
+            if write_docstring(out, &e.doc_comments)? && !e.entries.is_empty() {
+                writeln!(out)?;
+            }
+
+            for entry in &e.entries {
+                write_comments(out, &entry.doc_comments, CommentStyle::Hash, 4)?;
+
+                if bitflags {
+                    writeln!(out, "    {} = 0x{:x}", py_name(&entry.name), entry.value)?;
+                } else {
+                    writeln!(out, "    {} = {}", py_name(&entry.name), entry.value)?;
+                }
+            }
+
+            if e.entries.is_empty() {
+                writeln!(out, "    pass")?;
+            }
+
+            if !e.flags_name.is_empty() {
+                write_separator(out)?;
+                writeln!(out, "{} = {}", e.flags_name, e.name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_class<W: Write>(&self, out: &mut W, kind: &str, s: &Struct) -> Result<()> {
+        write_separator(out)?;
+        writeln!(out, "class {}(ctypes.{}):", s.name, kind)?;
+
+        if !write_docstring(out, &s.doc_comments)? {
+            writeln!(out, "    pass")?;
+        }
+
+        Ok(())
+    }
+
+    /// Variables from derived structs are added first. Handles are opaque and has no fields
+    fn write_fields<W: Write>(
+        &self,
+        out: &mut W,
+        s: &Struct,
+        api_defs: &[ApiDef],
+        wide_enums: &BTreeSet<String>,
+    ) -> Result<()> {
+        if s.has_attribute("Handle") {
+            return Ok(());
+        }
+
+        let derived = get_derived_structs(api_defs, s);
+        let vars = derived
+            .iter()
+            .flat_map(|d| d.variables.iter())
+            .chain(s.variables.iter())
+            .collect::<Vec<_>>();
+
+        if vars.is_empty() {
+            return Ok(());
+        }
+
+        write_separator(out)?;
+        writeln!(out, "{}._fields_ = [", s.name)?;
+
+        for var in vars {
+            let element = Self::ctype(
+                &Variable {
+                    array: None,
+                    ..var.clone()
+                },
+                &s.name,
+                wide_enums,
+            );
+            let name = py_name(&var.name);
+
+            match var.array {
+                Some(ArrayType::Unsized) => {
+                    writeln!(out, "    (\"{}\", ctypes.POINTER({})),", name, element)?;
+                    writeln!(out, "    (\"{}_size\", ctypes.c_uint64),", var.name)?;
+                }
+                Some(ArrayType::SizedArray(ref size)) => {
+                    writeln!(out, "    (\"{}\", {} * {}),", name, element, py_name(size))?;
+                }
+                None => writeln!(out, "    (\"{}\", {}),", name, element)?,
+            }
+        }
+
+        writeln!(out, "]")?;
+
+        Ok(())
+    }
+
+    /// Writes `load` which sets up all the functions
+    fn write_load<W: Write>(
+        &self,
+        out: &mut W,
+        api_defs: &[ApiDef],
+        wide_enums: &BTreeSet<String>,
+    ) -> Result<()> {
+        write_separator(out)?;
+        writeln!(out, "def load(path):")?;
+        writeln!(
+            out,
+            "    \"\"\"Loads the library at `path` and sets up the argument and return types\"\"\""
+        )?;
+        writeln!(out, "    lib = ctypes.CDLL(path)")?;
+
+        for api_def in api_defs {
+            for s in &api_def.structs {
+                for func in &s.functions {
+                    let c_name = func.get_c_name(&s.name, &self.c_prefix);
+
+                    writeln!(out)?;
+                    write_comments(out, &func.doc_comments, CommentStyle::Hash, 4)?;
+
+                    if !is_variadic(func) {
+                        let args = Self::arguments(func, &s.name, wide_enums);
+                        let line = format!("    lib.{}.argtypes = [{}]", c_name, args.join(", "));
+
+                        if line.len() <= MAX_LINE_LENGTH {
+                            writeln!(out, "{}", line)?;
+                        } else {
+                            writeln!(out, "    lib.{}.argtypes = [", c_name)?;
+
+                            for arg in args {
+                                writeln!(out, "        {},", arg)?;
+                            }
+
+                            writeln!(out, "    ]")?;
+                        }
+                    }
+
+                    writeln!(
+                        out,
+                        "    lib.{}.restype = {}",
+                        c_name,
+                        Self::return_type(func, wide_enums)
+                    )?;
+                }
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    return lib")?;
+
+        Ok(())
+    }
+
+    /// Arguments using the same rules as `Function::c_args`
+    fn arguments(func: &Function, self_name: &str, wide_enums: &BTreeSet<String>) -> Vec<String> {
+        let mut args = Vec::with_capacity(func.function_args.len());
+
+        for arg in &func.function_args {
+            if arg.vtype == VariableType::Str {
+                args.push("ctypes.c_char_p".to_owned());
+                continue;
+            }
+
+            let element = Variable {
+                array: None,
+                ..arg.clone()
+            };
+
+            match arg.array {
+                None => args.push(Self::ctype(arg, self_name, wide_enums)),
+                Some(ArrayType::Unsized) => {
+                    args.push(format!(
+                        "ctypes.POINTER({})",
+                        Self::ctype(&element, self_name, wide_enums)
+                    ));
+                    args.push("ctypes.c_uint64".to_owned());
+                }
+                // C arrays in arguments are pointers
+                Some(ArrayType::SizedArray(_)) => args.push(format!(
+                    "ctypes.POINTER({})",
+                    Self::ctype(&element, self_name, wide_enums)
+                )),
+            }
+        }
+
+        args
+    }
+
+    fn return_type(func: &Function, wide_enums: &BTreeSet<String>) -> String {
+        match func.return_val.as_ref() {
+            Some(ret) => Self::ctype(ret, "", wide_enums),
+            None => "None".to_owned(),
+        }
+    }
+
+    /// Return type followed by the arguments
+    fn cfunctype_args(func: &Function, wide_enums: &BTreeSet<String>) -> Vec<String> {
+        let mut args = vec![Self::return_type(func, wide_enums)];
+        args.extend(Self::arguments(func, "", wide_enums));
+        args
+    }
+
+    fn primitive(name: &str) -> &'static str {
+        match name {
+            "u8" => "ctypes.c_uint8",
+            "u16" => "ctypes.c_uint16",
+            "u32" => "ctypes.c_uint32",
+            "u64" => "ctypes.c_uint64",
+            "i8" => "ctypes.c_int8",
+            "i16" => "ctypes.c_int16",
+            "i32" => "ctypes.c_int32",
+            "i64" => "ctypes.c_int64",
+            "f32" => "ctypes.c_float",
+            "f64" => "ctypes.c_double",
+            "bool" => "ctypes.c_bool",
+            _ => "None",
+        }
+    }
+
+    /// ctypes type for `var` (not including arrays)
+    fn ctype(var: &Variable, self_name: &str, wide_enums: &BTreeSet<String>) -> String {
+        let is_void = var.vtype == VariableType::Primitive && var.type_name == "void";
+
+        let t = match var.vtype {
+            VariableType::Primitive => Self::primitive(&var.type_name).to_owned(),
+            VariableType::Str => "ctypes.c_char_p".to_owned(),
+            VariableType::Handle => format!("ctypes.POINTER({})", var.type_name),
+            VariableType::SelfType => format!("ctypes.POINTER({})", self_name),
+            VariableType::Enum if wide_enums.contains(&var.type_name) => {
+                "ctypes.c_uint64".to_owned()
+            }
+            VariableType::Enum => match var.enum_type {
+                // The flags typedef is an uint32_t in C
+                EnumType::Bitflags => "ctypes.c_uint32".to_owned(),
+                EnumType::Regular => "ctypes.c_int".to_owned(),
+            },
+            VariableType::Regular | VariableType::Alias | VariableType::Callback => {
+                var.type_name.to_owned()
+            }
+            VariableType::None => "None".to_owned(),
+        };
+
+        match var.type_modifier {
+            TypeModifier::None => t,
+            _ if is_void => "ctypes.c_void_p".to_owned(),
+            _ => format!("ctypes.POINTER({})", t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::test_util;
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+const MAX_NAME = 32
+
+/// Info about an image
+struct ImageInfo {
+    width: u32,
+    name: [u8; MAX_NAME],
+    format: Format,
+    data: [u8],
+}
+
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+[enum_flags(LoadFlags)]
+enum LoadFlag {
+    Cache = 1,
+    Mips = 2,
+    Srgb = 4,
+}
+
+callback LoadCallback(image: Image, user_data: *void)
+
+#[attributes(Handle)]
+struct Image {
+    [static] create_from_memory(name: String, data: [u8], flags: LoadFlags) -> Image?,
+    get_info() -> *const ImageInfo?,
+    destroy(),
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        PythonGenerator::new("FL_", "fl")
+            .generate(&mut out, &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_module() {
+        let p = generate();
+
+        assert!(p.contains("MAX_NAME = 32"));
+        assert!(p.contains("class Format(enum.IntEnum):\n    Rgb = 0\n    Rgba = 1\n"));
+        assert!(p.contains("class LoadFlag(enum.IntFlag):\n    Cache = 0x1\n"));
+        assert!(p.contains("LoadFlags = LoadFlag"));
+        assert!(
+            p.contains("class ImageInfo(ctypes.Structure):\n    \"\"\"Info about an image\"\"\"")
+        );
+        assert!(p.contains("class Image(ctypes.Structure):\n    pass"));
+        assert!(p.contains(
+            "LoadCallback = ctypes.CFUNCTYPE(None, ctypes.POINTER(Image), ctypes.c_void_p)"
+        ));
+        assert!(p.contains("    (\"name\", ctypes.c_uint8 * MAX_NAME),"));
+        assert!(p.contains("    (\"format\", ctypes.c_int),"));
+        assert!(p.contains(
+            "    (\"data\", ctypes.POINTER(ctypes.c_uint8)),\n    (\"data_size\", ctypes.c_uint64),"
+        ));
+        assert!(p.contains(
+            "    lib.FL_Image_create_from_memory.argtypes = [\n        ctypes.c_char_p,\n        ctypes.POINTER(ctypes.c_uint8),\n        ctypes.c_uint64,\n        ctypes.c_uint32,\n    ]"
+        ));
+        assert!(p.contains("    lib.FL_Image_create_from_memory.restype = ctypes.POINTER(Image)"));
+        assert!(p.contains("    lib.FL_Image_get_info.restype = ctypes.POINTER(ImageInfo)"));
+        assert!(p.contains("    lib.FL_Image_destroy.restype = None"));
+    }
+
+    const EDGE_CASES: &str = r#"
+const None = 4
+
+[enum_flags(BigFlags)]
+enum BigFlag {
+    Low = 1,
+    High = 0x100000000,
+}
+
+enum Mode {
+    None = 0,
+    True = 1,
+}
+
+struct Settings {
+    from: u32,
+    flags: BigFlags,
+    lambda: [u8; None],
+}
+
+#[attributes(Handle)]
+struct Device {
+    set_flags(flags: BigFlags) -> BigFlag,
+    get_mode() -> Mode,
+    destroy(),
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("edge.def", EDGE_CASES)]);
+
+        let mut out = Vec::new();
+        PythonGenerator::new("FL_", "fl")
+            .generate(&mut out, &defs)
+            .unwrap();
+        let p = String::from_utf8(out).unwrap();
+
+        assert!(p.contains("None_ = 4"));
+        assert!(p.contains("class Mode(enum.IntEnum):\n    None_ = 0\n    True_ = 1\n"));
+        assert!(p.contains("    (\"from_\", ctypes.c_uint32),"));
+        assert!(p.contains("    (\"flags\", ctypes.c_uint64),"));
+        assert!(p.contains("    (\"lambda_\", ctypes.c_uint8 * None_),"));
+        assert!(p.contains("    lib.FL_Device_set_flags.restype = ctypes.c_uint64"));
+
+        let check = "import ctypes, fl\nassert fl.Mode.None_ == 0\nassert fl.BigFlag.High == 1 << 32\nassert ctypes.sizeof(fl.Settings) == 24\n";
+        test_util::compiles(
+            "python",
+            &[("fl.py", p.as_bytes()), ("check.py", check.as_bytes())],
+            "python3",
+            &["check.py"],
+        );
+    }
+
+    #[test]
+    fn test_fields_order() {
+        let defs = test_util::parse(&[
+            (
+                "a.def",
+                "import \"b.def\"\nstruct A {\n    b: B,\n    c: [C; 2],\n}\nstruct C {\n    x: u32,\n}",
+            ),
+            ("b.def", "struct B {\n    x: u32,\n    c: Inner,\n}\ntype Inner: C"),
+        ]);
+
+        let mut out = Vec::new();
+        PythonGenerator::new("FL_", "fl")
+            .generate(&mut out, &defs)
+            .unwrap();
+        let p = String::from_utf8(out).unwrap();
+
+        let pos = |name: &str| p.find(&format!("{}._fields_", name)).unwrap();
+        assert!(pos("C") < pos("B"));
+        assert!(pos("B") < pos("A"));
+
+        let check = "import ctypes, fl\nassert ctypes.sizeof(fl.A) == 16\n";
+        test_util::compiles(
+            "python_fields_order",
+            &[("fl.py", p.as_bytes()), ("check.py", check.as_bytes())],
+            "python3",
+            &["check.py"],
+        );
+    }
+}
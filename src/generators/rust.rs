@@ -0,0 +1,838 @@
+//! Generates safe Rust wrappers on top of the bindings from the `rust_ffi` generator.
+//!
+//! Structs with the `Handle` attribute becomes owned types that implement `Drop` by calling the
+//! `[drop]` function (or `destroy` if the struct has the `Drop` attribute). Strings are passed as
+//! `&str`, unsized arrays as `&[T]`, optional (`?`) values as `Option` and non-static functions
+//! becomes methods. Methods take `&self`, except methods that return a mutable pointer which take
+//! `&mut self` so the returned reference can't alias the handle.
+//!
+//! Handles returned by `[static]` functions are owned by the caller and handles returned by
+//! methods are borrowed. `[owned]` and `[borrowed]` in front of a function overrides this (see
+//! `Function::returns_owned`). Borrowed handles are returned as a `<Name>Ref` that derefs to the
+//! handle type and isn't destroyed when dropped. Everything else (data structs, enums, etc) is
+//! re-exported from the FFI module as is.
+use crate::api_parser::{
+    ApiDef, ArrayType, Function, Result, Struct, TypeModifier, Variable, VariableType,
+};
+use crate::generators::rust_ffi::{dependency_path, module_file_name, namespace_path, rust_name};
+use crate::generators::util::{dependencies, wrap_list};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Lines longer than this are split up the same way as rustfmt does
+const MAX_WIDTH: usize = 100;
+/// rustfmt puts the arguments of calls on separate lines when they are longer than this
+const CALL_WIDTH: usize = 60;
+
+/// Writes safe Rust wrappers
+#[derive(Debug, Clone)]
+pub struct RustGenerator {
+    /// Has to match the prefix used for the C code
+    pub c_prefix: String,
+    /// Path to the module with the code from the `rust_ffi` generator
+    pub ffi_module: String,
+}
+
+impl Default for RustGenerator {
+    fn default() -> Self {
+        RustGenerator {
+            c_prefix: String::new(),
+            ffi_module: "crate::ffi".to_owned(),
+        }
+    }
+}
+
+/// Code for calling a function
+#[derive(Default)]
+struct Call {
+    /// Parameters of the wrapper function (excluding self)
+    params: Vec<String>,
+    /// Statements that prepares the arguments
+    setup: Vec<String>,
+    /// Arguments to the FFI function
+    args: Vec<String>,
+    /// If `to_cstring` or `from_cstr` is needed
+    uses_strings: bool,
+}
+
+/// Checks if `var` is a pointer to something that can be used as a Rust reference
+fn is_ref_pointer(var: &Variable) -> bool {
+    var.type_modifier != TypeModifier::None
+        && !matches!(
+            var.vtype,
+            VariableType::Handle | VariableType::Str | VariableType::SelfType | VariableType::None
+        )
+        && var.type_name != "void"
+}
+
+impl RustGenerator {
+    pub fn new(c_prefix: &str, ffi_module: &str) -> RustGenerator {
+        RustGenerator {
+            c_prefix: c_prefix.to_owned(),
+            ffi_module: ffi_module.to_owned(),
+        }
+    }
+
+    /// Name of the file generated for `api_def`
+    pub fn file_name(api_def: &ApiDef) -> String {
+        module_file_name(api_def)
+    }
+
+    /// Writes the wrappers for `api_def`. Handles from other def files are imported from their
+    /// modules (`super::<file>` for files in the same namespace). `api_defs` is all the def files.
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let mut uses_strings = false;
+        let mut body = String::new();
+
+        for s in api_def.structs.iter().filter(|s| s.has_attribute("Handle")) {
+            body.push_str(&self.handle_wrapper(s, &mut uses_strings));
+        }
+
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+        writeln!(out)?;
+        self.write_imports(out, api_def, api_defs, uses_strings)?;
+
+        if uses_strings {
+            Self::write_string_helpers(out)?;
+        }
+
+        write!(out, "{}", body)?;
+
+        Ok(())
+    }
+
+    fn write_imports<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+        uses_strings: bool,
+    ) -> Result<()> {
+        let imported = api_def.imported_files(api_defs);
+
+        // rustfmt sorts `super` imports first
+        for module in dependencies(api_def, api_defs, |var| var.vtype == VariableType::Handle) {
+            // Imported files may not have any handles that are used here
+            if imported.contains(&module) {
+                writeln!(out, "#[allow(unused_imports)]")?;
+            }
+
+            writeln!(
+                out,
+                "use {}::*;",
+                dependency_path(api_def, api_defs, &module)
+            )?;
+        }
+
+        if self.ffi_module.rsplit("::").next() == Some("ffi") {
+            writeln!(out, "use {};", self.ffi_module)?;
+        } else {
+            writeln!(out, "use {} as ffi;", self.ffi_module)?;
+        }
+
+        if uses_strings {
+            writeln!(out, "use std::ffi::{{c_char, CStr, CString}};")?;
+        }
+
+        // Everything except handles can be used directly
+        let mut exports = BTreeSet::new();
+
+        for c in api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+        {
+            if c.value.starts_with('"') || c.value.starts_with(|c: char| c.is_ascii_digit()) {
+                exports.insert(c.name.to_owned());
+            }
+        }
+
+        for e in &api_def.enums {
+            exports.insert(e.name.to_owned());
+
+            if !e.flags_name.is_empty() {
+                exports.insert(e.flags_name.to_owned());
+            }
+        }
+
+        for s in api_def
+            .structs
+            .iter()
+            .filter(|s| !s.has_attribute("Handle"))
+        {
+            exports.insert(s.name.to_owned());
+        }
+
+        exports.extend(api_def.unions.iter().map(|u| u.name.to_owned()));
+        exports.extend(api_def.types.iter().map(|t| t.var.name.to_owned()));
+        exports.extend(api_def.callbacks.iter().map(|c| c.name.to_owned()));
+
+        if !exports.is_empty() {
+            let path = namespace_path(&api_def.namespace);
+            writeln!(out)?;
+
+            for name in exports {
+                writeln!(out, "pub use ffi::{}{};", path, name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_string_helpers<W: Write>(out: &mut W) -> Result<()> {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "/// Strings are cut at the first nul byte (the same as C would do)"
+        )?;
+        writeln!(out, "#[allow(dead_code)]")?;
+        writeln!(out, "fn to_cstring(s: &str) -> CString {{")?;
+        writeln!(out, "    let bytes = s.as_bytes();")?;
+        writeln!(
+            out,
+            "    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());"
+        )?;
+        writeln!(out, "    CString::new(&bytes[..len]).unwrap_or_default()")?;
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "/// Copies a C string. Invalid UTF-8 is replaced")?;
+        writeln!(out, "#[allow(dead_code)]")?;
+        writeln!(
+            out,
+            "unsafe fn from_cstr(s: *const c_char) -> Option<String> {{"
+        )?;
+        writeln!(out, "    if s.is_null() {{")?;
+        writeln!(out, "        None")?;
+        writeln!(out, "    }} else {{")?;
+        writeln!(
+            out,
+            "        Some(unsafe {{ CStr::from_ptr(s) }}.to_string_lossy().into_owned())"
+        )?;
+        writeln!(out, "    }}")?;
+        writeln!(out, "}}")?;
+
+        Ok(())
+    }
+
+    fn handle_wrapper(&self, s: &Struct, uses_strings: &mut bool) -> String {
+        let name = &s.name;
+        let path = namespace_path(&s.namespace);
+        let mut out = String::new();
+
+        out.push('\n');
+
+        for c in &s.doc_comments {
+            out.push_str(&format!("/// {}\n", c));
+        }
+
+        out.push_str(&format!("pub struct {} {{\n", name));
+        out.push_str(&format!("    handle: *mut ffi::{}{},\n", path, name));
+        out.push_str("}\n\n");
+        out.push_str(&format!("impl {} {{\n", name));
+        out.push_str("    /// Takes ownership of a raw handle\n");
+        out.push_str("    ///\n");
+        out.push_str("    /// # Safety\n");
+        out.push_str("    ///\n");
+        out.push_str("    /// `handle` has to be valid and not owned by anything else\n");
+        out.push_str(&format!(
+            "    pub unsafe fn from_raw(handle: *mut ffi::{}{}) -> {} {{\n",
+            path, name, name
+        ));
+        out.push_str(&format!("        {} {{ handle }}\n", name));
+        out.push_str("    }\n\n");
+        out.push_str("    /// Returns the raw handle\n");
+        out.push_str(&format!(
+            "    pub fn as_raw(&self) -> *mut ffi::{}{} {{\n",
+            path, name
+        ));
+        out.push_str("        self.handle\n");
+        out.push_str("    }\n\n");
+        out.push_str("    /// Returns the raw handle without destroying the object\n");
+        out.push_str(&format!(
+            "    pub fn into_raw(self) -> *mut ffi::{}{} {{\n",
+            path, name
+        ));
+        out.push_str("        let handle = self.handle;\n");
+        out.push_str("        core::mem::forget(self);\n");
+        out.push_str("        handle\n");
+        out.push_str("    }\n");
+
+        let drop_func = s.get_drop_function();
+
+        for func in &s.functions {
+            if drop_func.is_some_and(|d| std::ptr::eq(d, func)) {
+                continue;
+            }
+
+            out.push('\n');
+            out.push_str(&self.method(s, func, uses_strings));
+        }
+
+        out.push_str("}\n");
+
+        if let Some(func) = drop_func {
+            let c_name = func.get_c_name(name, &self.c_prefix);
+            out.push_str(&format!("\nimpl Drop for {} {{\n", name));
+            out.push_str("    fn drop(&mut self) {\n");
+            out.push_str(&format!(
+                "        unsafe {{ ffi::{}{}(self.handle) }}\n",
+                path, c_name
+            ));
+            out.push_str("    }\n");
+            out.push_str("}\n");
+        }
+
+        out.push_str(&Self::ref_wrapper(s));
+        out
+    }
+
+    /// Borrowed handle. It only derefs to `&T` so it can't be swapped with an owned handle
+    fn ref_wrapper(s: &Struct) -> String {
+        let name = &s.name;
+        let path = namespace_path(&s.namespace);
+        let mut out = String::new();
+
+        out.push_str(&format!(
+            "\n/// Borrowed [`{}`] that isn't destroyed when it goes out of scope\n",
+            name
+        ));
+        out.push_str(&format!("pub struct {}Ref<'a> {{\n", name));
+        out.push_str(&format!("    inner: core::mem::ManuallyDrop<{}>,\n", name));
+        out.push_str(&format!(
+            "    _marker: core::marker::PhantomData<&'a {}>,\n",
+            name
+        ));
+        out.push_str("}\n\n");
+        out.push_str(&format!("impl<'a> {}Ref<'a> {{\n", name));
+        out.push_str("    /// Borrows a raw handle\n");
+        out.push_str("    ///\n");
+        out.push_str("    /// # Safety\n");
+        out.push_str("    ///\n");
+        out.push_str(
+            "    /// `handle` has to be valid for as long as the returned value is used\n",
+        );
+        out.push_str(&format!(
+            "    pub unsafe fn from_raw(handle: *mut ffi::{}{}) -> {}Ref<'a> {{\n",
+            path, name, name
+        ));
+        out.push_str(&format!("        {}Ref {{\n", name));
+        out.push_str(&format!(
+            "            inner: core::mem::ManuallyDrop::new({} {{ handle }}),\n",
+            name
+        ));
+        out.push_str("            _marker: core::marker::PhantomData,\n");
+        out.push_str("        }\n");
+        out.push_str("    }\n");
+        out.push_str("}\n\n");
+        out.push_str(&format!("impl core::ops::Deref for {}Ref<'_> {{\n", name));
+        out.push_str(&format!("    type Target = {};\n\n", name));
+        out.push_str(&format!("    fn deref(&self) -> &{} {{\n", name));
+        out.push_str("        &self.inner\n");
+        out.push_str("    }\n");
+        out.push_str("}\n");
+        out
+    }
+
+    fn method(&self, s: &Struct, func: &Function, uses_strings: &mut bool) -> String {
+        let mut call = Call::default();
+
+        for arg in &func.function_args {
+            Self::argument(&mut call, arg);
+        }
+
+        let has_self = func
+            .function_args
+            .iter()
+            .any(|arg| arg.vtype == VariableType::SelfType);
+        let (ret_type, ret_expr, mut_ret) = Self::return_value(func, has_self, &mut call);
+
+        let mut params = Vec::with_capacity(call.params.len() + 1);
+
+        if has_self {
+            params.push(if mut_ret {
+                "&mut self".to_owned()
+            } else {
+                "&self".to_owned()
+            });
+        }
+
+        params.extend(call.params.iter().cloned());
+        *uses_strings |= call.uses_strings;
+
+        let mut out = String::new();
+
+        for c in &func.doc_comments {
+            out.push_str(&format!("    /// {}\n", c));
+        }
+
+        // Raw pointers are passed to C as is so the caller has to make sure they are valid
+        let is_unsafe = call.params.iter().any(|p| p.contains(": *"));
+
+        if is_unsafe {
+            if !func.doc_comments.is_empty() {
+                out.push_str("    ///\n");
+            }
+
+            out.push_str("    /// # Safety\n");
+            out.push_str("    ///\n");
+            out.push_str(
+                "    /// Pointers are passed to the C function as is and have to be valid\n",
+            );
+        }
+
+        let start = format!(
+            "pub {}fn {}(",
+            if is_unsafe { "unsafe " } else { "" },
+            rust_name(&func.name)
+        );
+        let end = if ret_type.is_empty() {
+            ") {".to_owned()
+        } else {
+            format!(") -> {} {{", ret_type)
+        };
+
+        out.push_str(&wrap_list(4, &start, &params, &end, MAX_WIDTH, MAX_WIDTH));
+        out.push('\n');
+
+        for line in &call.setup {
+            out.push_str(&format!("        {}\n", line));
+        }
+
+        let start = format!(
+            "ffi::{}{}(",
+            namespace_path(&s.namespace),
+            func.get_c_name(&s.name, &self.c_prefix)
+        );
+        let ffi_call = wrap_list(0, &start, &call.args, ")", MAX_WIDTH, CALL_WIDTH);
+        let expr = ret_expr.replace("{call}", &ffi_call);
+
+        // rustfmt keeps blocks with a single expression on one line if it fits. Calls that are
+        // arguments (or the start of a chain) are also limited by the call width
+        let fits = !expr.contains('\n')
+            && expr.len() + "        unsafe {  }".len() <= MAX_WIDTH
+            && (ret_expr == "{call}" || ffi_call.len() <= CALL_WIDTH);
+
+        if fits {
+            out.push_str(&format!("        unsafe {{ {} }}\n", expr));
+        } else {
+            out.push_str("        unsafe {\n");
+
+            if ret_expr == "{call}" {
+                out.push_str(&wrap_list(
+                    12, &start, &call.args, ")", MAX_WIDTH, CALL_WIDTH,
+                ));
+                out.push('\n');
+            } else {
+                // The call is stored in `ret` first so only the call itself has to be wrapped
+                out.push_str(&Self::let_ret(&start, &call.args));
+
+                let rest = ret_expr
+                    .strip_prefix("let ret = {call};\n")
+                    .map_or_else(|| ret_expr.replace("{call}", "ret"), str::to_owned);
+
+                for line in rest.lines() {
+                    out.push_str(&format!("            {}\n", line));
+                }
+            }
+
+            out.push_str("        }\n");
+        }
+
+        out.push_str("    }\n");
+        out
+    }
+
+    /// `let ret = <call>;` inside an unsafe block formatted the same way as rustfmt: on one line
+    /// if it fits, then with the call on the next line and last with one argument per line
+    fn let_ret(start: &str, args: &[String]) -> String {
+        let list = args.join(", ");
+        let call = format!("{}{})", start, list);
+        let line = format!("            let ret = {};", call);
+
+        if list.len() <= CALL_WIDTH && line.len() <= MAX_WIDTH {
+            format!("{}\n", line)
+        } else if list.len() <= CALL_WIDTH && call.len() + "                ;".len() <= MAX_WIDTH {
+            format!("            let ret =\n                {};\n", call)
+        } else {
+            let start = format!("let ret = {}", start);
+            format!("{}\n", wrap_list(12, &start, args, ");", 0, 0))
+        }
+    }
+
+    /// Type without modifier and array
+    fn value_type(var: &Variable) -> String {
+        match var.vtype {
+            VariableType::Primitive => var.get_primitive_type().into_owned(),
+            VariableType::Handle => format!(
+                "*mut ffi::{}{}",
+                namespace_path(&var.type_namespace),
+                var.type_name
+            ),
+            VariableType::Str => "*const c_char".to_owned(),
+            VariableType::Regular
+            | VariableType::Enum
+            | VariableType::Alias
+            | VariableType::Callback => {
+                format!(
+                    "ffi::{}{}",
+                    namespace_path(&var.type_namespace),
+                    var.type_name
+                )
+            }
+            VariableType::SelfType | VariableType::None => "c_void".to_owned(),
+        }
+    }
+
+    /// Type as used in the FFI bindings
+    fn raw_type(var: &Variable) -> String {
+        let t = Self::value_type(var);
+
+        match var.type_modifier {
+            TypeModifier::None => t,
+            TypeModifier::ConstPointer | TypeModifier::Reference => format!("*const {}", t),
+            TypeModifier::MutPointer => format!("*mut {}", t),
+        }
+        .replace("c_void", "core::ffi::c_void")
+        .replace("c_char", "core::ffi::c_char")
+    }
+
+    fn argument(call: &mut Call, arg: &Variable) {
+        let name = rust_name(&arg.name);
+
+        match (&arg.array, &arg.vtype) {
+            (_, VariableType::SelfType) => call.args.push("self.handle".to_owned()),
+            (Some(ArrayType::Unsized), _) => {
+                let t = Self::raw_type(&Variable {
+                    array: None,
+                    type_modifier: TypeModifier::None,
+                    ..arg.clone()
+                });
+                call.params.push(format!("{}: &[{}]", name, t));
+                call.args.push(format!("{}.as_ptr()", name));
+                call.args.push(format!("{}.len() as u64", name));
+            }
+            (Some(ArrayType::SizedArray(size)), _) => {
+                let t = Self::raw_type(&Variable {
+                    array: None,
+                    type_modifier: TypeModifier::None,
+                    ..arg.clone()
+                });
+                let size = if size.starts_with(|c: char| c.is_ascii_digit()) {
+                    size.to_owned()
+                } else {
+                    format!("ffi::{}{}", namespace_path(&arg.namespace), size)
+                };
+                call.params
+                    .push(format!("{}: &mut [{}; {}]", name, t, size));
+                call.args.push(format!("{}.as_mut_ptr()", name));
+            }
+            (None, VariableType::Str) if arg.type_modifier == TypeModifier::None => {
+                call.uses_strings = true;
+
+                if arg.optional {
+                    call.params.push(format!("{}: Option<&str>", name));
+                    call.setup
+                        .push(format!("let {} = {}.map(to_cstring);", name, name));
+                    call.args.push(format!(
+                        "{}.as_ref().map_or(core::ptr::null(), |s| s.as_ptr())",
+                        name
+                    ));
+                } else {
+                    call.params.push(format!("{}: &str", name));
+                    call.setup
+                        .push(format!("let {} = to_cstring({});", name, name));
+                    call.args.push(format!("{}.as_ptr()", name));
+                }
+            }
+            (None, VariableType::Handle) if arg.type_modifier == TypeModifier::None => {
+                if arg.optional {
+                    call.params
+                        .push(format!("{}: Option<&{}>", name, arg.type_name));
+                    call.args.push(format!(
+                        "{}.map_or(core::ptr::null_mut(), |v| v.as_raw())",
+                        name
+                    ));
+                } else {
+                    call.params.push(format!("{}: &{}", name, arg.type_name));
+                    call.args.push(format!("{}.as_raw()", name));
+                }
+            }
+            _ if is_ref_pointer(arg) => {
+                let (reference, null, ptr) = if arg.type_modifier == TypeModifier::MutPointer {
+                    ("&mut ", "null_mut", "*mut")
+                } else {
+                    ("&", "null", "*const")
+                };
+                let t = format!("{}{}", reference, Self::value_type(arg));
+
+                if arg.optional {
+                    call.params.push(format!("{}: Option<{}>", name, t));
+                    call.args.push(format!(
+                        "{}.map_or(core::ptr::{}(), |v| v as {} _)",
+                        name, null, ptr
+                    ));
+                } else {
+                    call.params.push(format!("{}: {}", name, t));
+                    call.args.push(name);
+                }
+            }
+            _ => {
+                call.params
+                    .push(format!("{}: {}", name, Self::raw_type(arg)));
+                call.args.push(name);
+            }
+        }
+    }
+
+    /// Returns the return type, an expression where `{call}` is replaced with the FFI call and
+    /// if the function has to take `&mut self`
+    fn return_value(func: &Function, has_self: bool, call: &mut Call) -> (String, String, bool) {
+        let ret = match func.return_val.as_ref() {
+            Some(ret)
+                if !(ret.vtype == VariableType::Primitive
+                    && ret.type_name == "void"
+                    && ret.type_modifier == TypeModifier::None) =>
+            {
+                ret
+            }
+            _ => return (String::new(), "{call}".to_owned(), false),
+        };
+
+        let plain = ret.type_modifier == TypeModifier::None && ret.array.is_none();
+
+        match ret.vtype {
+            VariableType::Handle if plain => {
+                let name = &ret.type_name;
+                let lifetime = if has_self { "'_" } else { "'static" };
+
+                let (ret_type, wrapper) = if func.returns_owned() {
+                    (name.to_owned(), name.to_owned())
+                } else {
+                    (format!("{}Ref<{}>", name, lifetime), format!("{}Ref", name))
+                };
+
+                if ret.optional {
+                    let expr = format!(
+                        "let ret = {{call}};\n\
+                         if ret.is_null() {{\n    None\n}} else {{\n    Some({}::from_raw(ret))\n}}",
+                        wrapper
+                    );
+                    (format!("Option<{}>", ret_type), expr, false)
+                } else {
+                    (ret_type, format!("{}::from_raw({{call}})", wrapper), false)
+                }
+            }
+            VariableType::Str if plain => {
+                call.uses_strings = true;
+
+                if ret.optional {
+                    (
+                        "Option<String>".to_owned(),
+                        "from_cstr({call})".to_owned(),
+                        false,
+                    )
+                } else {
+                    (
+                        "String".to_owned(),
+                        "from_cstr({call}).unwrap_or_default()".to_owned(),
+                        false,
+                    )
+                }
+            }
+            _ if has_self && ret.array.is_none() && is_ref_pointer(ret) => {
+                let t = Self::value_type(ret);
+
+                match (ret.type_modifier == TypeModifier::MutPointer, ret.optional) {
+                    (true, true) => (
+                        format!("Option<&mut {}>", t),
+                        "{call}.as_mut()".into(),
+                        true,
+                    ),
+                    (true, false) => (format!("&mut {}", t), "&mut *{call}".into(), true),
+                    (false, true) => (format!("Option<&{}>", t), "{call}.as_ref()".into(), false),
+                    (false, false) => (format!("&{}", t), "&*{call}".into(), false),
+                }
+            }
+            _ => (Self::raw_type(ret), "{call}".to_owned(), false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::{test_util, GeneratorOptions, MemorySink, Registry};
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+/// Info about an image
+struct ImageInfo {
+    width: u32,
+}
+
+#[attributes(Handle, Drop)]
+struct Image {
+    [static] create_from_file(filename: String) -> Image?,
+    [static] create_from_memory(name: String, data: [u8]) -> Image?,
+    get_info() -> *const ImageInfo?,
+    get_name() -> String,
+    set_name(name: String),
+    [drop] release(),
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        RustGenerator::new("FL_", "crate::ffi")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_wrapper() {
+        let r = generate();
+
+        assert!(r.contains("pub use ffi::ImageInfo;"));
+        assert!(r.contains("pub struct Image {\n    handle: *mut ffi::Image,\n}"));
+        assert!(r.contains(
+            "    pub fn create_from_file(filename: &str) -> Option<Image> {\n        let filename = to_cstring(filename);"
+        ));
+        assert!(
+            r.contains("    pub fn create_from_memory(name: &str, data: &[u8]) -> Option<Image> {")
+        );
+        assert!(r.contains("data.as_ptr(), data.len() as u64"));
+        assert!(r.contains("    pub fn get_info(&self) -> Option<&ffi::ImageInfo> {"));
+        assert!(r.contains("unsafe { ffi::FL_Image_get_info(self.handle).as_ref() }"));
+        assert!(r.contains("    pub fn get_name(&self) -> String {"));
+        assert!(r.contains("    pub fn set_name(&self, name: &str) {"));
+        assert!(!r.contains("pub fn release("));
+        assert!(r.contains("impl Drop for Image {\n    fn drop(&mut self) {\n        unsafe { ffi::FL_Image_release(self.handle) }"));
+    }
+
+    const EDGE_CASES: &str = r#"
+struct Settings {
+    level: u32,
+}
+
+#[attributes(Handle)]
+struct Texture {
+    get_width() -> u32,
+    [drop] destroy(),
+}
+
+#[attributes(Handle, Drop)]
+struct GraphicsDevice {
+    [static] create_with_very_long_name(name: String, data: [u8], other_data: [u8]) -> GraphicsDevice?,
+    [static] create_from_memory_with_settings(data: [u8], settings: *const Settings) -> GraphicsDevice,
+    [static] create_with_settings_and_flags(settings: *const Settings, flags: u32, count: u32, mask: u32) -> GraphicsDevice,
+    [static] [borrowed] get_default() -> GraphicsDevice?,
+    [static] load() -> GraphicsDevice?,
+    [owned] create_texture(width: u32, height: u32) -> Texture,
+    get_render_target() -> Texture,
+    newest() -> Texture,
+    find_texture_by_name_and_size(name: String, width: u32, height: u32) -> Texture?,
+    get_texture_name_with_a_long_name(texture: Texture, index: u32, fallback: u32) -> String,
+    upload_texture_data(texture: Texture, data: [u8], offset_in_bytes: u64, level: u32) -> bool,
+    get_settings() -> *Settings,
+    destroy(),
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("graphics.def", EDGE_CASES)]);
+        let options = GeneratorOptions {
+            c_prefix: "FL_".to_owned(),
+            ..Default::default()
+        };
+
+        let registry = Registry::with_builtins();
+        let mut ffi = MemorySink::default();
+        let mut wrapper = MemorySink::default();
+        registry
+            .get("rust-ffi")
+            .unwrap()
+            .generate(&defs, &options, &mut ffi)
+            .unwrap();
+        registry
+            .get("rust")
+            .unwrap()
+            .generate(&defs, &options, &mut wrapper)
+            .unwrap();
+
+        let r = String::from_utf8(wrapper.files["graphics.rs"].clone()).unwrap();
+
+        // Static functions returns owned handles and methods borrowed ones unless overridden
+        assert!(r.contains("    pub fn load() -> Option<GraphicsDevice> {"));
+        assert!(
+            r.contains("    pub fn create_texture(&self, width: u32, height: u32) -> Texture {")
+        );
+        assert!(r.contains("    pub fn newest(&self) -> TextureRef<'_> {"));
+        assert!(r.contains("    pub fn get_render_target(&self) -> TextureRef<'_> {"));
+        assert!(r.contains("unsafe { TextureRef::from_raw(ffi::FL_GraphicsDevice_get_render_target(self.handle)) }"));
+        assert!(r.contains("    pub fn get_default() -> Option<GraphicsDeviceRef<'static>> {"));
+        assert!(
+            r.contains("pub struct TextureRef<'a> {\n    inner: core::mem::ManuallyDrop<Texture>,")
+        );
+        assert!(r.contains("impl core::ops::Deref for TextureRef<'_> {"));
+        assert!(!r.contains("DerefMut"));
+
+        // Long calls are wrapped the same way as rustfmt
+        assert!(r.contains("        unsafe {\n            let ret =\n                ffi::FL_GraphicsDevice_create_with_settings_and_flags(settings, flags, count, mask);\n"));
+        assert!(r.contains("            let ret = ffi::FL_GraphicsDevice_create_with_very_long_name(\n                name.as_ptr(),\n"));
+        assert!(r.contains("        unsafe {\n            ffi::FL_GraphicsDevice_upload_texture_data(\n                self.handle,\n"));
+
+        test_util::compiles(
+            "rust_fmt",
+            &[("graphics.rs", r.as_bytes())],
+            "rustfmt",
+            &["--edition=2021", "--check", "graphics.rs"],
+        );
+
+        let mut files = vec![(
+            "lib.rs".to_owned(),
+            b"#![allow(dead_code)]\npub mod api;\npub mod ffi;\n".to_vec(),
+        )];
+        files.extend(
+            ffi.files
+                .into_iter()
+                .map(|(n, d)| (format!("ffi/{}", n), d)),
+        );
+        files.extend(
+            wrapper
+                .files
+                .into_iter()
+                .map(|(n, d)| (format!("api/{}", n), d)),
+        );
+        let files = files
+            .iter()
+            .map(|(n, d)| (n.as_str(), d.as_slice()))
+            .collect::<Vec<_>>();
+
+        test_util::compiles(
+            "rust",
+            &files,
+            "rustc",
+            &[
+                "--edition=2021",
+                "--crate-type=lib",
+                "--emit=metadata",
+                "lib.rs",
+            ],
+        );
+    }
+}
@@ -0,0 +1,691 @@
+//! Generates `#[repr(C)]` Rust bindings (the kind of code that goes into a `-sys` crate) for each
+//! def file.
+//!
+//! The output only uses `core` so it works with `#![no_std]`. Functions are declared with the same
+//! names as the C generator uses (`<prefix><Struct>_<function>`). Bitflags enums are written as
+//! transparent structs with associated consts so any combination of flags can be represented.
+//!
+//! Files with a namespace are written to a directory for the namespace (`render/gl/<file>.rs`). The
+//! module file written by `generate_mod` declares them in nested modules for the namespace
+//! (`render::gl`) that re-export their content, the same way as the files in the global namespace
+//! are re-exported directly.
+use crate::api_parser::{
+    ApiDef, ArrayType, Enum, EnumType, Function, Result, Struct, TypeModifier, Variable,
+    VariableType,
+};
+use crate::generators::util::{dependencies, wrap_list, write_comments, CommentStyle};
+use crate::validate::RUST_KEYWORDS;
+use crate::{find_def_file, get_derived_structs};
+use heck::ToShoutySnakeCase;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Lines longer than this are split up the same way as rustfmt does
+const MAX_WIDTH: usize = 100;
+
+/// Writes Rust FFI bindings
+#[derive(Debug, Clone, Default)]
+pub struct RustFfiGenerator {
+    /// Added in front of the function names. Has to match the prefix used for the C code
+    pub c_prefix: String,
+}
+
+/// Writes Rust style doc comments
+/// Names that are keywords in Rust are written as raw identifiers. `self` (used for the first
+/// argument of methods) and the other keywords that can't be raw are written with a `_` suffix
+pub(crate) fn rust_name(name: &str) -> String {
+    match name {
+        "self" | "Self" | "super" | "crate" => format!("{}_", name),
+        _ if RUST_KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name.to_owned(),
+    }
+}
+
+/// Path of the module for `namespace` in the module file written by `generate_mod` (`render::gl::`
+/// for `render::gl` and empty for the global namespace)
+pub(crate) fn namespace_path(namespace: &[String]) -> String {
+    namespace.iter().map(|name| format!("{}::", name)).collect()
+}
+
+/// Name of the file generated for `api_def`, in a directory for the namespace (`render/gl/<file>.rs`)
+pub(crate) fn module_file_name(api_def: &ApiDef) -> String {
+    let dirs: String = api_def
+        .namespace
+        .iter()
+        .map(|name| format!("{}/", name))
+        .collect();
+    format!("{}{}.rs", dirs, api_def.base_filename)
+}
+
+/// Relative path from the module for `api_def` to the module for the def file `module` it
+/// depends on (`super::<file>` when both are in the same namespace). `module` is a qualified
+/// filename from `dependencies`
+pub(crate) fn dependency_path(api_def: &ApiDef, api_defs: &[ApiDef], module: &str) -> String {
+    let (namespace, module) = match find_def_file(api_defs, module) {
+        Some(dep) => (&dep.namespace[..], dep.base_filename.as_str()),
+        None => (&[][..], module),
+    };
+
+    let common = api_def
+        .namespace
+        .iter()
+        .zip(namespace)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    format!(
+        "{}{}{}",
+        "super::".repeat(api_def.namespace.len() - common + 1),
+        namespace_path(&namespace[common..]),
+        module
+    )
+}
+
+/// Type that holds the value of a bitflags enum
+pub(crate) fn bitflags_type(e: &Enum) -> &'static str {
+    if e.is_64bit() {
+        "u64"
+    } else {
+        "u32"
+    }
+}
+
+impl RustFfiGenerator {
+    pub fn new(c_prefix: &str) -> RustFfiGenerator {
+        RustFfiGenerator {
+            c_prefix: c_prefix.to_owned(),
+        }
+    }
+
+    /// Name of the file generated for `api_def`
+    pub fn file_name(api_def: &ApiDef) -> String {
+        module_file_name(api_def)
+    }
+
+    /// Writes a module file that declares and re-exports the modules for all `api_defs`
+    pub fn generate_mod<W: Write>(&self, out: &mut W, api_defs: &[ApiDef]) -> Result<()> {
+        writeln!(out, "// This file is generated by apigen. Do not edit.")?;
+        writeln!(out)?;
+
+        let global = api_defs
+            .iter()
+            .filter(|a| a.namespace.is_empty())
+            .collect::<Vec<_>>();
+
+        for api_def in &global {
+            writeln!(out, "pub mod {};", api_def.base_filename)?;
+        }
+
+        if !global.is_empty() {
+            writeln!(out)?;
+        }
+
+        for api_def in global {
+            writeln!(out, "pub use {}::*;", api_def.base_filename)?;
+        }
+
+        Self::write_namespaces(out, api_defs, &[])
+    }
+
+    /// Writes a module for each namespace directly inside `parent` that declares and re-exports
+    /// the files in the namespace
+    fn write_namespaces<W: Write>(
+        out: &mut W,
+        api_defs: &[ApiDef],
+        parent: &[String],
+    ) -> Result<()> {
+        let depth = parent.len();
+        let indent = " ".repeat(depth * 4);
+        let has_files = depth == 0 || api_defs.iter().any(|a| a.namespace == parent);
+
+        let names = api_defs
+            .iter()
+            .filter(|a| a.namespace.len() > depth && a.namespace.starts_with(parent))
+            .map(|a| a.namespace[depth].as_str())
+            .collect::<BTreeSet<_>>();
+
+        for (i, name) in names.into_iter().enumerate() {
+            let path = [parent, &[name.to_owned()]].concat();
+
+            if i > 0 || has_files {
+                writeln!(out)?;
+            }
+
+            writeln!(out, "{}pub mod {} {{", indent, name)?;
+
+            let files = api_defs
+                .iter()
+                .filter(|a| a.namespace == path)
+                .collect::<Vec<_>>();
+
+            for api_def in &files {
+                writeln!(out, "{}    pub mod {};", indent, api_def.base_filename)?;
+            }
+
+            for api_def in &files {
+                writeln!(
+                    out,
+                    "{}    pub use self::{}::*;",
+                    indent, api_def.base_filename
+                )?;
+            }
+
+            Self::write_namespaces(out, api_defs, &path)?;
+            writeln!(out, "{}}}", indent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the bindings for `api_def`. `api_defs` is all the def files and is used to look up
+    /// structs used with derive. Types from other def files are imported from their modules
+    /// (`super::<file>` for files in the same namespace).
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+        writeln!(
+            out,
+            "#![allow(non_camel_case_types, non_snake_case, dead_code)]"
+        )?;
+        writeln!(out)?;
+
+        // rustfmt sorts `super` imports first
+        for module in dependencies(api_def, api_defs, |_| true) {
+            writeln!(out, "#[allow(unused_imports)]")?;
+            writeln!(
+                out,
+                "use {}::*;",
+                dependency_path(api_def, api_defs, &module)
+            )?;
+        }
+
+        writeln!(out, "#[allow(unused_imports)]")?;
+        writeln!(out, "use core::ffi::{{c_char, c_void}};")?;
+
+        self.write_consts(out, api_def)?;
+
+        for t in &api_def.types {
+            writeln!(out)?;
+            write_comments(out, &t.var.doc_comments, CommentStyle::Rust, 0)?;
+            writeln!(out, "pub type {} = {};", t.var.name, t.var.ffi_type(""))?;
+        }
+
+        for e in &api_def.enums {
+            match e.enum_type {
+                EnumType::Regular => self.write_enum(out, e)?,
+                EnumType::Bitflags => self.write_bitflags(out, e)?,
+            }
+        }
+
+        for c in &api_def.callbacks {
+            self.write_callback(out, c)?;
+        }
+
+        for s in &api_def.structs {
+            if s.has_attribute("Handle") || (s.variables.is_empty() && s.derives.is_empty()) {
+                writeln!(out)?;
+                write_comments(out, &s.doc_comments, CommentStyle::Rust, 0)?;
+                writeln!(out, "#[repr(C)]")?;
+                writeln!(out, "pub struct {} {{", s.name)?;
+                writeln!(out, "    _data: [u8; 0],")?;
+                writeln!(
+                    out,
+                    "    _marker: core::marker::PhantomData<(*mut u8, core::marker::PhantomPinned)>,"
+                )?;
+                writeln!(out, "}}")?;
+            } else {
+                self.write_struct(out, "struct", s, api_defs)?;
+            }
+        }
+
+        for u in &api_def.unions {
+            self.write_struct(out, "union", u, api_defs)?;
+        }
+
+        let structs = api_def
+            .structs
+            .iter()
+            .filter(|s| !s.functions.is_empty())
+            .collect::<Vec<_>>();
+
+        if !structs.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "extern \"C\" {{")?;
+
+            for (i, s) in structs.iter().enumerate() {
+                for (j, func) in s.functions.iter().enumerate() {
+                    if i > 0 || j > 0 {
+                        writeln!(out)?;
+                    }
+
+                    self.write_function(out, s, func)?;
+                }
+            }
+
+            writeln!(out, "}}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Other def files that has types used by this file
+    /// Numbers are written as `usize` (so they can be used as array sizes) and strings as `&str`
+    fn write_consts<W: Write>(&self, out: &mut W, api_def: &ApiDef) -> Result<()> {
+        let consts = api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+            .collect::<Vec<_>>();
+
+        if consts.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(out)?;
+
+        for c in consts {
+            write_comments(out, &c.doc_comments, CommentStyle::Rust, 0)?;
+
+            if c.value.starts_with('"') {
+                writeln!(out, "pub const {}: &str = {};", c.name, c.value)?;
+            } else if c.value.starts_with(|c: char| c.is_ascii_digit()) {
+                writeln!(out, "pub const {}: usize = {};", c.name, c.value)?;
+            } else {
+                writeln!(
+                    out,
+                    "// const {} = {} isn't a number or string",
+                    c.name, c.value
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_enum<W: Write>(&self, out: &mut W, e: &Enum) -> Result<()> {
+        writeln!(out)?;
+        write_comments(out, &e.doc_comments, CommentStyle::Rust, 0)?;
+        // Same size as the C enum that gets a 64 bit type when the values doesn't fit in an int
+        writeln!(out, "#[repr({})]", if e.is_64bit() { "u64" } else { "C" })?;
+        writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]")?;
+        writeln!(out, "pub enum {} {{", e.name)?;
+
+        for entry in &e.entries {
+            write_comments(out, &entry.doc_comments, CommentStyle::Rust, 4)?;
+            writeln!(out, "    {} = {},", entry.name, entry.value)?;
+        }
+
+        writeln!(out, "}}")?;
+
+        Ok(())
+    }
+
+    fn write_bitflags<W: Write>(&self, out: &mut W, e: &Enum) -> Result<()> {
+        writeln!(out)?;
+        write_comments(out, &e.doc_comments, CommentStyle::Rust, 0)?;
+        writeln!(out, "#[repr(transparent)]")?;
+        writeln!(
+            out,
+            "#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]"
+        )?;
+        writeln!(out, "pub struct {}(pub {});", e.name, bitflags_type(e))?;
+        writeln!(out)?;
+        writeln!(out, "impl {} {{", e.name)?;
+
+        for entry in &e.entries {
+            write_comments(out, &entry.doc_comments, CommentStyle::Rust, 4)?;
+            writeln!(
+                out,
+                "    pub const {}: {} = {}(0x{:x});",
+                entry.name.to_shouty_snake_case(),
+                e.name,
+                e.name,
+                entry.value
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    /// Checks if all the flags in `other` are set")?;
+        writeln!(
+            out,
+            "    pub const fn contains(self, other: {}) -> bool {{",
+            e.name
+        )?;
+        writeln!(out, "        (self.0 & other.0) == other.0")?;
+        writeln!(out, "    }}")?;
+        writeln!(out, "}}")?;
+
+        for (op, func, token) in [("BitOr", "bitor", "|"), ("BitAnd", "bitand", "&")] {
+            writeln!(out)?;
+            writeln!(out, "impl core::ops::{} for {} {{", op, e.name)?;
+            writeln!(out, "    type Output = {};", e.name)?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "    fn {}(self, rhs: {}) -> {} {{",
+                func, e.name, e.name
+            )?;
+            writeln!(out, "        {}(self.0 {} rhs.0)", e.name, token)?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+            writeln!(out, "impl core::ops::{}Assign for {} {{", op, e.name)?;
+            writeln!(out, "    fn {}_assign(&mut self, rhs: {}) {{", func, e.name)?;
+            writeln!(out, "        self.0 {}= rhs.0;", token)?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}")?;
+        }
+
+        if !e.flags_name.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "pub type {} = {};", e.flags_name, e.name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Callbacks are nullable function pointers
+    fn write_callback<W: Write>(&self, out: &mut W, func: &Function) -> Result<()> {
+        let args = Self::arguments(func, "");
+        let ret = Self::return_type(func);
+        let func_type = format!(
+            "Option<unsafe extern \"C\" fn({}){}>;",
+            args.join(", "),
+            ret
+        );
+        let line = format!("pub type {} = {}", func.name, func_type);
+
+        writeln!(out)?;
+        write_comments(out, &func.doc_comments, CommentStyle::Rust, 0)?;
+
+        // Same order as rustfmt tries: one line, the type on the next line and then one line per
+        // argument
+        if line.len() <= MAX_WIDTH {
+            writeln!(out, "{}", line)?;
+        } else if func_type.len() + 4 <= MAX_WIDTH {
+            writeln!(out, "pub type {} =", func.name)?;
+            writeln!(out, "    {}", func_type)?;
+        } else {
+            writeln!(out, "pub type {} = Option<", func.name)?;
+            writeln!(
+                out,
+                "{},",
+                wrap_list(
+                    4,
+                    "unsafe extern \"C\" fn(",
+                    &args,
+                    &format!("){}", ret),
+                    MAX_WIDTH,
+                    MAX_WIDTH,
+                )
+            )?;
+            writeln!(out, ">;")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a struct or union. Variables from derived structs are added first
+    fn write_struct<W: Write>(
+        &self,
+        out: &mut W,
+        kind: &str,
+        s: &Struct,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        writeln!(out)?;
+        write_comments(out, &s.doc_comments, CommentStyle::Rust, 0)?;
+        writeln!(out, "#[repr(C)]")?;
+        writeln!(out, "#[derive(Clone, Copy)]")?;
+        writeln!(out, "pub {} {} {{", kind, s.name)?;
+
+        for derived in get_derived_structs(api_defs, s) {
+            writeln!(out, "    // From {}", derived.name)?;
+
+            for var in &derived.variables {
+                Self::write_field(out, var)?;
+            }
+        }
+
+        for var in &s.variables {
+            write_comments(out, &var.doc_comments, CommentStyle::Rust, 4)?;
+            Self::write_field(out, var)?;
+        }
+
+        writeln!(out, "}}")?;
+
+        Ok(())
+    }
+
+    /// Unsized arrays are written as a pointer and a size
+    fn write_field<W: Write>(out: &mut W, var: &Variable) -> Result<()> {
+        match var.array {
+            Some(ArrayType::Unsized) => {
+                writeln!(
+                    out,
+                    "    pub {}: *const {},",
+                    rust_name(&var.name),
+                    Self::element_type(var)
+                )?;
+                writeln!(out, "    pub {}_size: u64,", var.name)?;
+            }
+            _ => writeln!(
+                out,
+                "    pub {}: {},",
+                rust_name(&var.name),
+                var.ffi_type("")
+            )?,
+        }
+
+        Ok(())
+    }
+
+    fn write_function<W: Write>(&self, out: &mut W, s: &Struct, func: &Function) -> Result<()> {
+        let args = Self::arguments(func, &s.name);
+        let start = format!("pub fn {}(", func.get_c_name(&s.name, &self.c_prefix));
+        let end = format!("){};", Self::return_type(func));
+
+        write_comments(out, &func.doc_comments, CommentStyle::Rust, 4)?;
+        writeln!(
+            out,
+            "{}",
+            wrap_list(4, &start, &args, &end, MAX_WIDTH, MAX_WIDTH)
+        )?;
+
+        Ok(())
+    }
+
+    /// Type of the elements in an array
+    fn element_type(var: &Variable) -> String {
+        Variable {
+            array: None,
+            ..var.clone()
+        }
+        .ffi_type("")
+    }
+
+    /// Arguments using the same rules as the C generator
+    fn arguments(func: &Function, self_name: &str) -> Vec<String> {
+        let mut args = Vec::with_capacity(func.function_args.len());
+
+        for arg in &func.function_args {
+            let name = rust_name(&arg.name);
+
+            match arg.array {
+                // C arrays in arguments are pointers
+                Some(ArrayType::SizedArray(_)) => {
+                    args.push(format!("{}: *mut {}", name, Self::element_type(arg)))
+                }
+                Some(ArrayType::Unsized) => {
+                    args.push(format!("{}: *const {}", name, Self::element_type(arg)));
+                    args.push(format!("{}_size: u64", arg.name));
+                }
+                None => args.push(format!("{}: {}", name, arg.ffi_type(self_name))),
+            }
+        }
+
+        args
+    }
+
+    /// Return type including the `->` or empty if nothing is returned
+    fn return_type(func: &Function) -> String {
+        match func.return_val.as_ref() {
+            Some(ret)
+                if !(ret.vtype == VariableType::Primitive
+                    && ret.type_name == "void"
+                    && ret.type_modifier == TypeModifier::None) =>
+            {
+                format!(" -> {}", ret.ffi_type(""))
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::test_util;
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+const MAX_NAME = 32
+
+/// Info about an image
+struct ImageInfo {
+    /// width of the image
+    width: u32,
+    name: [u8; MAX_NAME],
+    format: Format,
+    data: [u8],
+}
+
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+[enum_flags(LoadFlags)]
+enum LoadFlag {
+    Cache = 1,
+    Mips = 2,
+    Srgb = 4,
+}
+
+type ImageId: u64
+
+callback LoadCallback(image: Image, user_data: *void)
+
+#[attributes(Handle, Drop)]
+struct Image {
+    [static] create_from_file(filename: String, flags: LoadFlags) -> Image?,
+    [static] create_from_memory(name: String, data: [u8], flags: LoadFlags, callback: LoadCallback) -> Image?,
+    get_info() -> *const ImageInfo?,
+    destroy(),
+}
+
+union Color {
+    rgba: u32,
+    data: [u8; 4],
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        RustFfiGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bindings() {
+        let r = generate();
+
+        assert!(r.contains("pub const MAX_NAME: usize = 32;"));
+        assert!(r.contains("pub type ImageId = u64;"));
+        assert!(r.contains("#[repr(C)]\n#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]\npub enum Format {\n    Rgb = 0,\n    Rgba = 1,\n}"));
+        assert!(r.contains("pub struct LoadFlag(pub u32);"));
+        assert!(r.contains("    pub const SRGB: LoadFlag = LoadFlag(0x4);"));
+        assert!(r.contains("pub type LoadFlags = LoadFlag;"));
+        assert!(r.contains(
+            "pub type LoadCallback = Option<unsafe extern \"C\" fn(image: *mut Image, user_data: *mut c_void)>;"
+        ));
+        assert!(r.contains("    /// width of the image\n    pub width: u32,"));
+        assert!(r.contains("    pub name: [u8; MAX_NAME],"));
+        assert!(r.contains("    pub data: *const u8,\n    pub data_size: u64,"));
+        assert!(r.contains("pub struct Image {\n    _data: [u8; 0],"));
+        assert!(r.contains("pub union Color {\n    pub rgba: u32,\n    pub data: [u8; 4],\n}"));
+        assert!(r.contains("    pub fn FL_Image_get_info(self_: *mut Image) -> *const ImageInfo;"));
+        assert!(r.contains("    pub fn FL_Image_create_from_memory(\n        name: *const c_char,\n        data: *const u8,\n        data_size: u64,"));
+        assert!(r.contains("    pub fn FL_Image_destroy(self_: *mut Image);"));
+    }
+
+    const EDGE_CASES: &str = r#"
+[enum_flags(BigFlags)]
+enum BigFlag {
+    Low = 1,
+    High = 0x100000000,
+}
+
+enum Big {
+    Small = 3,
+    Large = 0x100000005,
+}
+
+struct Buffer {
+    fixed: [u8; 16],
+    data: [u8],
+    type: u32,
+}
+
+#[attributes(Handle)]
+struct Device {
+    get_flags(flags: BigFlags) -> BigFlag,
+    upload(data: [u8], fixed: [f32; 4], match: Big),
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("edge.def", EDGE_CASES)]);
+
+        let mut out = Vec::new();
+        RustFfiGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        let r = String::from_utf8(out).unwrap();
+
+        assert!(r.contains("pub struct BigFlag(pub u64);"));
+        assert!(r.contains("    pub const HIGH: BigFlag = BigFlag(0x100000000);"));
+        assert!(r.contains(
+            "#[repr(u64)]\n#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]\npub enum Big {"
+        ));
+        assert!(r.contains("    pub fixed: [u8; 16],\n    pub data: *const u8,\n    pub data_size: u64,\n    pub r#type: u32,"));
+        assert!(r.contains("r#match: Big"));
+
+        test_util::compiles(
+            "rust_ffi",
+            &[("edge.rs", r.as_bytes())],
+            "rustc",
+            &[
+                "--edition=2021",
+                "--crate-type=lib",
+                "--emit=metadata",
+                "edge.rs",
+            ],
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Generates code from user supplied [minijinja](https://docs.rs/minijinja) templates.
+//!
+//! A template is rendered once per def file with these variables:
+//!
+//! * `api_def` - the resolved `ApiDef` for the file
+//! * `api_defs` - all the def files
+//! * `file` - base name of the def file with the namespace in front (`ApiDef::qualified_filename`)
+//! * `c_prefix` - the C prefix
+//!
+//! It also has filters for the helpers used by the built-in generators: `c_type`, `ffi_type`,
+//! `c_name`, `c_args`, `snake_case`, `camel_case`, `pascal_case`, `shouty_snake_case`, `rust_doc`
+//! and `c_doc`. Blocks are trimmed (`trim_blocks` and `lstrip_blocks`) so tags can be put on their
+//! own lines without adding empty lines to the output.
+use crate::api_parser::{ApiDef, ApigenError, Function, Result, Variable};
+use crate::{get_c_comments, get_rust_comments};
+use heck::{ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use minijinja::value::{Value, ViaDeserialize};
+use minijinja::{context, path_loader, Environment};
+use std::io::Write;
+use std::path::Path;
+
+/// Renders templates for def files
+#[derive(Debug)]
+pub struct TemplateGenerator {
+    env: Environment<'static>,
+    c_prefix: String,
+}
+
+fn template_error(e: minijinja::Error) -> ApigenError {
+    ApigenError::Template(format!("{:#}", e))
+}
+
+impl TemplateGenerator {
+    pub fn new(c_prefix: &str) -> TemplateGenerator {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_keep_trailing_newline(true);
+
+        let prefix = c_prefix.to_owned();
+        env.add_filter(
+            "c_type",
+            move |var: ViaDeserialize<Variable>, self_name: Option<String>| {
+                var.c_type(self_name.as_deref().unwrap_or(""), &prefix)
+            },
+        );
+
+        env.add_filter(
+            "ffi_type",
+            |var: ViaDeserialize<Variable>, self_name: Option<String>| {
+                var.ffi_type(self_name.as_deref().unwrap_or(""))
+            },
+        );
+
+        let prefix = c_prefix.to_owned();
+        env.add_filter(
+            "c_name",
+            move |func: ViaDeserialize<Function>, struct_name: String| {
+                func.get_c_name(&struct_name, &prefix)
+            },
+        );
+
+        let prefix = c_prefix.to_owned();
+        env.add_filter(
+            "c_args",
+            move |func: ViaDeserialize<Function>, struct_name: String| {
+                func.c_args(&struct_name, &prefix).join(", ")
+            },
+        );
+
+        env.add_filter("snake_case", |s: String| s.to_snake_case());
+        env.add_filter("camel_case", |s: String| s.to_lower_camel_case());
+        env.add_filter("pascal_case", |s: String| s.to_upper_camel_case());
+        env.add_filter("shouty_snake_case", |s: String| s.to_shouty_snake_case());
+        env.add_filter(
+            "rust_doc",
+            |comments: Vec<String>, indent: Option<usize>| {
+                get_rust_comments(&comments, indent.unwrap_or(0))
+            },
+        );
+        env.add_filter("c_doc", |comments: Vec<String>, indent: Option<usize>| {
+            get_c_comments(&comments, indent.unwrap_or(0))
+        });
+
+        TemplateGenerator {
+            env,
+            c_prefix: c_prefix.to_owned(),
+        }
+    }
+
+    /// Adds a template that can be rendered or included by other templates
+    pub fn add_template(&mut self, name: &str, source: &str) -> Result<()> {
+        self.env
+            .add_template_owned(name.to_owned(), source.to_owned())
+            .map_err(template_error)
+    }
+
+    /// Templates that hasn't been added are loaded from `dir` when they are used
+    pub fn set_template_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.env.set_loader(path_loader(dir.as_ref()));
+    }
+
+    /// Name of the file generated for `api_def` with the template `template`. `.j2` and `.jinja`
+    /// are removed from the template name and the extension that is left is used (so both
+    /// `go.j2` and `bindings.go.j2` gives `<file>.go`)
+    pub fn file_name(template: &str, api_def: &ApiDef) -> String {
+        let name = Path::new(template)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let name = name
+            .strip_suffix(".j2")
+            .or_else(|| name.strip_suffix(".jinja"))
+            .unwrap_or(&name);
+
+        let extension = match name.rsplit_once('.') {
+            Some((_, extension)) => extension,
+            None => name,
+        };
+
+        format!("{}.{}", api_def.qualified_filename(), extension)
+    }
+
+    /// Renders the template `name` for `api_def`. `api_defs` is all the def files
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        name: &str,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let template = self.env.get_template(name).map_err(template_error)?;
+
+        let ctx = context! {
+            api_def => Value::from_serialize(api_def),
+            api_defs => Value::from_serialize(api_defs),
+            file => api_def.qualified_filename(),
+            c_prefix => self.c_prefix,
+        };
+
+        let output = template.render(ctx).map_err(template_error)?;
+        out.write_all(output.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+struct ImageInfo {
+    /// Width in pixels
+    width: u32,
+    name: String,
+}
+
+#[attributes(Handle)]
+struct Image {
+    get_info() -> *const ImageInfo,
+    set_name(name: String),
+}
+"#;
+
+    const TEMPLATE: &str = r#"// {{ file }}
+{% for s in api_def.structs %}
+{% for var in s.variables %}
+{{ var.doc_comments | rust_doc }}
+{{ var.name | camel_case }}: {{ var | c_type }}
+{% endfor %}
+{% for func in s.functions %}
+{{ func.return_val | c_type if func.return_val else "void" }} {{ func | c_name(s.name) }}({{ func | c_args(s.name) }}) {{ s.name | snake_case }}
+{% endfor %}
+{% endfor %}
+"#;
+
+    #[test]
+    fn test_render() {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut generator = TemplateGenerator::new("FL_");
+        generator.add_template("c.j2", TEMPLATE).unwrap();
+
+        let mut out = Vec::new();
+        generator
+            .generate(&mut out, "c.j2", &defs[0], &defs)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "// image\n\
+             /// Width in pixels\n\
+             width: uint32_t\n\
+             \n\
+             name: const char*\n\
+             const FL_ImageInfo* FL_Image_get_info(struct FL_Image* self) image\n\
+             void FL_Image_set_name(struct FL_Image* self, const char* name) image\n"
+        );
+
+        assert_eq!(
+            TemplateGenerator::file_name("templates/bindings.go.j2", &defs[0]),
+            "image.go"
+        );
+        assert_eq!(
+            TemplateGenerator::file_name("zig.jinja", &defs[0]),
+            "image.zig"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Helpers for the generator tests
+use crate::api_parser::{ApiDef, ApiParser};
+use crate::resolver::resolve;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Parses and resolves `(filename, code)` pairs
+pub fn parse(files: &[(&str, &str)]) -> Vec<ApiDef> {
+    let mut defs = files
+        .iter()
+        .map(|(filename, code)| ApiParser::parse_string(code, filename).unwrap())
+        .collect::<Vec<_>>();
+    resolve(&mut defs);
+    defs
+}
+
+/// Writes `files` to an empty directory named `name` and runs `command` with `args` in it. Panics
+/// with the output (and keeps the directory) if the command fails. Returns false without doing anything if the command
+/// isn't installed so tests can skip checks for toolchains that aren't available.
+pub fn compiles(name: &str, files: &[(&str, &[u8])], command: &str, args: &[&str]) -> bool {
+    if let Err(e) = Command::new(command).arg("--version").output() {
+        if e.kind() == ErrorKind::NotFound {
+            eprintln!("{} not found, skipping compile check for {}", command, name);
+            return false;
+        }
+    }
+
+    let dir = temp_dir(name);
+
+    for (filename, data) in files {
+        let path = dir.join(filename);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    let output = Command::new(command)
+        .args(args)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "`{} {}` failed in {}:\n{}{}",
+        command,
+        args.join(" "),
+        dir.display(),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Only kept around when the command fails so the output can be looked at
+    let _ = std::fs::remove_dir_all(&dir);
+    true
+}
+
+/// Empty directory for a test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("apigen-tests-{}", std::process::id()))
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,176 @@
+//! Helpers for indentation, comments and line wrapping shared by the generators
+use crate::api_parser::{ApiDef, ArrayType, Result, Variable};
+use crate::{find_const_def, get_derived_structs};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// How comments are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentStyle {
+    /// `// comment` (C, C++, C#, Zig)
+    C,
+    /// `/// comment` (Rust doc comments)
+    Rust,
+    /// `# comment` (Python)
+    Hash,
+}
+
+impl CommentStyle {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            CommentStyle::C => "//",
+            CommentStyle::Rust => "///",
+            CommentStyle::Hash => "#",
+        }
+    }
+}
+
+/// Doc comments without any text are parsed as a single empty line
+fn is_empty(comments: &[String]) -> bool {
+    comments.len() == 1 && comments[0].is_empty()
+}
+
+/// Writes `comments` with `indent` spaces in front of each line
+pub fn write_comments<W: Write>(
+    out: &mut W,
+    comments: &[String],
+    style: CommentStyle,
+    indent: usize,
+) -> Result<()> {
+    if is_empty(comments) {
+        return Ok(());
+    }
+
+    for c in comments {
+        writeln!(
+            out,
+            "{:indent$}{} {}",
+            "",
+            style.prefix(),
+            c.trim_end(),
+            indent = indent
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Same as [`write_comments`] but returns the lines without a trailing newline
+pub fn format_comments(comments: &[String], style: CommentStyle, indent: usize) -> String {
+    if is_empty(comments) {
+        return String::new();
+    }
+
+    comments
+        .iter()
+        .map(|c| {
+            format!(
+                "{:indent$}{} {}",
+                "",
+                style.prefix(),
+                c.trim_end(),
+                indent = indent
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Adds `indent` spaces in front of each line in `text` that isn't empty
+pub fn indent(text: &str, indent: usize) -> String {
+    text.split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{:indent$}{}", "", line, indent = indent)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes `start`, `args` and `end` on one line if it fits in `max_width` and the arguments fits
+/// in `max_list_width`. Otherwise each argument is put on its own line (the same way as rustfmt
+/// and clang-format with one argument per line)
+pub fn wrap_list(
+    indent: usize,
+    start: &str,
+    args: &[String],
+    end: &str,
+    max_width: usize,
+    max_list_width: usize,
+) -> String {
+    let list = args.join(", ");
+    let line = format!("{:indent$}{}{}{}", "", start, list, end, indent = indent);
+
+    if line.len() <= max_width && list.len() <= max_list_width {
+        return line;
+    }
+
+    let mut output = format!("{:indent$}{}\n", "", start, indent = indent);
+
+    for arg in args {
+        output.push_str(&format!("{:indent$}{},\n", "", arg, indent = indent + 4));
+    }
+
+    output.push_str(&format!("{:indent$}{}", "", end, indent = indent));
+    output
+}
+
+/// Qualified filenames (see `ApiDef::qualified_filename`) of the other def files `api_def` depends
+/// on: the files it imports, the files that defines the types (see `Variable::type_def_file`) and
+/// array size consts of the variables where `filter` returns true and the consts that other consts
+/// are set to. Fields of the structs that are derived from are included as they are written into
+/// the structs that derives them. `api_defs` is all the def files. Used for the includes and
+/// imports at the top of the generated files
+pub fn dependencies<F: Fn(&Variable) -> bool>(
+    api_def: &ApiDef,
+    api_defs: &[ApiDef],
+    filter: F,
+) -> BTreeSet<String> {
+    let mut files = api_def.imported_files(api_defs);
+
+    let mut add = |var: &Variable| {
+        if !filter(var) {
+            return;
+        }
+
+        if !var.type_def_file.is_empty() {
+            files.insert(var.type_def_file.to_owned());
+        }
+
+        if let Some(ArrayType::SizedArray(size)) = &var.array {
+            if let Some(def) = find_const_def(api_defs, &var.namespace, size) {
+                files.insert(def.qualified_filename());
+            }
+        }
+    };
+
+    api_def.visit_variables(&mut add);
+
+    for s in &api_def.structs {
+        for derived in get_derived_structs(api_defs, s) {
+            derived.variables.iter().for_each(&mut add);
+        }
+    }
+
+    for c in &api_def.consts {
+        if let Some(def) = find_const_def(api_defs, &c.namespace, &c.value) {
+            files.insert(def.qualified_filename());
+        }
+    }
+
+    files.remove(&api_def.qualified_filename());
+    files
+}
+
+/// Writes an empty line before everything except the first item
+pub fn write_separator<W: Write>(out: &mut W, first: &mut bool) -> Result<()> {
+    if !*first {
+        writeln!(out)?;
+    }
+
+    *first = false;
+    Ok(())
+}
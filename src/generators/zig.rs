@@ -0,0 +1,727 @@
+//! Generates Zig bindings for each def file.
+//!
+//! Structs and unions are `extern struct`/`extern union`, handles are `opaque` types, regular enums
+//! are `enum(u32)` and bitflags are `packed struct(u32)` with one `bool` per flag (`u64` when the
+//! values doesn't fit in 32 bits). Functions are
+//! declared with `extern fn` using the same names and ABI as the C header. Values marked with `?`
+//! are optional pointers (`?*T`). Handles also get their functions as decls so they can be called
+//! with method syntax (`image.getInfo()`). Consts and array sizes that refers to consts in other
+//! files are qualified with the import (`types.MAX_NAME`).
+use crate::api_parser::{
+    ApiDef, ArrayType, Enum, EnumType, Function, Result, Struct, TypeModifier, Variable,
+    VariableType,
+};
+use crate::generators::c::is_opaque;
+use crate::generators::util::dependencies;
+use crate::{find_const_def, get_derived_structs, write_c_commments};
+use heck::{ToLowerCamelCase, ToSnakeCase};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Keywords and primitive type names that has to be written as `@"name"`
+const ZIG_KEYWORDS: &[&str] = &[
+    "addrspace",
+    "align",
+    "allowzero",
+    "and",
+    "anyframe",
+    "anytype",
+    "asm",
+    "async",
+    "await",
+    "break",
+    "callconv",
+    "catch",
+    "comptime",
+    "const",
+    "continue",
+    "defer",
+    "else",
+    "enum",
+    "errdefer",
+    "error",
+    "export",
+    "extern",
+    "fn",
+    "for",
+    "if",
+    "inline",
+    "linksection",
+    "noalias",
+    "noinline",
+    "nosuspend",
+    "opaque",
+    "or",
+    "orelse",
+    "packed",
+    "pub",
+    "resume",
+    "return",
+    "struct",
+    "suspend",
+    "switch",
+    "test",
+    "threadlocal",
+    "try",
+    "union",
+    "unreachable",
+    "usingnamespace",
+    "var",
+    "volatile",
+    "while",
+    "anyerror",
+    "anyopaque",
+    "bool",
+    "false",
+    "null",
+    "noreturn",
+    "true",
+    "type",
+    "undefined",
+    "void",
+];
+
+/// Writes Zig bindings
+#[derive(Debug, Clone, Default)]
+pub struct ZigGenerator {
+    /// Has to match the prefix used for the C code
+    pub c_prefix: String,
+}
+
+fn zig_name(name: &str) -> String {
+    if ZIG_KEYWORDS.contains(&name) {
+        format!("@\"{}\"", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Integer type of an enum. The C enum (and flags type) is 64 bit when the values doesn't fit in
+/// 32 bits
+/// Reference to the const `name` used from `namespace` in `api_def`. Consts from other files are
+/// used through the import
+fn const_ref(
+    name: &str,
+    namespace: &[String],
+    api_def: &ApiDef,
+    api_defs: &[ApiDef],
+) -> Option<String> {
+    let def = find_const_def(api_defs, namespace, name)?;
+
+    if def.qualified_filename() == api_def.qualified_filename() {
+        Some(zig_name(name))
+    } else {
+        Some(format!("{}.{}", def.qualified_filename(), zig_name(name)))
+    }
+}
+
+/// Size of an array that is either a number or a const
+fn array_size(var: &Variable, size: &str, api_def: &ApiDef, api_defs: &[ApiDef]) -> String {
+    const_ref(size, &var.namespace, api_def, api_defs).unwrap_or_else(|| size.to_owned())
+}
+
+fn bits_type(e: &Enum) -> &'static str {
+    if e.is_64bit() {
+        "u64"
+    } else {
+        "u32"
+    }
+}
+
+impl ZigGenerator {
+    pub fn new(c_prefix: &str) -> ZigGenerator {
+        ZigGenerator {
+            c_prefix: c_prefix.to_owned(),
+        }
+    }
+
+    /// Name of the file generated for `api_def`
+    pub fn file_name(api_def: &ApiDef) -> String {
+        format!("{}.zig", api_def.qualified_filename())
+    }
+
+    /// Writes the bindings for `api_def`. `api_defs` is all the def files and is used to look up
+    /// structs used with derive. Types from other def files are used through `@import`.
+    pub fn generate<W: Write>(
+        &self,
+        out: &mut W,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        writeln!(
+            out,
+            "// This file is generated by apigen from {}. Do not edit.",
+            api_def.filename
+        )?;
+
+        let imports = dependencies(api_def, api_defs, |_| true);
+
+        if !imports.is_empty() {
+            writeln!(out)?;
+
+            for module in &imports {
+                writeln!(out, "const {} = @import(\"{}.zig\");", module, module)?;
+            }
+        }
+
+        let consts = api_def
+            .consts
+            .iter()
+            .filter(|c| !c.name.starts_with("_MANUAL"))
+            .collect::<Vec<_>>();
+
+        if !consts.is_empty() {
+            writeln!(out)?;
+
+            for c in consts {
+                // Values that aren't literals has to be other consts. Anything else is an error
+                // when the const is used
+                let value = if c.value.starts_with('"')
+                    || c.value.starts_with(|c: char| c.is_ascii_digit())
+                {
+                    c.value.to_owned()
+                } else if let Some(value) = const_ref(&c.value, &c.namespace, api_def, api_defs) {
+                    value
+                } else {
+                    format!(
+                        "@compileError(\"{} is set to '{}' which isn't a number, string or const\")",
+                        c.name, c.value
+                    )
+                };
+
+                write_c_commments(out, &c.doc_comments, 0)?;
+                writeln!(out, "pub const {} = {};", zig_name(&c.name), value)?;
+            }
+        }
+
+        for t in &api_def.types {
+            writeln!(out)?;
+            write_c_commments(out, &t.var.doc_comments, 0)?;
+            writeln!(
+                out,
+                "pub const {} = {};",
+                t.var.name,
+                self.field_type(&t.var, api_def, api_defs)
+            )?;
+        }
+
+        for e in &api_def.enums {
+            match e.enum_type {
+                EnumType::Regular => self.write_enum(out, e)?,
+                EnumType::Bitflags => self.write_bitflags(out, e)?,
+            }
+
+            if !e.flags_name.is_empty() {
+                writeln!(out)?;
+                writeln!(out, "pub const {} = {};", e.flags_name, e.name)?;
+            }
+        }
+
+        for c in &api_def.callbacks {
+            writeln!(out)?;
+            write_c_commments(out, &c.doc_comments, 0)?;
+            writeln!(
+                out,
+                "pub const {} = ?*const fn ({}) callconv(.c) {};",
+                c.name,
+                self.arguments(c, "", api_def, api_defs, &imports)
+                    .join(", "),
+                self.return_type(c, api_def)
+            )?;
+        }
+
+        for s in &api_def.structs {
+            if is_opaque(s) {
+                self.write_opaque(out, s)?;
+            } else {
+                self.write_struct(out, "struct", s, api_def, api_defs)?;
+            }
+        }
+
+        for u in &api_def.unions {
+            self.write_struct(out, "union", u, api_def, api_defs)?;
+        }
+
+        for s in &api_def.structs {
+            for func in &s.functions {
+                writeln!(out)?;
+                write_c_commments(out, &func.doc_comments, 0)?;
+                writeln!(
+                    out,
+                    "pub extern fn {}({}) {};",
+                    func.get_c_name(&s.name, &self.c_prefix),
+                    self.arguments(func, &s.name, api_def, api_defs, &imports)
+                        .join(", "),
+                    self.return_type(func, api_def)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_enum<W: Write>(&self, out: &mut W, e: &Enum) -> Result<()> {
+        writeln!(out)?;
+        write_c_commments(out, &e.doc_comments, 0)?;
+        writeln!(out, "pub const {} = enum({}) {{", e.name, bits_type(e))?;
+
+        for entry in &e.entries {
+            write_c_commments(out, &entry.doc_comments, 4)?;
+            writeln!(
+                out,
+                "    {} = {},",
+                zig_name(&entry.name.to_snake_case()),
+                entry.value
+            )?;
+        }
+
+        writeln!(out, "}};")?;
+
+        Ok(())
+    }
+
+    /// Flags with one bit set becomes `bool` fields. Bits without flags are padding and flags
+    /// with more than one bit set are added as consts
+    fn write_bitflags<W: Write>(&self, out: &mut W, e: &Enum) -> Result<()> {
+        let size = if e.is_64bit() { 64 } else { 32 };
+        let mut bits: Vec<Option<&str>> = vec![None; size];
+        let mut combined = Vec::new();
+
+        for entry in &e.entries {
+            if entry.value.count_ones() == 1 && (entry.value.trailing_zeros() as usize) < size {
+                bits[entry.value.trailing_zeros() as usize] = Some(&entry.name);
+            } else {
+                combined.push(entry);
+            }
+        }
+
+        writeln!(out)?;
+        write_c_commments(out, &e.doc_comments, 0)?;
+        writeln!(
+            out,
+            "pub const {} = packed struct({}) {{",
+            e.name,
+            bits_type(e)
+        )?;
+
+        let mut padding = 0;
+
+        for (bit, name) in bits.iter().enumerate() {
+            match name {
+                Some(name) => {
+                    if padding > 0 {
+                        writeln!(out, "    _padding{}: u{} = 0,", bit - padding, padding)?;
+                        padding = 0;
+                    }
+
+                    let entry = e.entries.iter().find(|entry| entry.name == *name).unwrap();
+                    write_c_commments(out, &entry.doc_comments, 4)?;
+                    writeln!(
+                        out,
+                        "    {}: bool = false,",
+                        zig_name(&name.to_snake_case())
+                    )?;
+                }
+                None => padding += 1,
+            }
+        }
+
+        if padding > 0 {
+            writeln!(out, "    _padding{}: u{} = 0,", size - padding, padding)?;
+        }
+
+        if !combined.is_empty() {
+            writeln!(out)?;
+
+            for entry in combined {
+                write_c_commments(out, &entry.doc_comments, 4)?;
+                writeln!(
+                    out,
+                    "    pub const {}: {} = @bitCast(@as({}, 0x{:x}));",
+                    zig_name(&entry.name.to_snake_case()),
+                    e.name,
+                    bits_type(e),
+                    entry.value
+                )?;
+            }
+        }
+
+        writeln!(out, "}};")?;
+
+        Ok(())
+    }
+
+    /// Handles are opaque and has the functions as decls so they can be used as methods
+    fn write_opaque<W: Write>(&self, out: &mut W, s: &Struct) -> Result<()> {
+        writeln!(out)?;
+        write_c_commments(out, &s.doc_comments, 0)?;
+
+        if s.functions.is_empty() {
+            writeln!(out, "pub const {} = opaque {{}};", s.name)?;
+            return Ok(());
+        }
+
+        writeln!(out, "pub const {} = opaque {{", s.name)?;
+
+        for func in &s.functions {
+            writeln!(
+                out,
+                "    pub const {} = {};",
+                zig_name(&func.name.to_lower_camel_case()),
+                func.get_c_name(&s.name, &self.c_prefix)
+            )?;
+        }
+
+        writeln!(out, "}};")?;
+
+        Ok(())
+    }
+
+    fn write_struct<W: Write>(
+        &self,
+        out: &mut W,
+        kind: &str,
+        s: &Struct,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        writeln!(out)?;
+        write_c_commments(out, &s.doc_comments, 0)?;
+        writeln!(out, "pub const {} = extern {} {{", s.name, kind)?;
+
+        for derived in get_derived_structs(api_defs, s) {
+            writeln!(out, "    // From {}", derived.name)?;
+
+            for var in &derived.variables {
+                self.write_field(out, var, api_def, api_defs)?;
+            }
+        }
+
+        for var in &s.variables {
+            write_c_commments(out, &var.doc_comments, 4)?;
+            self.write_field(out, var, api_def, api_defs)?;
+        }
+
+        writeln!(out, "}};")?;
+
+        Ok(())
+    }
+
+    fn write_field<W: Write>(
+        &self,
+        out: &mut W,
+        var: &Variable,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+    ) -> Result<()> {
+        let name = zig_name(&var.name);
+
+        match var.array {
+            Some(ArrayType::Unsized) => {
+                writeln!(
+                    out,
+                    "    {}: ?[*]{},",
+                    name,
+                    self.field_type(&Self::element(var), api_def, api_defs)
+                )?;
+                writeln!(out, "    {}_size: u64,", var.name)?;
+            }
+            _ => writeln!(
+                out,
+                "    {}: {},",
+                name,
+                self.field_type(var, api_def, api_defs)
+            )?,
+        }
+
+        Ok(())
+    }
+
+    fn element(var: &Variable) -> Variable {
+        Variable {
+            array: None,
+            ..var.clone()
+        }
+    }
+
+    /// Name of a type defined in a def file. Types from other files are used through the import
+    fn type_name(var: &Variable, api_def: &ApiDef) -> String {
+        if !var.type_def_file.is_empty() && var.type_def_file != api_def.qualified_filename() {
+            format!("{}.{}", var.type_def_file, var.type_name)
+        } else {
+            var.type_name.to_owned()
+        }
+    }
+
+    /// Type without modifiers and arrays
+    fn value_type(&self, var: &Variable, self_name: &str, api_def: &ApiDef) -> String {
+        match var.vtype {
+            VariableType::Primitive if var.type_name == "void" => "anyopaque".to_owned(),
+            VariableType::Primitive => var.type_name.to_owned(),
+            VariableType::SelfType => self_name.to_owned(),
+            VariableType::Str => "[*:0]const u8".to_owned(),
+            VariableType::None => "anyopaque".to_owned(),
+            VariableType::Handle
+            | VariableType::Regular
+            | VariableType::Enum
+            | VariableType::Alias
+            | VariableType::Callback => Self::type_name(var, api_def),
+        }
+    }
+
+    /// Type matching `Variable::c_type`. Handles (and self) are pointers, void pointers are always
+    /// optional and other pointers are optional if the variable is marked with `?`
+    fn var_type(&self, var: &Variable, self_name: &str, api_def: &ApiDef) -> String {
+        let t = self.value_type(var, self_name, api_def);
+        let optional = if var.optional { "?" } else { "" };
+        let is_void = var.vtype == VariableType::Primitive && var.type_name == "void";
+
+        let t = match var.vtype {
+            VariableType::Handle | VariableType::SelfType => format!("*{}", t),
+            VariableType::Str if var.type_modifier == TypeModifier::None => {
+                return format!("{}{}", optional, t)
+            }
+            _ => t,
+        };
+
+        let optional = if is_void { "?" } else { optional };
+
+        match var.type_modifier {
+            TypeModifier::None if t.starts_with('*') => format!("{}{}", optional, t),
+            TypeModifier::None => t,
+            TypeModifier::ConstPointer | TypeModifier::Reference => {
+                format!("{}*const {}", optional, t)
+            }
+            TypeModifier::MutPointer => format!("{}*{}", optional, t),
+        }
+    }
+
+    /// Strings in structs can be null
+    fn field_type(&self, var: &Variable, api_def: &ApiDef, api_defs: &[ApiDef]) -> String {
+        let t = match var.vtype {
+            VariableType::Str if var.type_modifier == TypeModifier::None => {
+                "?[*:0]const u8".to_owned()
+            }
+            _ => self.var_type(&Self::element(var), "", api_def),
+        };
+
+        match var.array {
+            Some(ArrayType::SizedArray(ref size)) => {
+                format!("[{}]{}", array_size(var, size, api_def, api_defs), t)
+            }
+            _ => t,
+        }
+    }
+
+    /// Arguments using the same rules as `Function::c_args`. Zig doesn't allow arguments
+    /// to shadow the imports so those gets a `_` suffix
+    fn arguments(
+        &self,
+        func: &Function,
+        self_name: &str,
+        api_def: &ApiDef,
+        api_defs: &[ApiDef],
+        imports: &BTreeSet<String>,
+    ) -> Vec<String> {
+        let mut args = Vec::with_capacity(func.function_args.len());
+
+        for arg in &func.function_args {
+            let name = if imports.contains(&arg.name) {
+                format!("{}_", arg.name)
+            } else {
+                zig_name(&arg.name)
+            };
+            let element = self.var_type(&Self::element(arg), self_name, api_def);
+
+            match arg.array {
+                None => args.push(format!("{}: {}", name, element)),
+                Some(ArrayType::Unsized) => {
+                    args.push(format!("{}: [*]const {}", name, element));
+                    args.push(format!("{}_size: u64", arg.name));
+                }
+                // C arrays in arguments are pointers
+                Some(ArrayType::SizedArray(ref size)) => {
+                    let size = array_size(arg, size, api_def, api_defs);
+                    args.push(format!("{}: *[{}]{}", name, size, element))
+                }
+            }
+        }
+
+        args
+    }
+
+    fn return_type(&self, func: &Function, api_def: &ApiDef) -> String {
+        match func.return_val.as_ref() {
+            Some(ret)
+                if !(ret.vtype == VariableType::Primitive
+                    && ret.type_name == "void"
+                    && ret.type_modifier == TypeModifier::None) =>
+            {
+                self.var_type(ret, "", api_def)
+            }
+            _ => "void".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::generators::test_util;
+    use crate::resolver::resolve;
+
+    const IMAGE: &str = r#"
+const MAX_NAME = 32
+
+/// Info about an image
+struct ImageInfo {
+    width: u32,
+    name: [u8; MAX_NAME],
+    format: Format,
+    data: [u8],
+}
+
+enum Format {
+    Rgb,
+    Rgba,
+}
+
+[enum_flags(LoadFlags)]
+enum LoadFlag {
+    Cache = 1,
+    Mips = 2,
+    Srgb = 8,
+    All = 11,
+}
+
+callback LoadCallback(image: Image, user_data: *void)
+
+#[attributes(Handle)]
+struct Image {
+    [static] create_from_memory(name: String, data: [u8], flags: LoadFlags) -> Image?,
+    get_info() -> *const ImageInfo?,
+    destroy(),
+}
+"#;
+
+    fn generate() -> String {
+        let mut defs = vec![ApiParser::parse_string(IMAGE, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+
+        let mut out = Vec::new();
+        ZigGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bindings() {
+        let z = generate();
+
+        assert!(z.contains("pub const MAX_NAME = 32;"));
+        assert!(z.contains("pub const Format = enum(u32) {\n    rgb = 0,\n    rgba = 1,\n};"));
+        assert!(z.contains(
+            "pub const LoadFlag = packed struct(u32) {\n    cache: bool = false,\n    mips: bool = false,\n    _padding2: u1 = 0,\n    srgb: bool = false,\n    _padding4: u28 = 0,\n\n    pub const all: LoadFlag = @bitCast(@as(u32, 0xb));\n};"
+        ));
+        assert!(z.contains("pub const LoadFlags = LoadFlag;"));
+        assert!(z.contains(
+            "pub const LoadCallback = ?*const fn (image: *Image, user_data: ?*anyopaque) callconv(.c) void;"
+        ));
+        assert!(z.contains(
+            "pub const ImageInfo = extern struct {\n    width: u32,\n    name: [MAX_NAME]u8,\n    format: Format,\n    data: ?[*]u8,\n    data_size: u64,\n};"
+        ));
+        assert!(z.contains("pub const Image = opaque {\n    pub const createFromMemory = FL_Image_create_from_memory;"));
+        assert!(z.contains(
+            "pub extern fn FL_Image_create_from_memory(name: [*:0]const u8, data: [*]const u8, data_size: u64, flags: LoadFlags) ?*Image;"
+        ));
+        assert!(z.contains("pub extern fn FL_Image_get_info(self: *Image) ?*const ImageInfo;"));
+        assert!(z.contains("pub extern fn FL_Image_destroy(self: *Image) void;"));
+    }
+
+    const EDGE_CASES: &str = r#"
+[enum_flags(BigFlags)]
+enum BigFlag {
+    Low = 1,
+    High = 0x100000000,
+    Both = 0x100000001,
+}
+
+enum Big {
+    Small = 3,
+    Large = 0x100000005,
+}
+
+enum Mode {
+    error,
+    type,
+}
+"#;
+
+    #[test]
+    fn test_edge_cases() {
+        let defs = test_util::parse(&[("edge.def", EDGE_CASES)]);
+
+        let mut out = Vec::new();
+        ZigGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        let z = String::from_utf8(out).unwrap();
+
+        assert!(z.contains(
+            "pub const BigFlag = packed struct(u64) {\n    low: bool = false,\n    _padding1: u31 = 0,\n    high: bool = false,\n    _padding33: u31 = 0,\n\n    pub const both: BigFlag = @bitCast(@as(u64, 0x100000001));\n};"
+        ));
+        assert!(
+            z.contains("pub const Big = enum(u64) {\n    small = 3,\n    large = 4294967301,\n};")
+        );
+        assert!(
+            z.contains("pub const Mode = enum(u32) {\n    @\"error\" = 0,\n    @\"type\" = 1,\n};")
+        );
+    }
+
+    #[test]
+    fn test_consts_from_other_files() {
+        let defs = test_util::parse(&[
+            (
+                "types.def",
+                "const MAX_NAME = 32\nconst NAME_SIZE = MAX_NAME",
+            ),
+            (
+                "image.def",
+                "import \"types.def\"
+const DEFAULT_NAME_SIZE = NAME_SIZE
+const INVALID = unknown
+struct Info {
+    name: [u8; MAX_NAME],
+}
+#[attributes(Handle)]
+struct Image {
+    set_name(name: [u8; NAME_SIZE]),
+}",
+            ),
+        ]);
+
+        let mut out = Vec::new();
+        ZigGenerator::new("FL_")
+            .generate(&mut out, &defs[1], &defs)
+            .unwrap();
+        let z = String::from_utf8(out).unwrap();
+
+        assert!(z.contains("const types = @import(\"types.zig\");"));
+        assert!(z.contains("pub const DEFAULT_NAME_SIZE = types.NAME_SIZE;"));
+        assert!(z.contains("pub const INVALID = @compileError(\"INVALID is set to 'unknown' which isn't a number, string or const\");"));
+        assert!(z.contains("    name: [types.MAX_NAME]u8,"));
+        assert!(z.contains(
+            "pub extern fn FL_Image_set_name(self: *Image, name: *[types.NAME_SIZE]u8) void;"
+        ));
+
+        let mut out = Vec::new();
+        ZigGenerator::new("FL_")
+            .generate(&mut out, &defs[0], &defs)
+            .unwrap();
+        let z = String::from_utf8(out).unwrap();
+
+        assert!(z.contains("pub const NAME_SIZE = MAX_NAME;"));
+    }
+}
@@ -0,0 +1,807 @@
+//! Versioned intermediate representation of the parsed data. The IR is a stable JSON format for
+//! generators that aren't written in Rust. The layout of the IR is described by the JSON Schema
+//! in `schema/apigen_ir.schema.json` and changes to it bumps `IR_VERSION`.
+use crate::api_parser::{
+    split_namespace, ApiDef, ApiParser, ApigenError, ArrayType, Const, Enum, EnumEntry, EnumType,
+    Function, FunctionType, Import, Ownership, Result, Span, Struct, Type, TypeModifier, Variable,
+    VariableType,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Current version of the IR. Stored in the `apigen_ir` field of every document
+pub const IR_VERSION: u32 = 1;
+
+/// A full IR document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrDocument {
+    /// Version of the IR. Always `IR_VERSION` when produced by this crate
+    pub apigen_ir: u32,
+    /// All the parsed def files
+    pub files: Vec<IrFile>,
+}
+
+/// A single def file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrFile {
+    /// Full path of the file as given to the parser
+    pub path: String,
+    /// Filename without directory and extension
+    pub name: String,
+    /// Namespace declared with `namespace` (such as `render::gl`). Missing for the global
+    /// namespace
+    pub namespace: Option<String>,
+    /// Names given with `mod`
+    pub mods: Vec<String>,
+    /// Files imported with `use` or `import`
+    pub imports: Vec<IrImport>,
+    /// Structs in the order they are declared
+    pub structs: Vec<IrStruct>,
+    /// Unions in the order they are declared
+    pub unions: Vec<IrStruct>,
+    /// Enums in the order they are declared
+    pub enums: Vec<IrEnum>,
+    /// Type aliases declared with `type`
+    pub aliases: Vec<IrAlias>,
+    /// Callbacks declared with `callback`
+    pub callbacks: Vec<IrFunction>,
+    /// Constants declared with `const`
+    pub consts: Vec<IrConst>,
+}
+
+/// Position in the def file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrLocation {
+    /// Line (1 based, 0 if unknown)
+    pub line: usize,
+    /// Column (1 based, 0 if unknown)
+    pub column: usize,
+}
+
+/// A struct or union
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrStruct {
+    /// Name of the struct
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// Names given with `#[attributes(...)]` such as `Handle` and `Drop`
+    pub attributes: Vec<String>,
+    /// Names given with `[traits(...)]`
+    pub traits: Vec<String>,
+    /// Structs this struct derives from
+    pub derives: Vec<String>,
+    /// Data members
+    pub fields: Vec<IrVariable>,
+    /// Functions
+    pub functions: Vec<IrFunction>,
+    /// Where the struct is declared
+    pub location: IrLocation,
+}
+
+/// What kind of function
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IrFunctionKind {
+    /// Takes `self` as first argument
+    Method,
+    /// Declared with `[static]` (and callbacks)
+    Static,
+    /// Declared with `[manual]`. Implemented by hand in some generators
+    Manual,
+    /// Destroys the object. Declared with `[drop]` or named `destroy` in a struct with the `Drop`
+    /// attribute
+    Drop,
+}
+
+/// Who owns a handle returned from a function
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IrOwnership {
+    /// Static functions give the caller ownership of the handle and methods return a borrowed
+    /// handle
+    Default,
+    /// Declared with `[owned]`. The caller owns the handle and has to destroy it
+    Owned,
+    /// Declared with `[borrowed]`. The caller mustn't destroy the handle
+    Borrowed,
+}
+
+/// A function or callback
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrFunction {
+    /// Name of the function
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// Kind of function
+    pub kind: IrFunctionKind,
+    /// Who owns a returned handle
+    pub ownership: IrOwnership,
+    /// Arguments. Methods has a first argument of type `self`
+    pub args: Vec<IrVariable>,
+    /// Return type. Missing if the function doesn't return anything
+    pub returns: Option<IrType>,
+    /// Where the function is declared
+    pub location: IrLocation,
+}
+
+/// A named value such as a field or argument
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrVariable {
+    /// Name of the variable
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// Type of the variable
+    #[serde(rename = "type")]
+    pub ty: IrType,
+    /// Default value as written in the def file
+    pub default_value: Option<String>,
+    /// Where the variable is declared
+    pub location: IrLocation,
+}
+
+/// What a type refers to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IrTypeKind {
+    /// Built in type such as `u32` or `f32`
+    Primitive,
+    /// `String`
+    String,
+    /// The struct the function belongs to
+    SelfType,
+    /// A struct or union
+    Struct,
+    /// A struct with the `Handle` attribute. Handles are opaque and always used through a pointer
+    Handle,
+    /// An enum
+    Enum,
+    /// A type alias
+    Alias,
+    /// A callback
+    Callback,
+    /// The type couldn't be resolved
+    Unknown,
+}
+
+/// Pointer/reference on a type
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IrModifier {
+    /// Plain value
+    None,
+    /// `*const T`
+    ConstPointer,
+    /// `*T`
+    Pointer,
+    /// `&T`
+    Reference,
+}
+
+/// Use of a type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrType {
+    /// What kind of type this is
+    pub kind: IrTypeKind,
+    /// Name of the type (such as `u32` or `Image`)
+    pub name: String,
+    /// Name of the file the type is defined in with its namespace in front joined with `_`
+    /// (`render_gl_texture` for `texture.def` in `render::gl`). Missing for built in types
+    pub defined_in: Option<String>,
+    /// Namespace (see `IrFile::namespace`) the type is defined in. Missing for the global
+    /// namespace and built in types
+    pub namespace: Option<String>,
+    /// Pointer or reference
+    pub modifier: IrModifier,
+    /// Set if the type is an array
+    pub array: Option<IrArray>,
+    /// Nullable (declared with `?`)
+    pub optional: bool,
+}
+
+/// Array info
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrArray {
+    /// Size as a number or const name. Missing for unsized arrays (pointer + size)
+    pub size: Option<String>,
+}
+
+/// How an enum should be represented
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IrEnumKind {
+    /// Sequential values without overlap
+    Regular,
+    /// Power of two or overlapping values that are combined with or
+    Bitflags,
+}
+
+/// An enum
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrEnum {
+    /// Name of the enum
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// How the enum should be represented
+    pub kind: IrEnumKind,
+    /// Name of the type holding a combination of values. Given with `[enum_flags(...)]`
+    pub flags_type: Option<String>,
+    /// All the entries with their values
+    pub entries: Vec<IrEnumEntry>,
+    /// Where the enum is declared
+    pub location: IrLocation,
+}
+
+/// Entry in an enum
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrEnumEntry {
+    /// Name of the entry
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// Value of the entry
+    pub value: u64,
+}
+
+/// A type alias
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrAlias {
+    /// Name of the alias
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// Type the alias refers to
+    pub target: IrType,
+    /// Where the alias is declared
+    pub location: IrLocation,
+}
+
+/// Import of another def file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrImport {
+    /// Path of the imported file relative to the directory of the importing file
+    pub path: String,
+    /// Name (see `IrFile::name`) of the imported file
+    pub file: String,
+    /// Name imported with `use`. Missing for `import` that imports all names in the file
+    pub name: Option<String>,
+    /// Namespace (see `IrFile::namespace`) of the imported file. Missing for the global namespace
+    pub namespace: Option<String>,
+    /// Where the import is declared
+    pub location: IrLocation,
+}
+
+/// A constant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IrConst {
+    /// Name of the constant
+    pub name: String,
+    /// Documentation, one entry per line
+    pub doc: Vec<String>,
+    /// Value as written in the def file (strings includes the quotes)
+    pub value: String,
+}
+
+impl IrDocument {
+    /// Builds the IR for a set of (resolved) def files
+    pub fn from_api_defs(api_defs: &[ApiDef]) -> IrDocument {
+        IrDocument {
+            apigen_ir: IR_VERSION,
+            files: api_defs.iter().map(IrFile::from).collect(),
+        }
+    }
+
+    /// Converts the IR back to the parser data
+    pub fn to_api_defs(&self) -> Vec<ApiDef> {
+        self.files.iter().map(IrFile::to_api_def).collect()
+    }
+
+    /// Writes the document as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Loads a document. Only documents with the version `IR_VERSION` are supported
+    pub fn from_json(data: &str) -> Result<IrDocument> {
+        let value: Value =
+            serde_json::from_str(data).map_err(|e| ApigenError::Ir(e.to_string()))?;
+
+        let version = value
+            .get("apigen_ir")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| ApigenError::Ir("missing `apigen_ir` version".to_owned()))?;
+
+        if version != IR_VERSION as u64 {
+            return Err(ApigenError::Ir(format!(
+                "unsupported IR version {} (supported: {})",
+                version, IR_VERSION
+            )));
+        }
+
+        serde_json::from_value(value).map_err(|e| ApigenError::Ir(e.to_string()))
+    }
+
+    /// JSON Schema for the current version of the IR
+    pub fn json_schema() -> String {
+        serde_json::to_string_pretty(&schemars::schema_for!(IrDocument)).unwrap()
+    }
+}
+
+impl From<&Span> for IrLocation {
+    fn from(span: &Span) -> IrLocation {
+        IrLocation {
+            line: span.line,
+            column: span.column,
+        }
+    }
+}
+
+/// The IR only stores the line and column so the rest of the span is left empty
+impl From<IrLocation> for Span {
+    fn from(location: IrLocation) -> Span {
+        Span {
+            line: location.line,
+            column: location.column,
+            ..Default::default()
+        }
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_owned())
+    }
+}
+
+impl From<&ApiDef> for IrFile {
+    fn from(api_def: &ApiDef) -> IrFile {
+        IrFile {
+            path: api_def.filename.to_owned(),
+            name: api_def.base_filename.to_owned(),
+            namespace: non_empty(&api_def.namespace.join("::")),
+            mods: api_def.mods.to_owned(),
+            imports: api_def.imports.iter().map(IrImport::from).collect(),
+            structs: api_def.structs.iter().map(IrStruct::from).collect(),
+            unions: api_def.unions.iter().map(IrStruct::from).collect(),
+            enums: api_def.enums.iter().map(IrEnum::from).collect(),
+            aliases: api_def.types.iter().map(IrAlias::from).collect(),
+            callbacks: api_def.callbacks.iter().map(IrFunction::from).collect(),
+            consts: api_def.consts.iter().map(IrConst::from).collect(),
+        }
+    }
+}
+
+impl IrFile {
+    fn to_api_def(&self) -> ApiDef {
+        let def_file = &self.name;
+
+        let mut api_def = ApiDef {
+            filename: self.path.to_owned(),
+            base_filename: self.name.to_owned(),
+            namespace: split_namespace(self.namespace.as_deref().unwrap_or_default()),
+            mods: self.mods.to_owned(),
+            imports: self.imports.iter().map(IrImport::to_import).collect(),
+            callbacks: self
+                .callbacks
+                .iter()
+                .map(|f| f.to_function(def_file))
+                .collect(),
+            structs: self.structs.iter().map(|s| s.to_struct(def_file)).collect(),
+            enums: self.enums.iter().map(|e| e.to_enum(def_file)).collect(),
+            types: self.aliases.iter().map(|a| a.to_type(def_file)).collect(),
+            unions: self.unions.iter().map(|s| s.to_struct(def_file)).collect(),
+            consts: self.consts.iter().map(IrConst::to_const).collect(),
+        };
+
+        ApiParser::set_namespace(&mut api_def);
+        api_def
+    }
+}
+
+impl From<&Struct> for IrStruct {
+    fn from(s: &Struct) -> IrStruct {
+        IrStruct {
+            name: s.name.to_owned(),
+            doc: s.doc_comments.to_owned(),
+            attributes: s.attributes.to_owned(),
+            traits: s.traits.to_owned(),
+            derives: s.derives.to_owned(),
+            fields: s.variables.iter().map(IrVariable::from).collect(),
+            functions: s
+                .functions
+                .iter()
+                .map(|func| {
+                    let mut ir = IrFunction::from(func);
+
+                    if s.is_drop_function(func) {
+                        ir.kind = IrFunctionKind::Drop;
+                    }
+
+                    ir
+                })
+                .collect(),
+            location: (&s.span).into(),
+        }
+    }
+}
+
+impl IrStruct {
+    fn to_struct(&self, def_file: &str) -> Struct {
+        Struct {
+            doc_comments: self.doc.to_owned(),
+            name: self.name.to_owned(),
+            def_file: def_file.to_owned(),
+            namespace: Vec::new(),
+            variables: self
+                .fields
+                .iter()
+                .map(|v| v.to_variable(def_file))
+                .collect(),
+            functions: self
+                .functions
+                .iter()
+                .map(|f| f.to_function(def_file))
+                .collect(),
+            attributes: self.attributes.to_owned(),
+            traits: self.traits.to_owned(),
+            derives: self.derives.to_owned(),
+            span: self.location.into(),
+        }
+    }
+}
+
+impl From<&Function> for IrFunction {
+    fn from(func: &Function) -> IrFunction {
+        let kind = match func.func_type {
+            FunctionType::Regular => IrFunctionKind::Method,
+            FunctionType::Static => IrFunctionKind::Static,
+            FunctionType::Manual => IrFunctionKind::Manual,
+            FunctionType::Drop => IrFunctionKind::Drop,
+        };
+
+        let ownership = match func.ownership {
+            Ownership::Default => IrOwnership::Default,
+            Ownership::Owned => IrOwnership::Owned,
+            Ownership::Borrowed => IrOwnership::Borrowed,
+        };
+
+        IrFunction {
+            name: func.name.to_owned(),
+            doc: func.doc_comments.to_owned(),
+            kind,
+            ownership,
+            args: func.function_args.iter().map(IrVariable::from).collect(),
+            returns: func.return_val.as_ref().map(IrType::from),
+            location: (&func.span).into(),
+        }
+    }
+}
+
+impl IrFunction {
+    fn to_function(&self, def_file: &str) -> Function {
+        let func_type = match self.kind {
+            IrFunctionKind::Method => FunctionType::Regular,
+            IrFunctionKind::Static => FunctionType::Static,
+            IrFunctionKind::Manual => FunctionType::Manual,
+            IrFunctionKind::Drop => FunctionType::Drop,
+        };
+
+        Function {
+            doc_comments: self.doc.to_owned(),
+            def_file: def_file.to_owned(),
+            namespace: Vec::new(),
+            name: self.name.to_owned(),
+            function_args: self.args.iter().map(|v| v.to_variable(def_file)).collect(),
+            return_val: self.returns.as_ref().map(|t| t.to_variable("", def_file)),
+            func_type,
+            ownership: match self.ownership {
+                IrOwnership::Default => Ownership::Default,
+                IrOwnership::Owned => Ownership::Owned,
+                IrOwnership::Borrowed => Ownership::Borrowed,
+            },
+            span: self.location.into(),
+        }
+    }
+}
+
+impl From<&Variable> for IrVariable {
+    fn from(var: &Variable) -> IrVariable {
+        IrVariable {
+            name: var.name.to_owned(),
+            doc: var.doc_comments.to_owned(),
+            ty: IrType::from(var),
+            default_value: non_empty(&var.default_value),
+            location: (&var.span).into(),
+        }
+    }
+}
+
+impl IrVariable {
+    fn to_variable(&self, def_file: &str) -> Variable {
+        Variable {
+            doc_comments: self.doc.to_owned(),
+            default_value: self.default_value.clone().unwrap_or_default(),
+            span: self.location.into(),
+            ..self.ty.to_variable(&self.name, def_file)
+        }
+    }
+}
+
+impl From<&Variable> for IrType {
+    fn from(var: &Variable) -> IrType {
+        let kind = match var.vtype {
+            VariableType::None => IrTypeKind::Unknown,
+            VariableType::SelfType => IrTypeKind::SelfType,
+            VariableType::Enum => IrTypeKind::Enum,
+            VariableType::Regular if var.type_def_file.is_empty() => IrTypeKind::Unknown,
+            VariableType::Regular => IrTypeKind::Struct,
+            VariableType::Handle => IrTypeKind::Handle,
+            VariableType::Str => IrTypeKind::String,
+            VariableType::Primitive => IrTypeKind::Primitive,
+            VariableType::Alias => IrTypeKind::Alias,
+            VariableType::Callback => IrTypeKind::Callback,
+        };
+
+        let modifier = match var.type_modifier {
+            TypeModifier::None => IrModifier::None,
+            TypeModifier::ConstPointer => IrModifier::ConstPointer,
+            TypeModifier::MutPointer => IrModifier::Pointer,
+            TypeModifier::Reference => IrModifier::Reference,
+        };
+
+        let array = var.array.as_ref().map(|a| match a {
+            ArrayType::Unsized => IrArray { size: None },
+            ArrayType::SizedArray(size) => IrArray {
+                size: Some(size.to_owned()),
+            },
+        });
+
+        IrType {
+            kind,
+            name: var.type_name.to_owned(),
+            defined_in: non_empty(&var.type_def_file),
+            namespace: non_empty(&var.type_namespace.join("::")),
+            modifier,
+            array,
+            optional: var.optional,
+        }
+    }
+}
+
+impl IrType {
+    fn to_variable(&self, name: &str, def_file: &str) -> Variable {
+        let vtype = match self.kind {
+            IrTypeKind::Primitive => VariableType::Primitive,
+            IrTypeKind::String => VariableType::Str,
+            IrTypeKind::SelfType => VariableType::SelfType,
+            IrTypeKind::Struct | IrTypeKind::Unknown => VariableType::Regular,
+            IrTypeKind::Handle => VariableType::Handle,
+            IrTypeKind::Enum => VariableType::Enum,
+            IrTypeKind::Alias => VariableType::Alias,
+            IrTypeKind::Callback => VariableType::Callback,
+        };
+
+        let type_modifier = match self.modifier {
+            IrModifier::None => TypeModifier::None,
+            IrModifier::ConstPointer => TypeModifier::ConstPointer,
+            IrModifier::Pointer => TypeModifier::MutPointer,
+            IrModifier::Reference => TypeModifier::Reference,
+        };
+
+        Variable {
+            def_file: def_file.to_owned(),
+            name: name.to_owned(),
+            vtype,
+            type_name: self.name.to_owned(),
+            type_def_file: self.defined_in.clone().unwrap_or_default(),
+            type_namespace: split_namespace(self.namespace.as_deref().unwrap_or_default()),
+            array: self.array.as_ref().map(|a| match a.size {
+                Some(ref size) => ArrayType::SizedArray(size.to_owned()),
+                None => ArrayType::Unsized,
+            }),
+            type_modifier,
+            optional: self.optional,
+            ..Variable::default()
+        }
+    }
+}
+
+impl From<&Enum> for IrEnum {
+    fn from(e: &Enum) -> IrEnum {
+        let kind = match e.enum_type {
+            EnumType::Regular => IrEnumKind::Regular,
+            EnumType::Bitflags => IrEnumKind::Bitflags,
+        };
+
+        IrEnum {
+            name: e.name.to_owned(),
+            doc: e.doc_comments.to_owned(),
+            kind,
+            flags_type: non_empty(&e.flags_name),
+            entries: e
+                .entries
+                .iter()
+                .map(|entry| IrEnumEntry {
+                    name: entry.name.to_owned(),
+                    doc: entry.doc_comments.to_owned(),
+                    value: entry.value,
+                })
+                .collect(),
+            location: (&e.span).into(),
+        }
+    }
+}
+
+impl IrEnum {
+    fn to_enum(&self, def_file: &str) -> Enum {
+        let enum_type = match self.kind {
+            IrEnumKind::Regular => EnumType::Regular,
+            IrEnumKind::Bitflags => EnumType::Bitflags,
+        };
+
+        Enum {
+            doc_comments: self.doc.to_owned(),
+            name: self.name.to_owned(),
+            def_file: def_file.to_owned(),
+            namespace: Vec::new(),
+            enum_type,
+            flags_name: self.flags_type.clone().unwrap_or_default(),
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| EnumEntry {
+                    doc_comments: entry.doc.to_owned(),
+                    name: entry.name.to_owned(),
+                    value: entry.value,
+                    span: Span::default(),
+                })
+                .collect(),
+            span: self.location.into(),
+        }
+    }
+}
+
+impl From<&Type> for IrAlias {
+    fn from(t: &Type) -> IrAlias {
+        IrAlias {
+            name: t.var.name.to_owned(),
+            doc: t.var.doc_comments.to_owned(),
+            target: IrType::from(&t.var),
+            location: (&t.var.span).into(),
+        }
+    }
+}
+
+impl IrAlias {
+    fn to_type(&self, def_file: &str) -> Type {
+        let var = Variable {
+            doc_comments: self.doc.to_owned(),
+            span: self.location.into(),
+            ..self.target.to_variable(&self.name, def_file)
+        };
+
+        Type {
+            doc_comments: self.doc.to_owned(),
+            span: var.span.clone(),
+            var,
+        }
+    }
+}
+
+impl From<&Import> for IrImport {
+    fn from(import: &Import) -> IrImport {
+        IrImport {
+            path: import.path.to_owned(),
+            file: import.def_file.to_owned(),
+            name: import.name.to_owned(),
+            namespace: non_empty(&import.namespace.join("::")),
+            location: (&import.span).into(),
+        }
+    }
+}
+
+impl IrImport {
+    fn to_import(&self) -> Import {
+        Import {
+            path: self.path.to_owned(),
+            def_file: self.file.to_owned(),
+            name: self.name.to_owned(),
+            namespace: split_namespace(self.namespace.as_deref().unwrap_or_default()),
+            span: self.location.into(),
+        }
+    }
+}
+
+impl From<&Const> for IrConst {
+    fn from(c: &Const) -> IrConst {
+        IrConst {
+            name: c.name.to_owned(),
+            doc: c.doc_comments.to_owned(),
+            value: c.value.to_owned(),
+        }
+    }
+}
+
+impl IrConst {
+    fn to_const(&self) -> Const {
+        Const {
+            doc_comments: self.doc.to_owned(),
+            name: self.name.to_owned(),
+            value: self.value.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::resolver::resolve;
+
+    const SCHEMA_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/apigen_ir.schema.json");
+
+    fn document() -> IrDocument {
+        let code = "namespace gfx\n/// Info\nstruct ImageInfo { width: u32, name: [u8; 16] }\n#[attributes(Handle, Drop)]\nstruct Image {\n    [static] create_from_file(filename: String) -> Image?,\n    get_info() -> *const ImageInfo?,\n}\n[enum_flags(Flags)]\nenum Flag { A = 1, B = 2, C = 4 }\ntype Id: u64\nconst SIZE = 16";
+        let mut defs = vec![ApiParser::parse_string(code, "defs/image.def").unwrap()];
+        resolve(&mut defs);
+        IrDocument::from_api_defs(&defs)
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let doc = document();
+        let json = doc.to_json();
+        let value: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["apigen_ir"], 1);
+        assert_eq!(value["files"][0]["name"], "image");
+        assert_eq!(value["files"][0]["namespace"], "gfx");
+        assert_eq!(value["files"][0]["enums"][0]["flags_type"], "Flags");
+        assert_eq!(value["files"][0]["enums"][0]["kind"], "bitflags");
+
+        let func = &value["files"][0]["structs"][1]["functions"][1];
+        assert_eq!(func["kind"], "method");
+        assert_eq!(func["args"][0]["type"]["kind"], "self_type");
+        assert_eq!(func["returns"]["kind"], "struct");
+        let create = &value["files"][0]["structs"][1]["functions"][0];
+        assert_eq!(create["returns"]["kind"], "handle");
+        assert_eq!(func["returns"]["defined_in"], "gfx_image");
+        assert_eq!(func["returns"]["namespace"], "gfx");
+        assert_eq!(func["returns"]["optional"], true);
+
+        assert_eq!(IrDocument::from_json(&json).unwrap(), doc);
+
+        let defs = doc.to_api_defs();
+        assert_eq!(IrDocument::from_api_defs(&defs), doc);
+    }
+
+    #[test]
+    fn test_version_check() {
+        assert!(IrDocument::from_json("{\"files\": []}").is_err());
+        assert!(IrDocument::from_json("{\"apigen_ir\": 2, \"files\": []}").is_err());
+        assert!(IrDocument::from_json("{\"apigen_ir\": 1, \"files\": []}").is_ok());
+    }
+
+    #[test]
+    fn test_schema_up_to_date() {
+        let schema = IrDocument::json_schema() + "\n";
+
+        if std::env::var_os("APIGEN_UPDATE_SCHEMA").is_some() {
+            std::fs::write(SCHEMA_PATH, &schema).unwrap();
+        }
+
+        let current = std::fs::read_to_string(SCHEMA_PATH).unwrap_or_default();
+        assert!(
+            current.replace("\r\n", "\n") == schema,
+            "{} is out of date. Run the tests with APIGEN_UPDATE_SCHEMA=1 to update it",
+            SCHEMA_PATH
+        );
+    }
+}
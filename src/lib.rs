@@ -1,107 +1,192 @@
 use rayon::prelude::*;
 use std::io::Write;
-use std::{path::Path, sync::RwLock};
+use std::path::Path;
 use walkdir::WalkDir;
 
 pub mod api_parser;
+pub mod cst;
+pub mod formatter;
+pub mod generators;
+#[cfg(feature = "ir")]
+pub mod ir;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod resolver;
+pub mod validate;
 pub use crate::api_parser::*;
 
-/// Parse a given file and return the resulting data
+/// Parse a given file and return the resulting data. The files it imports are loaded as well so
+/// the imported types can be resolved.
 pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<ApiDef> {
-    let api_gen = ApiParser::parse_file(path)?;
-    // TODO: Second pass
-    Ok(api_gen)
+    let mut data = vec![ApiParser::parse_file(path)?];
+    load_imports(&mut data, |path| ApiParser::parse_file(path))?;
+    resolver::resolve(&mut data);
+    Ok(data.swap_remove(0))
 }
 
-/// Given a path load all the files and parse them.
-pub fn parse_files<P: AsRef<Path>>(path: P, print_process: bool) -> Result<Vec<ApiDef>> {
-    let wd = WalkDir::new(path);
+/// Parses the files imported by `api_defs` (and the files they import) that hasn't been loaded
+/// yet and adds them to `api_defs`. Imports of files that doesn't exist are skipped and reported
+/// by `validate`.
+fn load_imports<F>(api_defs: &mut Vec<ApiDef>, mut parse: F) -> Result<()>
+where
+    F: FnMut(&Path) -> Result<ApiDef>,
+{
+    let mut index = 0;
+
+    while index < api_defs.len() {
+        let api_def = &api_defs[index];
+        let paths = api_def
+            .imports
+            .iter()
+            .filter(|import| api_def.find_import(import, api_defs).is_none())
+            .map(|import| api_def.import_path(import))
+            .collect::<Vec<_>>();
+
+        for path in paths {
+            let loaded = api_defs
+                .iter()
+                .any(|a| Path::new(&a.filename) == path.as_path());
+
+            if !loaded && path.is_file() {
+                api_defs.push(parse(&path)?);
+            }
+        }
 
-    let files = wd
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Returns all the def files in path (recursively) sorted by name
+fn collect_files<P: AsRef<Path>>(path: P) -> Vec<walkdir::DirEntry> {
+    let mut files = WalkDir::new(path)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().metadata().unwrap().is_file())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "def"))
         .collect::<Vec<_>>();
 
-    let api_defs = RwLock::new(Vec::with_capacity(files.len()));
+    files.sort_by(|a, b| a.path().cmp(b.path()));
+    files
+}
+
+/// Given a path load all the def files and parse them. If any of the files fails to parse the
+/// first error (in filename order) is returned.
+pub fn parse_files<P: AsRef<Path>>(path: P, print_process: bool) -> Result<Vec<ApiDef>> {
+    let files = collect_files(path);
 
     // Pass 1: Parse all the files
 
-    files.par_iter().for_each(|f| {
-        if print_process {
-            println!("Parsing file {:?}", f.path());
-        }
+    let mut data = files
+        .par_iter()
+        .map(|f| {
+            if print_process {
+                println!("Parsing file {:?}", f.path());
+            }
 
-        let api_def = ApiParser::parse_file(f.path()).unwrap();
+            ApiParser::parse_file(f.path())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
 
-        // Insert the api_def for later usage
-        {
-            let mut data = api_defs.write().unwrap();
-            data.push(api_def);
+    load_imports(&mut data, |path| {
+        if print_process {
+            println!("Parsing file {:?}", path);
         }
-    });
 
-    let mut data = api_defs.into_inner().unwrap();
+        ApiParser::parse_file(path)
+    })?;
 
-    //ApiParser::second_pass(&mut data);
+    // Pass 2: Resolve types across all the files
+    resolver::resolve(&mut data);
     data.sort_by(|a, b| a.filename.cmp(&b.filename));
 
     Ok(data)
 }
 
-/// Hepler function to write C style comments
-pub fn write_c_commments<W: Write>(f: &mut W, comments: &[String], indent: usize) -> Result<()> {
-    if comments.len() == 1 && comments[0].is_empty() {
-        return Ok(());
-    }
+/// Given a path load all the def files and parse them with error recovery. All the files are
+/// parsed even if some of them has errors. Returns the (partially filled) data for all files and
+/// all the parse errors. Only failing to read a file is returned as an error.
+pub fn parse_files_with_recovery<P: AsRef<Path>>(
+    path: P,
+) -> Result<(Vec<ApiDef>, Vec<ApigenError>)> {
+    let files = collect_files(path);
+
+    let results = files
+        .par_iter()
+        .map(|f| ApiParser::parse_file_with_recovery(f.path()))
+        .collect::<Vec<_>>();
 
-    for c in comments {
-        writeln!(f, "{:indent$}// {}", "", c, indent = indent)?;
+    let mut data = Vec::with_capacity(results.len());
+    let mut errors = Vec::new();
+
+    for result in results {
+        let (api_def, file_errors) = result?;
+        data.push(api_def);
+        errors.extend(file_errors);
     }
 
-    Ok(())
+    load_imports(&mut data, |path| {
+        let (api_def, file_errors) = ApiParser::parse_file_with_recovery(path)?;
+        errors.extend(file_errors);
+        Ok(api_def)
+    })?;
+
+    resolver::resolve(&mut data);
+
+    Ok((data, errors))
+}
+
+/// Hepler function to write C style comments
+pub fn write_c_commments<W: Write>(f: &mut W, comments: &[String], indent: usize) -> Result<()> {
+    generators::util::write_comments(f, comments, generators::util::CommentStyle::C, indent)
 }
 
+/// Structs that `s` derives from. Names are looked up in the namespace of `s` first and then in
+/// the namespaces around it
 pub fn get_derived_structs<'a>(apis: &'a [ApiDef], s: &Struct) -> Vec<&'a Struct> {
     let mut structs = Vec::with_capacity(s.derives.len());
 
     for name in &s.derives {
-        for api in apis {
-            for sdef in &api.structs {
-                if sdef.name == *name {
-                    structs.push(sdef);
-                }
-            }
-        }
+        let found = (0..=s.namespace.len()).rev().find_map(|len| {
+            apis.iter()
+                .flat_map(|api| api.structs.iter())
+                .find(|sdef| sdef.name == *name && sdef.namespace == s.namespace[..len])
+        });
+
+        structs.extend(found);
     }
 
     structs
 }
 
-/// Hepler function to write C style comments
-pub fn get_c_comments(comments: &[String], indent: usize) -> String {
-    let mut output = String::with_capacity(256);
+/// Finds the def file with the qualified filename `name` (see `ApiDef::qualified_filename`)
+pub fn find_def_file<'a>(apis: &'a [ApiDef], name: &str) -> Option<&'a ApiDef> {
+    apis.iter().find(|api| api.qualified_filename() == name)
+}
 
-    for (i, c) in comments.iter().enumerate() {
-        if i > 0 {
-            output.push('\n');
-        }
-        output.push_str(&format!("{:indent$}// {}", "", c, indent = indent));
-    }
+/// Finds the def file that defines the const `name` as it's used from `namespace`. The const is
+/// looked up in `namespace` and then in each of the namespaces around it
+pub fn find_const_def<'a>(
+    apis: &'a [ApiDef],
+    namespace: &[String],
+    name: &str,
+) -> Option<&'a ApiDef> {
+    (0..=namespace.len()).rev().find_map(|len| {
+        apis.iter().find(|api| {
+            api.namespace == namespace[..len] && api.consts.iter().any(|c| c.name == name)
+        })
+    })
+}
 
-    output
+/// Hepler function to write C style comments
+pub fn get_c_comments(comments: &[String], indent: usize) -> String {
+    generators::util::format_comments(comments, generators::util::CommentStyle::C, indent)
 }
 
 /// Hepler function to write C style comments
 pub fn get_rust_comments(comments: &[String], indent: usize) -> String {
-    let mut output = String::with_capacity(256);
-
-    for (i, c) in comments.iter().enumerate() {
-        if i > 0 {
-            output.push('\n');
-        }
-        output.push_str(&format!("{:indent$}/// {}", "", c, indent = indent));
-    }
-
-    output
+    generators::util::format_comments(comments, generators::util::CommentStyle::Rust, indent)
 }
@@ -0,0 +1,1001 @@
+//! Language server for def files (used by the `apigen-lsp` binary).
+//!
+//! All `.def` files in the workspace are loaded when the server starts and the files that are
+//! open in the editor replaces the ones on disk. Everything is parsed (with error recovery),
+//! resolved and validated again on each change, so diagnostics, definitions and completions are
+//! always for the whole workspace.
+//!
+//! Type and const names in the source are found with the syntax tree (see [`Cst`]), so renaming
+//! only touches names in type positions. Files that doesn't parse have no tree and are skipped
+//! when renaming.
+use crate::api_parser::{
+    ApiDef, ApiParser, ApigenError, FunctionType, Result, Rule, Span, PRMITIVE_TYPES,
+};
+use crate::cst::{Cst, NodeKind, SyntaxNode};
+use crate::resolver::{self, TypeKind, TypeTable};
+use crate::validate::{self, Severity};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Rename, Request as LspRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind, OneOf,
+    Position, PublishDiagnosticsParams, RenameParams, ServerCapabilities, SymbolKind,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Runs the server on `connection` until the client asks it to shut down
+pub fn run(connection: &Connection) -> Result<()> {
+    let capabilities = serde_json::to_value(capabilities()).map_err(lsp_error)?;
+    let params = connection.initialize(capabilities).map_err(lsp_error)?;
+    let params: InitializeParams = serde_json::from_value(params).map_err(lsp_error)?;
+
+    let mut server = Server::new(workspace_root(&params).as_deref());
+    server.publish_diagnostics(connection)?;
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request).map_err(lsp_error)? {
+                    return Ok(());
+                }
+
+                send(connection, server.handle_request(&request))?;
+            }
+            // Notifications has no response so errors are only logged (stdout is used by the
+            // connection)
+            Message::Notification(notification) => match server.handle_notification(notification) {
+                Ok(true) => server.publish_diagnostics(connection)?,
+                Ok(false) => (),
+                Err(e) => eprintln!("apigen-lsp: {}", e),
+            },
+            Message::Response(_) => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(Default::default()),
+        rename_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+#[allow(deprecated)]
+fn workspace_root(params: &InitializeParams) -> Option<PathBuf> {
+    params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .map(|folder| &folder.uri)
+        .or(params.root_uri.as_ref())
+        .and_then(|uri| uri.to_file_path().ok())
+}
+
+fn lsp_error<E: Display>(e: E) -> ApigenError {
+    ApigenError::Lsp(e.to_string())
+}
+
+fn send<M: Into<Message>>(connection: &Connection, message: M) -> Result<()> {
+    connection.sender.send(message.into()).map_err(lsp_error)
+}
+
+/// A def file in the workspace
+struct Document {
+    uri: Url,
+    text: String,
+    /// Syntax tree of the file. `None` if it has parse errors
+    cst: Option<Cst>,
+    /// Parse errors
+    errors: Vec<ApigenError>,
+}
+
+/// A type or const defined in the workspace
+struct Definition<'a> {
+    /// Index of the document
+    doc: usize,
+    /// Keyword used to declare it (such as `struct`)
+    kind: &'static str,
+    name: &'a str,
+    /// Where it's defined and the name at that location. Differs from `name` for `enum_flags`
+    /// names that are defined by the enum
+    span: &'a Span,
+    span_name: &'a str,
+    doc_comments: &'a [String],
+}
+
+struct Server {
+    documents: Vec<Document>,
+    /// Resolved data for each document (same order as `documents`)
+    api_defs: Vec<ApiDef>,
+    /// Documents that has been removed since the diagnostics were published
+    removed: Vec<Url>,
+}
+
+impl Server {
+    /// Creates the server with all def files in `root`
+    fn new(root: Option<&Path>) -> Server {
+        let mut documents = Vec::new();
+
+        if let Some(root) = root {
+            let mut files = WalkDir::new(root)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "def"))
+                .collect::<Vec<_>>();
+            files.sort_by(|a, b| a.path().cmp(b.path()));
+
+            for file in files {
+                let text = std::fs::read_to_string(file.path());
+
+                if let (Ok(text), Ok(uri)) = (text, Url::from_file_path(file.path())) {
+                    documents.push(Document::new(uri, text));
+                }
+            }
+        }
+
+        let mut server = Server {
+            documents,
+            api_defs: Vec::new(),
+            removed: Vec::new(),
+        };
+
+        server.update();
+        server
+    }
+
+    /// Parses and resolves all documents
+    fn update(&mut self) {
+        let mut api_defs = Vec::with_capacity(self.documents.len());
+
+        for doc in &mut self.documents {
+            let filename = file_name(&doc.uri);
+            let (api_def, errors) = ApiParser::parse_string_with_recovery(&doc.text, &filename);
+
+            doc.cst = Cst::parse(&doc.text, &filename).ok();
+            doc.errors = errors;
+            api_defs.push(api_def);
+        }
+
+        resolver::resolve(&mut api_defs);
+        self.api_defs = api_defs;
+    }
+
+    fn document(&self, uri: &Url) -> Result<usize> {
+        self.documents
+            .iter()
+            .position(|doc| doc.uri == *uri)
+            .ok_or_else(|| ApigenError::Lsp(format!("unknown document {}", uri)))
+    }
+
+    /// Returns true if the documents has changed
+    fn handle_notification(&mut self, notification: Notification) -> Result<bool> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: <DidOpenTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params).map_err(lsp_error)?;
+                let uri = params.text_document.uri;
+
+                match self.document(&uri) {
+                    Ok(index) => self.documents[index].text = params.text_document.text,
+                    Err(_) => self
+                        .documents
+                        .push(Document::new(uri, params.text_document.text)),
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: <DidChangeTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params).map_err(lsp_error)?;
+                let index = self.document(&params.text_document.uri)?;
+
+                // Only full syncs are used so the last change has the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents[index].text = change.text;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: <DidCloseTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params).map_err(lsp_error)?;
+                let index = self.document(&params.text_document.uri)?;
+
+                // Go back to the file on disk. Files that doesn't exist are removed
+                let path = params.text_document.uri.to_file_path().ok();
+
+                match path.and_then(|p| std::fs::read_to_string(p).ok()) {
+                    Some(text) => self.documents[index].text = text,
+                    None => {
+                        let doc = self.documents.remove(index);
+                        self.removed.push(doc.uri);
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+
+        self.update();
+        Ok(true)
+    }
+
+    fn handle_request(&self, request: &Request) -> Response {
+        self.dispatch::<GotoDefinition>(request, Server::definition)
+            .or_else(|| self.dispatch::<HoverRequest>(request, Server::hover))
+            .or_else(|| self.dispatch::<DocumentSymbolRequest>(request, Server::symbols))
+            .or_else(|| self.dispatch::<Completion>(request, Server::completion))
+            .or_else(|| self.dispatch::<Rename>(request, Server::rename))
+            .unwrap_or_else(|| {
+                Response::new_err(
+                    request.id.clone(),
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request `{}`", request.method),
+                )
+            })
+    }
+
+    /// Calls `f` if `request` is a `R` request
+    fn dispatch<R: LspRequest>(
+        &self,
+        request: &Request,
+        f: fn(&Server, R::Params) -> Result<R::Result>,
+    ) -> Option<Response> {
+        if request.method != R::METHOD {
+            return None;
+        }
+
+        let id = request.id.clone();
+
+        let response = match serde_json::from_value(request.params.clone()) {
+            Ok(params) => match f(self, params) {
+                Ok(result) => Response::new_ok(id, result),
+                Err(e) => Response::new_err(id, ErrorCode::RequestFailed as i32, e.to_string()),
+            },
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        };
+
+        Some(response)
+    }
+
+    fn publish_diagnostics(&mut self, connection: &Connection) -> Result<()> {
+        let mut diagnostics = vec![Vec::new(); self.documents.len()];
+
+        for (doc, out) in self.documents.iter().zip(diagnostics.iter_mut()) {
+            for e in &doc.errors {
+                if let ApigenError::Parse {
+                    line,
+                    column,
+                    message,
+                    ..
+                } = e
+                {
+                    let offset = line_col_offset(&doc.text, *line, *column);
+                    out.push(diagnostic(doc, offset, Severity::Error, message));
+                }
+            }
+        }
+
+        for d in validate::validate(&self.api_defs) {
+            let index = self.api_defs.iter().position(|a| a.filename == d.filename);
+
+            if let Some(index) = index {
+                let doc = &self.documents[index];
+                let offset = line_col_offset(&doc.text, d.line, d.column);
+                diagnostics[index].push(diagnostic(doc, offset, d.severity, &d.message));
+            }
+        }
+
+        // Clear the diagnostics for removed documents
+        let removed = self.removed.drain(..).map(|uri| (uri, Vec::new()));
+        let uris = self.documents.iter().map(|doc| doc.uri.clone());
+
+        for (uri, diagnostics) in removed.chain(uris.zip(diagnostics)) {
+            let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+            send(
+                connection,
+                Notification::new(PublishDiagnostics::METHOD.to_owned(), params),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// All types and consts in the workspace
+    fn definitions(&self) -> Vec<Definition<'_>> {
+        let mut defs = Vec::new();
+
+        for (doc, api_def) in self.api_defs.iter().enumerate() {
+            let mut add = |kind, name, span, span_name, doc_comments| {
+                defs.push(Definition::new(
+                    doc,
+                    kind,
+                    name,
+                    span,
+                    span_name,
+                    doc_comments,
+                ))
+            };
+
+            for s in &api_def.structs {
+                add("struct", &s.name, &s.span, &s.name, &s.doc_comments);
+            }
+
+            for u in &api_def.unions {
+                add("union", &u.name, &u.span, &u.name, &u.doc_comments);
+            }
+
+            for e in &api_def.enums {
+                add("enum", &e.name, &e.span, &e.name, &e.doc_comments);
+
+                if !e.flags_name.is_empty() {
+                    add(
+                        "enum_flags",
+                        &e.flags_name,
+                        &e.span,
+                        &e.name,
+                        &e.doc_comments,
+                    );
+                }
+            }
+
+            for t in &api_def.types {
+                add("type", &t.var.name, &t.span, &t.var.name, &t.doc_comments);
+            }
+
+            for c in &api_def.callbacks {
+                add("callback", &c.name, &c.span, &c.name, &c.doc_comments);
+            }
+
+            for c in &api_def.consts {
+                add("const", &c.name, &c.span, &c.name, &c.doc_comments);
+            }
+        }
+
+        defs
+    }
+
+    fn find_definition(&self, name: &str) -> Option<Definition<'_>> {
+        self.definitions().into_iter().find(|d| d.name == name)
+    }
+
+    /// Type or const name at `position` in a document
+    fn symbol_at(&self, uri: &Url, position: Position) -> Result<Option<Symbol>> {
+        let doc = &self.documents[self.document(uri)?];
+        let offset = offset(&doc.text, position);
+
+        Ok(doc.cst.as_ref().and_then(|cst| {
+            symbols(cst)
+                .into_iter()
+                .find(|s| s.range.start <= offset && offset <= s.range.end)
+        }))
+    }
+
+    fn definition_location(&self, def: &Definition) -> Location {
+        let doc = &self.documents[def.doc];
+        let start = line_col_offset(&doc.text, def.span.line, def.span.column);
+        let range = range(&doc.text, start..start + def.span_name.len());
+        Location::new(doc.uri.clone(), range)
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let position = params.text_document_position_params;
+        let symbol = self.symbol_at(&position.text_document.uri, position.position)?;
+
+        Ok(symbol
+            .and_then(|s| self.find_definition(&s.name))
+            .map(|def| GotoDefinitionResponse::Scalar(self.definition_location(&def))))
+    }
+
+    fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let position = params.text_document_position_params;
+        let Some(symbol) = self.symbol_at(&position.text_document.uri, position.position)? else {
+            return Ok(None);
+        };
+
+        let Some(def) = self.find_definition(&symbol.name) else {
+            return Ok(None);
+        };
+
+        let mut value = format!(
+            "```\n{} {}\n```\n\nDefined in `{}`",
+            def.kind, def.name, self.api_defs[def.doc].filename
+        );
+
+        if !def.doc_comments.is_empty() {
+            value.push_str("\n\n---\n\n");
+            value.push_str(&def.doc_comments.join("\n"));
+        }
+
+        let doc = &self.documents[self.document(&position.text_document.uri)?];
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range(&doc.text, symbol.range)),
+        }))
+    }
+
+    fn symbols(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let index = self.document(&params.text_document.uri)?;
+        let text = &self.documents[index].text;
+        let api_def = &self.api_defs[index];
+        let mut symbols = Vec::new();
+
+        for s in api_def.structs.iter().chain(api_def.unions.iter()) {
+            let fields = s
+                .variables
+                .iter()
+                .map(|v| symbol(text, &v.name, SymbolKind::FIELD, &v.span, Vec::new()));
+
+            let functions = s.functions.iter().map(|f| {
+                let kind = match f.func_type {
+                    FunctionType::Static => SymbolKind::FUNCTION,
+                    _ => SymbolKind::METHOD,
+                };
+
+                symbol(text, &f.name, kind, &f.span, Vec::new())
+            });
+
+            let children = fields.chain(functions).collect();
+            symbols.push(symbol(text, &s.name, SymbolKind::STRUCT, &s.span, children));
+        }
+
+        for e in &api_def.enums {
+            let entries = e
+                .entries
+                .iter()
+                .map(|entry| {
+                    symbol(
+                        text,
+                        &entry.name,
+                        SymbolKind::ENUM_MEMBER,
+                        &entry.span,
+                        Vec::new(),
+                    )
+                })
+                .collect();
+
+            symbols.push(symbol(text, &e.name, SymbolKind::ENUM, &e.span, entries));
+        }
+
+        for c in &api_def.callbacks {
+            symbols.push(symbol(
+                text,
+                &c.name,
+                SymbolKind::FUNCTION,
+                &c.span,
+                Vec::new(),
+            ));
+        }
+
+        for t in &api_def.types {
+            let kind = SymbolKind::TYPE_PARAMETER;
+            symbols.push(symbol(text, &t.var.name, kind, &t.span, Vec::new()));
+        }
+
+        for c in &api_def.consts {
+            symbols.push(symbol(
+                text,
+                &c.name,
+                SymbolKind::CONSTANT,
+                &c.span,
+                Vec::new(),
+            ));
+        }
+
+        symbols.sort_by_key(|s| (s.range.start.line, s.range.start.character));
+
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    fn completion(&self, _params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let table = TypeTable::new(&self.api_defs);
+
+        let builtins = PRMITIVE_TYPES
+            .iter()
+            .chain(&["String"])
+            .map(|name| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..Default::default()
+            });
+
+        let types = table.iter().map(|(name, info)| {
+            let kind = match info.kind {
+                TypeKind::Struct | TypeKind::Handle | TypeKind::Union => CompletionItemKind::STRUCT,
+                TypeKind::Enum(_) => CompletionItemKind::ENUM,
+                TypeKind::Alias => CompletionItemKind::TYPE_PARAMETER,
+                TypeKind::Callback => CompletionItemKind::FUNCTION,
+            };
+
+            CompletionItem {
+                label: name.to_owned(),
+                kind: Some(kind),
+                detail: Some(format!("{}.def", info.def_file)),
+                ..Default::default()
+            }
+        });
+
+        let consts = self.api_defs.iter().flat_map(|api_def| {
+            api_def.consts.iter().map(|c| CompletionItem {
+                label: c.name.to_owned(),
+                kind: Some(CompletionItemKind::CONSTANT),
+                detail: Some(format!("{}.def", api_def.base_filename)),
+                ..Default::default()
+            })
+        });
+
+        let mut items = builtins.chain(types).chain(consts).collect::<Vec<_>>();
+        items.sort_by(|a, b| a.label.cmp(&b.label));
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        let new_name = params.new_name;
+
+        let Some(symbol) = self.symbol_at(&position.text_document.uri, position.position)? else {
+            return Err(ApigenError::Lsp(
+                "only types and consts can be renamed".to_owned(),
+            ));
+        };
+
+        if self.find_definition(&symbol.name).is_none() {
+            return Err(ApigenError::Lsp(format!(
+                "`{}` isn't defined in the workspace",
+                symbol.name
+            )));
+        }
+
+        if !is_identifier(&new_name) || PRMITIVE_TYPES.contains(&new_name.as_str()) {
+            return Err(ApigenError::Lsp(format!(
+                "`{}` isn't a valid name",
+                new_name
+            )));
+        }
+
+        let mut changes = HashMap::new();
+
+        for doc in &self.documents {
+            let Some(cst) = &doc.cst else {
+                continue;
+            };
+
+            let edits = symbols(cst)
+                .into_iter()
+                .filter(|s| s.name == symbol.name)
+                .map(|s| TextEdit::new(range(&doc.text, s.range), new_name.clone()))
+                .collect::<Vec<_>>();
+
+            if !edits.is_empty() {
+                changes.insert(doc.uri.clone(), edits);
+            }
+        }
+
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+}
+
+impl<'a> Definition<'a> {
+    fn new(
+        doc: usize,
+        kind: &'static str,
+        name: &'a str,
+        span: &'a Span,
+        span_name: &'a str,
+        doc_comments: &'a [String],
+    ) -> Definition<'a> {
+        Definition {
+            doc,
+            kind,
+            name,
+            span,
+            span_name,
+            doc_comments,
+        }
+    }
+}
+
+impl Document {
+    fn new(uri: Url, text: String) -> Document {
+        Document {
+            uri,
+            text,
+            cst: None,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Name used for the document when parsing. This is the path for files
+fn file_name(uri: &Url) -> String {
+    match uri.to_file_path() {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(_) => uri.to_string(),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A type or const name in the source
+struct Symbol {
+    name: String,
+    range: Range<usize>,
+}
+
+/// All the places where types and consts are defined or used in the tree
+fn symbols(cst: &Cst) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    collect_symbols(cst.root(), &mut Vec::new(), &mut symbols);
+    symbols
+}
+
+fn collect_symbols(node: &SyntaxNode, parents: &mut Vec<Rule>, out: &mut Vec<Symbol>) {
+    for child in node.child_nodes() {
+        let NodeKind::Rule(rule) = child.kind else {
+            continue;
+        };
+
+        if is_symbol(rule, parents) {
+            let text = child.text();
+
+            // Only the last part of a qualified name (`render::Texture`) is the type name
+            let start = text.rfind("::").map_or(0, |i| i + 2);
+            let name = &text[start..];
+
+            // Array sizes can be numbers as well
+            if !name.starts_with(|c: char| c.is_ascii_digit()) {
+                out.push(Symbol {
+                    name: name.to_owned(),
+                    range: child.range.start + start..child.range.end,
+                });
+            }
+        } else {
+            parents.push(rule);
+            collect_symbols(child, parents, out);
+            parents.pop();
+        }
+    }
+}
+
+/// Checks if a node for `rule` with the `parents` rules is a type or const name
+fn is_symbol(rule: Rule, parents: &[Rule]) -> bool {
+    const DEFINITIONS: &[Rule] = &[
+        Rule::structdef,
+        Rule::uniondef,
+        Rule::enumdef,
+        Rule::enum_flags,
+        Rule::const_value,
+    ];
+
+    match (rule, parents) {
+        (Rule::vtype, _) => true,
+        (Rule::name_or_num, [.., Rule::array_size]) => true,
+        (Rule::name, [.., parent]) if DEFINITIONS.contains(parent) => true,
+        (Rule::name, [.., Rule::type_value, Rule::var]) => true,
+        (Rule::name, [.., Rule::callbackdef, Rule::function]) => true,
+        (Rule::name, [.., Rule::derive, Rule::namelist]) => true,
+        _ => false,
+    }
+}
+
+/// Symbol for the outline. The selection is the name at the line and column of the span
+#[allow(deprecated)]
+fn symbol(
+    text: &str,
+    name: &str,
+    kind: SymbolKind,
+    span: &Span,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    let start = line_col_offset(text, span.line, span.column);
+    let selection_range = range(text, start..start + name.len());
+
+    // Elements that aren't parsed from the source (such as the implicit `self`) has no range
+    let range = if span.byte_range.is_empty() {
+        selection_range
+    } else {
+        range(text, span.byte_range.clone())
+    };
+
+    DocumentSymbol {
+        name: name.to_owned(),
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range,
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+fn diagnostic(doc: &Document, offset: usize, severity: Severity, message: &str) -> Diagnostic {
+    // Underline the word at the location
+    let len = doc.text[offset..]
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(doc.text.len() - offset);
+
+    let severity = match severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+
+    Diagnostic {
+        range: range(
+            &doc.text,
+            offset..offset + len.max(1).min(doc.text.len() - offset),
+        ),
+        severity: Some(severity),
+        source: Some("apigen".to_owned()),
+        message: message.to_owned(),
+        ..Default::default()
+    }
+}
+
+/// Byte offset of a 1 based line and column (in chars) as used by `Span` and the errors
+fn line_col_offset(text: &str, line: usize, column: usize) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+
+    text[line_start..]
+        .char_indices()
+        .take_while(|(_, c)| *c != '\n')
+        .nth(column.saturating_sub(1))
+        .map_or_else(
+            || {
+                line_start
+                    + text[line_start..]
+                        .find('\n')
+                        .unwrap_or(text.len() - line_start)
+            },
+            |(i, _)| line_start + i,
+        )
+}
+
+/// LSP position (0 based line and column in UTF-16 code units) of a byte offset
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// Byte offset of a LSP position. Positions past the end of a line are moved to the end
+fn offset(text: &str, position: Position) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum::<usize>();
+
+    let mut units = 0;
+
+    for (i, c) in text[line_start..].char_indices() {
+        if c == '\n' || units >= position.character as usize {
+            return line_start + i;
+        }
+
+        units += c.len_utf16();
+    }
+
+    text.len()
+}
+
+fn range(text: &str, range: Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(position(text, range.start), position(text, range.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::RequestId;
+    use lsp_types::notification::{Exit, Initialized};
+    use lsp_types::request::{Initialize, Shutdown};
+    use lsp_types::{
+        DidOpenTextDocumentParams, InitializedParams, PartialResultParams, TextDocumentIdentifier,
+        TextDocumentItem, TextDocumentPositionParams, WorkDoneProgressParams,
+    };
+    use serde_json::Value;
+
+    const IMAGE: &str =
+        "import \"info.def\"\n/// An image\n#[attributes(Handle)]\nstruct Image {\n    get_info() -> *const Info,\n}\n";
+    const INFO: &str = "struct Info {\n    width: u32,\n    extra: Unknown,\n}\n";
+
+    struct Client {
+        connection: Connection,
+        next_id: i32,
+        diagnostics: HashMap<Url, Vec<Diagnostic>>,
+    }
+
+    impl Client {
+        fn request<R: LspRequest>(&mut self, params: R::Params) -> Response {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            let request = Request::new(id.clone(), R::METHOD.to_owned(), params);
+            self.connection.sender.send(request.into()).unwrap();
+
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) if response.id == id => return response,
+                    Message::Notification(n) => self.notification(n),
+                    _ => (),
+                }
+            }
+        }
+
+        fn result<R: LspRequest>(&mut self, params: R::Params) -> R::Result {
+            let response = self.request::<R>(params);
+            serde_json::from_value(response.result.unwrap_or(Value::Null)).unwrap()
+        }
+
+        fn notify<N: LspNotification>(&mut self, params: N::Params) {
+            let notification = Notification::new(N::METHOD.to_owned(), params);
+            self.connection.sender.send(notification.into()).unwrap();
+        }
+
+        fn notification(&mut self, notification: Notification) {
+            if notification.method == PublishDiagnostics::METHOD {
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(notification.params).unwrap();
+                self.diagnostics.insert(params.uri, params.diagnostics);
+            }
+        }
+
+        fn open(&mut self, uri: &Url, text: &str) {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "apigen".to_owned(),
+                    1,
+                    text.to_owned(),
+                ),
+            });
+        }
+    }
+
+    fn position(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(line, character),
+        )
+    }
+
+    #[test]
+    fn test_lsp() {
+        let (server, connection) = Connection::memory();
+        let thread = std::thread::spawn(move || run(&server));
+        let mut client = Client {
+            connection,
+            next_id: 0,
+            diagnostics: HashMap::new(),
+        };
+
+        let image = Url::parse("file:///lsp/image.def").unwrap();
+        let info = Url::parse("file:///lsp/info.def").unwrap();
+
+        client.request::<Initialize>(InitializeParams::default());
+        client.notify::<Initialized>(InitializedParams {});
+        client.open(&image, IMAGE);
+        client.open(&info, INFO);
+
+        let work_done = WorkDoneProgressParams::default;
+        let partial = PartialResultParams::default;
+
+        // Go to the definition in the other file
+        let definition = client.result::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: position(&image, 4, 27),
+            work_done_progress_params: work_done(),
+            partial_result_params: partial(),
+        });
+        let expected = Location::new(
+            info.clone(),
+            lsp_types::Range::new(Position::new(0, 7), Position::new(0, 11)),
+        );
+        assert_eq!(definition, Some(GotoDefinitionResponse::Scalar(expected)));
+
+        let hover = client.result::<HoverRequest>(HoverParams {
+            text_document_position_params: position(&image, 3, 9),
+            work_done_progress_params: work_done(),
+        });
+        match hover.unwrap().contents {
+            HoverContents::Markup(markup) => {
+                assert!(markup.value.contains("struct Image"));
+                assert!(markup.value.contains("An image"));
+            }
+            contents => panic!("unexpected hover {:?}", contents),
+        }
+
+        let symbols = client.result::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(image.clone()),
+            work_done_progress_params: work_done(),
+            partial_result_params: partial(),
+        });
+        match symbols.unwrap() {
+            DocumentSymbolResponse::Nested(symbols) => {
+                assert_eq!(symbols.len(), 1);
+                assert_eq!(symbols[0].name, "Image");
+                assert_eq!(symbols[0].range.start, Position::new(2, 0));
+                assert_eq!(symbols[0].children.as_ref().unwrap()[0].name, "get_info");
+            }
+            symbols => panic!("unexpected symbols {:?}", symbols),
+        }
+
+        let completion = client.result::<Completion>(CompletionParams {
+            text_document_position: position(&info, 1, 11),
+            work_done_progress_params: work_done(),
+            partial_result_params: partial(),
+            context: None,
+        });
+        let labels = match completion.unwrap() {
+            CompletionResponse::Array(items) => items.into_iter().map(|i| i.label).collect(),
+            CompletionResponse::List(list) => {
+                list.items.into_iter().map(|i| i.label).collect::<Vec<_>>()
+            }
+        };
+        for label in ["u32", "String", "Image", "Info"] {
+            assert!(labels.iter().any(|l| l == label), "missing {}", label);
+        }
+
+        // Rename from the definition updates the use in the other file
+        let edit = client.result::<Rename>(RenameParams {
+            text_document_position: position(&info, 0, 8),
+            new_name: "ImageInfo".to_owned(),
+            work_done_progress_params: work_done(),
+        });
+        let changes = edit.unwrap().changes.unwrap();
+        assert_eq!(changes[&info].len(), 1);
+        assert_eq!(changes[&image].len(), 1);
+        assert_eq!(changes[&image][0].range.start, Position::new(4, 25));
+
+        let response = client.request::<Rename>(RenameParams {
+            text_document_position: position(&info, 1, 5),
+            new_name: "Foo".to_owned(),
+            work_done_progress_params: work_done(),
+        });
+        assert!(response.error.is_some());
+
+        let errors = &client.diagnostics[&info];
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("Unknown"));
+        assert_eq!(errors[0].range.start.line, 2);
+        assert!(client.diagnostics[&image].is_empty());
+
+        client.request::<Shutdown>(());
+        client.notify::<Exit>(());
+        thread.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,460 @@
+//! Whole-program type resolution. After all files has been parsed the resolver walks every
+//! variable and figures out what kind of type it refers to and which def file that defines it.
+//!
+//! Types are looked up by their qualified name (`render::Texture`). Names are first looked up in
+//! the namespace of the variable and then in each of the namespaces around it, so `gl::Texture`
+//! used in `render` is `render::gl::Texture` if it exists and `gl::Texture` otherwise.
+//!
+//! Imports brings names into scope. A name imported with `use` is found without its namespace
+//! (`use gl::Texture` makes `Texture` refer to the type in `gl.def` whatever namespace that file is
+//! in) and takes precedence over the lookup above. Names that still can't be found are looked up
+//! in the namespaces of the files imported with `import`.
+use crate::api_parser::{ApiDef, EnumType, VariableType};
+use std::collections::HashMap;
+
+///
+/// Kind of a user defined type
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeKind {
+    /// Declared with `struct`
+    Struct,
+    /// Declared with `struct` and has the `Handle` attribute
+    Handle,
+    /// Declared with `union`
+    Union,
+    /// Declared with `enum` (or the name given with `enum_flags`)
+    Enum(EnumType),
+    /// Declared with `type`
+    Alias,
+    /// Declared with `callback`
+    Callback,
+}
+
+impl TypeKind {
+    /// Returns the variable type used for variables of this kind
+    pub fn variable_type(self) -> VariableType {
+        match self {
+            TypeKind::Struct | TypeKind::Union => VariableType::Regular,
+            TypeKind::Handle => VariableType::Handle,
+            TypeKind::Enum(_) => VariableType::Enum,
+            TypeKind::Alias => VariableType::Alias,
+            TypeKind::Callback => VariableType::Callback,
+        }
+    }
+}
+
+/// Info about a user defined type
+#[derive(Debug, Clone)]
+pub struct TypeInfo {
+    /// What kind of type this is
+    pub kind: TypeKind,
+    /// Base filename of the def file the type is defined in
+    pub def_file: String,
+    /// Namespace of the def file the type is defined in
+    pub namespace: Vec<String>,
+}
+
+/// Lookup table of all the types defined in a set of def files. The names are qualified with the
+/// namespace they are in
+#[derive(Debug, Default)]
+pub struct TypeTable {
+    types: HashMap<String, TypeInfo>,
+}
+
+impl TypeTable {
+    /// Builds the table for all the types in `api_defs`. If a type is defined more than once the
+    /// first definition is used.
+    pub fn new(api_defs: &[ApiDef]) -> TypeTable {
+        let mut table = TypeTable::default();
+
+        for api_def in api_defs {
+            let def_file = &api_def.qualified_filename();
+            let namespace = &api_def.namespace;
+
+            for s in &api_def.structs {
+                let kind = if s.has_attribute("Handle") {
+                    TypeKind::Handle
+                } else {
+                    TypeKind::Struct
+                };
+
+                table.insert(namespace, &s.name, kind, def_file);
+            }
+
+            for u in &api_def.unions {
+                table.insert(namespace, &u.name, TypeKind::Union, def_file);
+            }
+
+            for e in &api_def.enums {
+                table.insert(namespace, &e.name, TypeKind::Enum(e.enum_type), def_file);
+
+                if !e.flags_name.is_empty() {
+                    table.insert(
+                        namespace,
+                        &e.flags_name,
+                        TypeKind::Enum(EnumType::Bitflags),
+                        def_file,
+                    );
+                }
+            }
+
+            for t in &api_def.types {
+                table.insert(namespace, &t.var.name, TypeKind::Alias, def_file);
+            }
+
+            for c in &api_def.callbacks {
+                table.insert(namespace, &c.name, TypeKind::Callback, def_file);
+            }
+        }
+
+        table
+    }
+
+    fn insert(&mut self, namespace: &[String], name: &str, kind: TypeKind, def_file: &str) {
+        self.types
+            .entry(qualified_name(namespace, name))
+            .or_insert_with(|| TypeInfo {
+                kind,
+                def_file: def_file.to_owned(),
+                namespace: namespace.to_owned(),
+            });
+    }
+
+    /// Get the info for a qualified type name (such as `Image` or `render::Texture`)
+    pub fn get(&self, name: &str) -> Option<&TypeInfo> {
+        self.types.get(name)
+    }
+
+    /// Looks up `name` (qualified with `namespace`) as it's used from the namespace `scope`. The
+    /// name is looked up in `scope` and then in each of the namespaces around it.
+    pub fn lookup(&self, scope: &[String], namespace: &[String], name: &str) -> Option<&TypeInfo> {
+        (0..=scope.len()).rev().find_map(|len| {
+            let path = [&scope[..len], namespace].concat();
+            self.get(&qualified_name(&path, name))
+        })
+    }
+
+    /// Looks up a type used from the namespace `scope` in a file with `imports`. See the module
+    /// documentation for the order names are looked up in
+    pub fn lookup_imported(
+        &self,
+        scope: &[String],
+        imports: &Imports,
+        namespace: &[String],
+        name: &str,
+    ) -> Option<&TypeInfo> {
+        if namespace.is_empty() {
+            if let Some(info) = imports
+                .names
+                .get(name)
+                .and_then(|(qualified, _)| self.get(qualified))
+            {
+                return Some(info);
+            }
+        }
+
+        self.lookup(scope, namespace, name).or_else(|| {
+            imports.files.iter().find_map(|(file_namespace, file)| {
+                let path = [&file_namespace[..], namespace].concat();
+                self.get(&qualified_name(&path, name))
+                    .filter(|info| &info.def_file == file)
+            })
+        })
+    }
+
+    /// Checks if a type with the name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.types.contains_key(name)
+    }
+
+    /// Iterate over all the types in the table
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TypeInfo)> {
+        self.types.iter().map(|(name, info)| (name.as_str(), info))
+    }
+}
+
+/// Names a def file brings into scope with its imports
+#[derive(Debug, Default, Clone)]
+pub struct Imports {
+    /// Names imported with `use` and the qualified name and base filename of the file they refer
+    /// to
+    names: HashMap<String, (String, String)>,
+    /// Namespaces and base filenames of the files imported with `import`
+    files: Vec<(Vec<String>, String)>,
+}
+
+impl Imports {
+    /// Collects the imports of `api_def` using the namespaces the resolver has set on them.
+    /// Imports of files that isn't in `api_defs` are left out
+    pub fn new(api_def: &ApiDef, api_defs: &[ApiDef]) -> Imports {
+        let mut imports = Imports::default();
+
+        for import in &api_def.imports {
+            let Some(index) = api_def.find_import(import, api_defs) else {
+                continue;
+            };
+
+            let target = &api_defs[index];
+
+            match &import.name {
+                Some(name) => {
+                    imports.names.insert(
+                        name.to_owned(),
+                        (
+                            qualified_name(&import.namespace, name),
+                            target.qualified_filename(),
+                        ),
+                    );
+                }
+                None => imports
+                    .files
+                    .push((import.namespace.clone(), target.qualified_filename())),
+            }
+        }
+
+        imports
+    }
+
+    /// Checks if `name` from the file `def_file` is imported, either by importing the file or
+    /// the name
+    pub fn contains(&self, def_file: &str, name: &str) -> bool {
+        self.files.iter().any(|(_, file)| file == def_file)
+            || self
+                .names
+                .get(name)
+                .is_some_and(|(_, file)| file == def_file)
+    }
+}
+
+/// Joins a namespace and a name to a qualified name such as `render::Texture`
+pub fn qualified_name(namespace: &[String], name: &str) -> String {
+    namespace
+        .iter()
+        .map(String::as_str)
+        .chain(std::iter::once(name))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Resolves the type of every variable (struct and union fields, function arguments, return
+/// values, callbacks and type aliases) in `api_defs`. Variables that refer to a user defined type
+/// gets `vtype` set to Enum, Regular, Handle, Alias or Callback and `type_def_file` set to the def file
+/// the type is defined in. `type_namespace` is set to the namespace of the type. Enums also gets
+/// `enum_type` setup. Types that can't be found are left as `Regular` with an empty
+/// `type_def_file`. Imports gets the namespace of the imported file.
+pub fn resolve(api_defs: &mut [ApiDef]) {
+    let table = TypeTable::new(api_defs);
+
+    let import_namespaces = api_defs
+        .iter()
+        .map(|api_def| {
+            api_def
+                .imports
+                .iter()
+                .map(|import| match api_def.find_import(import, api_defs) {
+                    Some(index) => api_defs[index].namespace.clone(),
+                    None => Vec::new(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    for (api_def, namespaces) in api_defs.iter_mut().zip(import_namespaces) {
+        for (import, namespace) in api_def.imports.iter_mut().zip(namespaces) {
+            import.namespace = namespace;
+        }
+    }
+
+    let imports = api_defs
+        .iter()
+        .map(|api_def| Imports::new(api_def, api_defs))
+        .collect::<Vec<_>>();
+
+    for (api_def, imports) in api_defs.iter_mut().zip(&imports) {
+        api_def.visit_variables_mut(|var| {
+            match var.vtype {
+                VariableType::SelfType | VariableType::Str | VariableType::Primitive => return,
+                _ => (),
+            }
+
+            match table.lookup_imported(
+                &var.namespace,
+                imports,
+                &var.type_namespace,
+                &var.type_name,
+            ) {
+                Some(info) => {
+                    var.vtype = info.kind.variable_type();
+                    var.type_def_file = info.def_file.to_owned();
+                    var.type_namespace = info.namespace.to_owned();
+
+                    if let TypeKind::Enum(enum_type) = info.kind {
+                        var.enum_type = enum_type;
+                    }
+                }
+
+                None => {
+                    var.vtype = VariableType::Regular;
+                    var.type_def_file.clear();
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+
+    fn parse(data: &[(&str, &str)]) -> Vec<ApiDef> {
+        let mut defs: Vec<ApiDef> = data
+            .iter()
+            .map(|(name, code)| ApiParser::parse_string(code, name).unwrap())
+            .collect();
+        resolve(&mut defs);
+        defs
+    }
+
+    #[test]
+    fn test_resolve_across_files() {
+        let defs = parse(&[
+            (
+                "image.def",
+                "struct Image {\n    info: ImageInfo,\n    format: Format,\n    load: LoadFunc,\n    id: Id,\n    size: u32,\n}",
+            ),
+            (
+                "types.def",
+                "struct ImageInfo { width: u32 }\nenum Format { Rgb, Rgba }\ntype Id: u64\ncallback LoadFunc(name: String) -> Image?",
+            ),
+        ]);
+
+        let vars = &defs[0].structs[0].variables;
+        assert_eq!(vars[0].vtype, VariableType::Regular);
+        assert_eq!(vars[0].type_def_file, "types");
+        assert_eq!(vars[1].vtype, VariableType::Enum);
+        assert_eq!(vars[1].enum_type, EnumType::Regular);
+        assert_eq!(vars[2].vtype, VariableType::Callback);
+        assert_eq!(vars[3].vtype, VariableType::Alias);
+        assert_eq!(vars[4].vtype, VariableType::Primitive);
+        assert!(vars[4].type_def_file.is_empty());
+
+        let callback = &defs[1].callbacks[0];
+        assert_eq!(callback.function_args.len(), 1);
+        assert_eq!(callback.function_args[0].vtype, VariableType::Str);
+        let ret = callback.return_val.as_ref().unwrap();
+        assert_eq!(ret.vtype, VariableType::Regular);
+        assert_eq!(ret.type_def_file, "image");
+    }
+
+    #[test]
+    fn test_resolve_flags_and_functions() {
+        let defs = parse(&[(
+            "flags.def",
+            "[enum_flags(Flags)]\nenum Flag { A = 1, B = 2, C = 4 }\nstruct Window {\n    set_flags(flags: Flags, flag: Flag) -> Flag,\n    destroy(),\n}",
+        )]);
+
+        let func = &defs[0].structs[0].functions[0];
+        assert_eq!(func.function_args[1].vtype, VariableType::Enum);
+        assert_eq!(func.function_args[1].enum_type, EnumType::Bitflags);
+        assert_eq!(func.function_args[2].vtype, VariableType::Enum);
+        assert_eq!(func.return_val.as_ref().unwrap().vtype, VariableType::Enum);
+
+        let destroy = &defs[0].structs[0].functions[1];
+        assert_eq!(destroy.function_args.len(), 1);
+        assert_eq!(destroy.function_args[0].vtype, VariableType::SelfType);
+    }
+
+    #[test]
+    fn test_resolve_handles() {
+        let defs = parse(&[
+            (
+                "image.def",
+                "#[attributes(Handle, Drop)]\nstruct Image { destroy() }\nstruct Info { width: u32 }",
+            ),
+            (
+                "app.def",
+                "struct App { image: Image, info: Info }\nunion U { image: *Image }",
+            ),
+        ]);
+
+        let table = TypeTable::new(&defs);
+        assert_eq!(
+            table.lookup(&[], &[], "Image").unwrap().kind,
+            TypeKind::Handle
+        );
+        assert_eq!(
+            table.lookup(&[], &[], "Info").unwrap().kind,
+            TypeKind::Struct
+        );
+
+        let vars = &defs[1].structs[0].variables;
+        assert_eq!(vars[0].vtype, VariableType::Handle);
+        assert_eq!(vars[0].type_def_file, "image");
+        assert_eq!(vars[1].vtype, VariableType::Regular);
+        assert_eq!(defs[1].unions[0].variables[0].vtype, VariableType::Handle);
+    }
+
+    #[test]
+    fn test_resolve_namespaces() {
+        let defs = parse(&[
+            (
+                "app.def",
+                "struct App { device: render::Device, tex: render::gl::Texture }",
+            ),
+            (
+                "dev.def",
+                "namespace render
+struct Device { tex: gl::Texture, info: Info }
+struct Info { id: u32 }",
+            ),
+            (
+                "gl.def",
+                "namespace render::gl
+struct Texture { info: Info }",
+            ),
+            ("info.def", "struct Info { id: u64 }"),
+        ]);
+
+        let vars = &defs[0].structs[0].variables;
+        assert_eq!(vars[0].type_def_file, "render_dev");
+        assert_eq!(vars[0].type_namespace, ["render"]);
+        assert_eq!(vars[1].type_def_file, "render_gl_gl");
+        assert_eq!(vars[1].type_namespace, ["render", "gl"]);
+
+        // Relative names are looked up in the enclosing namespaces
+        let vars = &defs[1].structs[0].variables;
+        assert_eq!(vars[0].type_def_file, "render_gl_gl");
+        assert_eq!(vars[1].type_def_file, "render_dev");
+
+        let var = &defs[2].structs[0].variables[0];
+        assert_eq!(var.type_def_file, "render_dev");
+        assert_eq!(var.type_namespace, ["render"]);
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let defs = parse(&[
+            (
+                "app.def",
+                "use gl::Texture
+import \"dev.def\"
+struct App { tex: Texture, device: Device, info: Info }",
+            ),
+            ("dev.def", "namespace render\nstruct Device { id: u32 }"),
+            ("gl.def", "namespace render::gl\nstruct Texture { id: u32 }"),
+            ("info.def", "namespace other\nstruct Info { id: u32 }"),
+        ]);
+
+        let vars = &defs[0].structs[0].variables;
+        assert_eq!(vars[0].type_def_file, "render_gl_gl");
+        assert_eq!(vars[0].type_namespace, ["render", "gl"]);
+        assert_eq!(vars[1].type_def_file, "render_dev");
+        assert_eq!(vars[1].type_namespace, ["render"]);
+
+        // Names in other namespaces that aren't imported can't be found
+        assert!(vars[2].type_def_file.is_empty());
+        assert_eq!(defs[0].imports[0].namespace, ["render", "gl"]);
+    }
+}
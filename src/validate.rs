@@ -0,0 +1,809 @@
+//! Semantic checks that are run on a set of parsed (and resolved) def files.
+use crate::api_parser::{ApiDef, ArrayType, Import, Span, Variable, VariableType};
+use crate::resolver::{qualified_name, Imports, TypeKind, TypeTable};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The definitions are invalid and generated code won't work
+    Error,
+    /// The definitions are valid but likely to cause problems
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found while validating
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// File the problem was found in
+    pub filename: String,
+    /// Line (1 based, 0 if unknown)
+    pub line: usize,
+    /// Column (1 based, 0 if unknown)
+    pub column: usize,
+    /// Description of the problem
+    pub message: String,
+    /// Extra information such as where a clashing name was first defined
+    pub note: Option<Note>,
+}
+
+/// Points to a location related to a [`Diagnostic`]
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub filename: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(api_def: &ApiDef, span: &Span, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            filename: api_def.filename.to_owned(),
+            line: span.line,
+            column: span.column,
+            message,
+            note: None,
+        }
+    }
+
+    fn with_note(mut self, api_def: &ApiDef, span: &Span, message: String) -> Diagnostic {
+        self.note = Some(Note {
+            filename: api_def.filename.to_owned(),
+            line: span.line,
+            column: span.column,
+            message,
+        });
+        self
+    }
+
+    fn warning(api_def: &ApiDef, span: &Span, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(api_def, span, message)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.filename, self.line, self.column, self.severity, self.message
+        )?;
+
+        if let Some(note) = &self.note {
+            write!(
+                f,
+                "\n{}:{}:{}: note: {}",
+                note.filename, note.line, note.column, note.message
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keywords in C (C11) that can't be used as names in generated code
+const C_KEYWORDS: &[&str] = &[
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_Bool",
+    "_Complex",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+];
+
+/// Keywords in Rust (including reserved ones) that can't be used as names in generated code
+pub(crate) const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Checks if the value is a valid number for an array size
+fn parse_size(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse::<u64>().ok(),
+    }
+}
+
+/// Validates the references between all the def files. This reports types that can't be found,
+/// `derives` that doesn't refer to a struct, `enum_flags` names that clashes with other types and
+/// sized arrays that doesn't have a valid size. Files that would be generated with the same name
+/// are errors and types from other files that aren't imported are reported as warnings. The results of `check_imports` and `check_duplicates` are included as
+/// well.
+pub fn validate(api_defs: &[ApiDef]) -> Vec<Diagnostic> {
+    let table = TypeTable::new(api_defs);
+    let consts: HashMap<&str, &str> = api_defs
+        .iter()
+        .flat_map(|api_def| api_def.consts.iter())
+        .map(|c| (c.name.as_str(), c.value.as_str()))
+        .collect();
+
+    let mut diagnostics = Vec::new();
+
+    for api_def in api_defs {
+        let imports = Imports::new(api_def, api_defs);
+
+        api_def.visit_variables(|var| {
+            check_type(api_def, &table, &imports, var, &mut diagnostics);
+            check_array_size(api_def, &consts, var, &mut diagnostics);
+        });
+
+        for s in &api_def.structs {
+            for derive in &s.derives {
+                match table
+                    .lookup_imported(&s.namespace, &imports, &[], derive)
+                    .map(|info| info.kind)
+                {
+                    Some(TypeKind::Struct | TypeKind::Handle) => (),
+                    Some(_) => diagnostics.push(Diagnostic::error(
+                        api_def,
+                        &s.span,
+                        format!(
+                            "`{}` derives from `{}` which isn't a struct",
+                            s.name, derive
+                        ),
+                    )),
+                    None => diagnostics.push(Diagnostic::error(
+                        api_def,
+                        &s.span,
+                        format!("`{}` derives from unknown struct `{}`", s.name, derive),
+                    )),
+                }
+            }
+        }
+    }
+
+    check_flags_names(api_defs, &mut diagnostics);
+    check_file_names(api_defs, &mut diagnostics);
+    diagnostics.extend(check_imports(api_defs));
+    diagnostics.extend(check_duplicates(api_defs));
+    diagnostics
+}
+
+/// Reports files with the same name in the same namespace. The generated files are named after
+/// `ApiDef::qualified_filename` so they would overwrite each other
+fn check_file_names(api_defs: &[ApiDef], out: &mut Vec<Diagnostic>) {
+    let mut names: HashMap<String, &ApiDef> = HashMap::new();
+
+    for api_def in api_defs {
+        let name = api_def.qualified_filename();
+
+        if let Some(first) = names.get(&name) {
+            let start = Span {
+                line: 1,
+                column: 1,
+                ..Span::default()
+            };
+
+            out.push(Diagnostic::error(
+                api_def,
+                &start,
+                format!(
+                    "`{}` has the same name and namespace as `{}` so the generated files would overwrite each other",
+                    api_def.filename, first.filename
+                ),
+            ));
+        } else {
+            names.insert(name, api_def);
+        }
+    }
+}
+
+/// Reports `enum_flags` names that clashes with a type or another `enum_flags` name in the same
+/// namespace. Clashes are reported once at the definition that comes last (in file order) with a
+/// note pointing to the first one. Clashes between two types are left to `check_duplicates`.
+fn check_flags_names(api_defs: &[ApiDef], out: &mut Vec<Diagnostic>) {
+    // (file index, name, span, is flags name) for all type names in definition order
+    let mut names = Vec::new();
+
+    for (index, api_def) in api_defs.iter().enumerate() {
+        let types = api_def
+            .structs
+            .iter()
+            .chain(api_def.unions.iter())
+            .map(|s| (s.name.as_str(), &s.span))
+            .chain(api_def.types.iter().map(|t| (t.var.name.as_str(), &t.span)))
+            .chain(api_def.callbacks.iter().map(|c| (c.name.as_str(), &c.span)))
+            .map(|(name, span)| (index, name, span, false));
+
+        let enums = api_def.enums.iter().flat_map(|e| {
+            let flags =
+                (!e.flags_name.is_empty()).then_some((index, e.flags_name.as_str(), &e.span, true));
+            std::iter::once((index, e.name.as_str(), &e.span, false)).chain(flags)
+        });
+
+        names.extend(types.chain(enums));
+    }
+
+    names.sort_by_key(|&(index, _, span, is_flags)| (index, span.byte_range.start, is_flags));
+
+    let mut first: HashMap<String, (usize, &Span, bool)> = HashMap::new();
+
+    for (index, name, span, is_flags) in names {
+        let api_def = &api_defs[index];
+        let key = qualified_name(&api_def.namespace, name);
+
+        let Some(&(first_index, first_span, first_is_flags)) = first.get(&key) else {
+            first.insert(key, (index, span, is_flags));
+            continue;
+        };
+
+        if !is_flags && !first_is_flags {
+            continue;
+        }
+
+        let first_def = &api_defs[first_index];
+        let what = if first_is_flags {
+            "an enum_flags name"
+        } else {
+            "a type"
+        };
+
+        let message = if is_flags {
+            format!("enum_flags name `{}` is already used by {}", name, what)
+        } else {
+            format!("type `{}` clashes with {}", name, what)
+        };
+
+        out.push(Diagnostic::error(api_def, span, message).with_note(
+            first_def,
+            first_span,
+            format!("`{}` is first defined here", name),
+        ));
+    }
+}
+
+/// Checks that imported files are loaded, that names imported with `use` are defined in the
+/// file and that files doesn't import each other in a cycle.
+pub fn check_imports(api_defs: &[ApiDef]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // Imported files by index for each file
+    let mut graph = vec![Vec::new(); api_defs.len()];
+
+    for (index, api_def) in api_defs.iter().enumerate() {
+        for import in &api_def.imports {
+            let Some(target) = api_def.find_import(import, api_defs) else {
+                diagnostics.push(Diagnostic::error(
+                    api_def,
+                    &import.span,
+                    format!("imported file `{}` not found", import.path),
+                ));
+                continue;
+            };
+
+            if let Some(name) = &import.name {
+                if !defines(&api_defs[target], name) {
+                    diagnostics.push(Diagnostic::error(
+                        api_def,
+                        &import.span,
+                        format!("`{}` isn't defined in `{}`", name, import.path),
+                    ));
+                }
+            }
+
+            // Several imports of the same file only need to report a cycle once
+            if !graph[index].iter().any(|(t, _)| *t == target) {
+                graph[index].push((target, import));
+            }
+        }
+    }
+
+    // Depth first search where an import of a file that is on the stack closes a cycle
+    let mut visited = vec![false; api_defs.len()];
+
+    for start in 0..api_defs.len() {
+        let mut stack = Vec::new();
+        find_cycles(
+            api_defs,
+            &graph,
+            start,
+            &mut stack,
+            &mut visited,
+            &mut diagnostics,
+        );
+    }
+
+    diagnostics
+}
+
+fn find_cycles(
+    api_defs: &[ApiDef],
+    graph: &[Vec<(usize, &Import)>],
+    index: usize,
+    stack: &mut Vec<usize>,
+    visited: &mut [bool],
+    out: &mut Vec<Diagnostic>,
+) {
+    if visited[index] {
+        return;
+    }
+
+    visited[index] = true;
+    stack.push(index);
+
+    for (target, import) in &graph[index] {
+        if let Some(pos) = stack.iter().position(|i| i == target) {
+            let cycle = stack[pos..]
+                .iter()
+                .chain(std::iter::once(target))
+                .map(|&i| api_defs[i].filename.as_str())
+                .collect::<Vec<_>>();
+
+            out.push(Diagnostic::error(
+                &api_defs[index],
+                &import.span,
+                format!("import cycle: {}", cycle.join(" -> ")),
+            ));
+        } else {
+            find_cycles(api_defs, graph, *target, stack, visited, out);
+        }
+    }
+
+    stack.pop();
+}
+
+/// Checks if a type or const with `name` is defined in `api_def`
+fn defines(api_def: &ApiDef, name: &str) -> bool {
+    api_def.structs.iter().any(|s| s.name == name)
+        || api_def.unions.iter().any(|u| u.name == name)
+        || api_def
+            .enums
+            .iter()
+            .any(|e| e.name == name || e.flags_name == name)
+        || api_def.types.iter().any(|t| t.var.name == name)
+        || api_def.callbacks.iter().any(|c| c.name == name)
+        || api_def.consts.iter().any(|c| c.name == name)
+}
+
+/// Checks for names that are defined more than once. This reports types with the same name
+/// (across all the def files in the same namespace), fields and functions with the same name inside a struct or union,
+/// enum entries with the same name and names that are keywords in C or Rust.
+pub fn check_duplicates(api_defs: &[ApiDef]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut types: HashMap<String, (&ApiDef, &Span)> = HashMap::new();
+
+    for api_def in api_defs {
+        let type_names = api_def
+            .structs
+            .iter()
+            .chain(api_def.unions.iter())
+            .map(|s| (s.name.as_str(), &s.span))
+            .chain(api_def.enums.iter().map(|e| (e.name.as_str(), &e.span)))
+            .chain(api_def.types.iter().map(|t| (t.var.name.as_str(), &t.span)))
+            .chain(api_def.callbacks.iter().map(|c| (c.name.as_str(), &c.span)));
+
+        for (name, span) in type_names {
+            check_keyword(api_def, span, name, "type", &mut diagnostics);
+
+            let name = qualified_name(&api_def.namespace, name);
+
+            if let Some((first_def, first_span)) = types.get(&name) {
+                diagnostics.push(Diagnostic::error(
+                    api_def,
+                    span,
+                    format!(
+                        "type `{}` is already defined at {}:{}",
+                        name, first_def.filename, first_span.line
+                    ),
+                ));
+            } else {
+                types.insert(name, (api_def, span));
+            }
+        }
+
+        for s in api_def.structs.iter().chain(api_def.unions.iter()) {
+            let mut names = HashSet::new();
+
+            let members = s
+                .variables
+                .iter()
+                .map(|v| (v.name.as_str(), &v.span, "field"))
+                .chain(
+                    s.functions
+                        .iter()
+                        .map(|f| (f.name.as_str(), &f.span, "function")),
+                );
+
+            for (name, span, kind) in members {
+                check_keyword(api_def, span, name, kind, &mut diagnostics);
+
+                if !names.insert(name) {
+                    diagnostics.push(Diagnostic::error(
+                        api_def,
+                        span,
+                        format!("`{}` is defined more than once in `{}`", name, s.name),
+                    ));
+                }
+            }
+        }
+
+        for e in &api_def.enums {
+            let mut names = HashSet::new();
+
+            for entry in &e.entries {
+                check_keyword(
+                    api_def,
+                    &entry.span,
+                    &entry.name,
+                    "enum entry",
+                    &mut diagnostics,
+                );
+
+                if !names.insert(entry.name.as_str()) {
+                    diagnostics.push(Diagnostic::error(
+                        api_def,
+                        &entry.span,
+                        format!(
+                            "entry `{}` is defined more than once in enum `{}`",
+                            entry.name, e.name
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let functions = api_def
+            .structs
+            .iter()
+            .chain(api_def.unions.iter())
+            .flat_map(|s| s.functions.iter())
+            .chain(api_def.callbacks.iter());
+
+        for func in functions {
+            let mut names = HashSet::new();
+
+            for arg in &func.function_args {
+                if arg.vtype == VariableType::SelfType {
+                    continue;
+                }
+
+                check_keyword(api_def, &arg.span, &arg.name, "argument", &mut diagnostics);
+
+                if !names.insert(arg.name.as_str()) {
+                    diagnostics.push(Diagnostic::error(
+                        api_def,
+                        &arg.span,
+                        format!(
+                            "argument `{}` is defined more than once in `{}`",
+                            arg.name, func.name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Warns if a name will collide with a keyword in the generated C or Rust code
+fn check_keyword(api_def: &ApiDef, span: &Span, name: &str, kind: &str, out: &mut Vec<Diagnostic>) {
+    let language = if C_KEYWORDS.contains(&name) {
+        "C"
+    } else if RUST_KEYWORDS.contains(&name) {
+        "Rust"
+    } else {
+        return;
+    };
+
+    out.push(Diagnostic::warning(
+        api_def,
+        span,
+        format!("{} name `{}` is a keyword in {}", kind, name, language),
+    ));
+}
+
+/// Reports variables that refers to types that doesn't exist and warns about types from other
+/// files that aren't imported
+fn check_type(
+    api_def: &ApiDef,
+    table: &TypeTable,
+    imports: &Imports,
+    var: &Variable,
+    out: &mut Vec<Diagnostic>,
+) {
+    match var.vtype {
+        VariableType::SelfType | VariableType::Str | VariableType::Primitive => return,
+        _ if var.type_name.is_empty() || var.type_name == "VA_ARGS" => return,
+        _ => (),
+    }
+
+    let type_name = qualified_name(&var.type_namespace, &var.type_name);
+
+    match table.lookup_imported(&var.namespace, imports, &var.type_namespace, &var.type_name) {
+        Some(info)
+            if info.def_file != api_def.base_filename
+                && !imports.contains(&info.def_file, &var.type_name) =>
+        {
+            out.push(Diagnostic::warning(
+                api_def,
+                &var.span,
+                format!(
+                    "`{}` is defined in `{}.def` which isn't imported",
+                    type_name, info.def_file
+                ),
+            ));
+            return;
+        }
+        Some(_) => return,
+        None => (),
+    }
+
+    let message = if var.name.is_empty() {
+        format!("unknown type `{}` in return value", type_name)
+    } else {
+        format!("unknown type `{}` for `{}`", type_name, var.name)
+    };
+
+    out.push(Diagnostic::error(api_def, &var.span, message));
+}
+
+/// Reports sized arrays where the size isn't a number or a const with a number
+fn check_array_size(
+    api_def: &ApiDef,
+    consts: &HashMap<&str, &str>,
+    var: &Variable,
+    out: &mut Vec<Diagnostic>,
+) {
+    let size = match var.array {
+        Some(ArrayType::SizedArray(ref size)) => size,
+        _ => return,
+    };
+
+    let value = consts.get(size.as_str()).copied().unwrap_or(size);
+
+    let message = match parse_size(value) {
+        Some(0) => format!("array `{}` has a size of zero", var.name),
+        Some(_) => return,
+        None if consts.contains_key(size.as_str()) => format!(
+            "array size const `{}` for `{}` isn't a number",
+            size, var.name
+        ),
+        None => format!("unknown array size `{}` for `{}`", size, var.name),
+    };
+
+    out.push(Diagnostic::error(api_def, &var.span, message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_parser::ApiParser;
+    use crate::resolver::resolve;
+
+    fn check(code: &str) -> Vec<Diagnostic> {
+        let mut defs = vec![ApiParser::parse_string(code, "test.def").unwrap()];
+        resolve(&mut defs);
+        validate(&defs)
+    }
+
+    #[test]
+    fn test_valid() {
+        let diags = check(
+            "const SIZE = 16\nstruct Info { name: [u8; SIZE], data: [u8; 0x10] }\nstruct Image : Info {\n    get_info(image: Image) -> *const Info?,\n}",
+        );
+        assert!(diags.is_empty(), "{:?}", diags);
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let diags = check("struct Image {\n    [static] get_info(image: Image) -> *const ImageInfo?,\n    foo: Bar,\n}");
+        assert_eq!(diags.len(), 2);
+        assert!(diags[0].message.contains("Bar"));
+        assert_eq!(diags[0].line, 3);
+        assert!(diags[1].message.contains("ImageInfo"));
+        assert_eq!(diags[1].line, 2);
+    }
+
+    #[test]
+    fn test_unknown_derive_and_flags() {
+        let diags = check(
+            "enum Mode { A }\nstruct Foo : Missing, Mode { }\n[enum_flags(Mode)]\nenum Flag { A = 1, B = 2 }",
+        );
+        assert_eq!(diags.len(), 3);
+        assert!(diags[0].message.contains("unknown struct `Missing`"));
+        assert!(diags[1].message.contains("isn't a struct"));
+        assert!(diags[2].message.contains("enum_flags"));
+        assert_eq!(diags[2].line, 4);
+    }
+
+    #[test]
+    fn test_flags_name_clash_reported_once() {
+        let mut defs = vec![
+            ApiParser::parse_string("[enum_flags(Flags)]\nenum A { X = 1 }", "a.def").unwrap(),
+            ApiParser::parse_string("\n[enum_flags(Flags)]\nenum B { Y = 1 }", "b.def").unwrap(),
+        ];
+        resolve(&mut defs);
+        let diags = validate(&defs);
+
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(
+            diags[0].message,
+            "enum_flags name `Flags` is already used by an enum_flags name"
+        );
+        assert_eq!(diags[0].filename, "b.def");
+        assert_eq!(diags[0].line, 3);
+
+        let note = diags[0].note.as_ref().unwrap();
+        assert_eq!((note.filename.as_str(), note.line), ("a.def", 2));
+        assert_eq!(
+            diags[0].to_string(),
+            "b.def:3:6: error: enum_flags name `Flags` is already used by an enum_flags name\na.def:2:6: note: `Flags` is first defined here"
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut defs = vec![
+            ApiParser::parse_string(
+                "struct Image { a: u32, a: u32, a() }\nenum Mode { A, B, A }",
+                "a.def",
+            )
+            .unwrap(),
+            ApiParser::parse_string("\n\nstruct Image { }\ncallback Cb(x: u32, x: u32)", "b.def")
+                .unwrap(),
+        ];
+        resolve(&mut defs);
+        let diags = check_duplicates(&defs);
+
+        assert_eq!(diags.len(), 5);
+        assert!(diags.iter().all(|d| d.severity == Severity::Error));
+        assert_eq!(diags[0].message, "`a` is defined more than once in `Image`");
+        assert_eq!(diags[1].message, "`a` is defined more than once in `Image`");
+        assert!(diags[2].message.contains("entry `A`"));
+        assert_eq!(
+            diags[3].message,
+            "type `Image` is already defined at a.def:1"
+        );
+        assert_eq!(diags[3].filename, "b.def");
+        assert_eq!(diags[3].line, 3);
+        assert!(diags[4].message.contains("argument `x`"));
+    }
+
+    #[test]
+    fn test_keywords() {
+        let diags = check("struct Foo {\n    type: u32,\n    int: u32,\n    match(),\n}");
+        assert_eq!(diags.len(), 3);
+        assert!(diags.iter().all(|d| d.severity == Severity::Warning));
+        assert_eq!(diags[0].message, "field name `type` is a keyword in Rust");
+        assert_eq!(diags[1].message, "field name `int` is a keyword in C");
+        assert_eq!(
+            diags[2].message,
+            "function name `match` is a keyword in Rust"
+        );
+    }
+
+    #[test]
+    fn test_bad_array_size() {
+        let diags = check("const NAME = \"foo\"\nstruct Foo {\n    a: [u8; COUNT],\n    b: [u8; NAME],\n    c: [u8; 0],\n}");
+        assert_eq!(diags.len(), 3);
+        assert!(diags[0].message.contains("unknown array size"));
+        assert!(diags[1].message.contains("isn't a number"));
+        assert!(diags[2].message.contains("zero"));
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut defs = vec![
+            ApiParser::parse_string(
+                "import \"b.def\"\nuse b::Missing\nuse other::Foo\nstruct A { b: B }",
+                "defs/a.def",
+            )
+            .unwrap(),
+            ApiParser::parse_string("use a::A\nstruct B { a: *A }", "defs/b.def").unwrap(),
+        ];
+        resolve(&mut defs);
+        let diags = check_imports(&defs);
+
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[0].message, "`Missing` isn't defined in `b.def`");
+        assert_eq!(diags[0].line, 2);
+        assert_eq!(diags[1].message, "imported file `other.def` not found");
+        assert_eq!(
+            diags[2].message,
+            "import cycle: defs/a.def -> defs/b.def -> defs/a.def"
+        );
+        assert_eq!(diags[2].line, 1);
+    }
+
+    #[test]
+    fn test_not_imported() {
+        let mut defs = vec![
+            ApiParser::parse_string(
+                "use gl::Texture\nstruct App {\n    tex: Texture,\n    info: Info,\n}",
+                "app.def",
+            )
+            .unwrap(),
+            ApiParser::parse_string("namespace render::gl\nstruct Texture { }", "gl.def").unwrap(),
+            ApiParser::parse_string("struct Info { }", "info.def").unwrap(),
+        ];
+        resolve(&mut defs);
+        let diags = validate(&defs);
+
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].severity, Severity::Warning);
+        assert_eq!(
+            diags[0].message,
+            "`Info` is defined in `info.def` which isn't imported"
+        );
+        assert_eq!(diags[0].line, 4);
+    }
+
+    #[test]
+    fn test_same_file_names() {
+        let mut defs = vec![
+            ApiParser::parse_string("namespace gl\nstruct Texture { }", "render/texture.def")
+                .unwrap(),
+            ApiParser::parse_string("struct Widget { }", "ui/texture.def").unwrap(),
+            ApiParser::parse_string("namespace gl\nstruct Image { }", "gl/texture.def").unwrap(),
+        ];
+        resolve(&mut defs);
+        let diags = validate(&defs);
+
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].filename, "gl/texture.def");
+        assert_eq!(
+            diags[0].message,
+            "`gl/texture.def` has the same name and namespace as `render/texture.def` so the generated files would overwrite each other"
+        );
+    }
+}
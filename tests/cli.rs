@@ -0,0 +1,186 @@
+//! Tests for the `apigen` binary
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const IMAGE_DEF: &str = "\
+struct ImageInfo {
+    width: u32,
+    height: u32,
+}
+
+#[attributes(Handle, Drop)]
+struct Image {
+    [static] create_from_file(filename: String) -> Image?,
+    get_info() -> ImageInfo,
+    destroy(),
+}
+";
+
+/// Creates an empty directory for a test with the def files in `files`
+fn setup(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for (filename, data) in files {
+        std::fs::write(dir.join(filename), data).unwrap();
+    }
+
+    dir
+}
+
+fn apigen(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_apigen"))
+        .args(args)
+        .arg(dir)
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_check() {
+    let dir = setup("check", &[("image.def", IMAGE_DEF)]);
+    let output = apigen(&["check"], &dir);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("checked 1 file(s): 0 error(s), 0 warning(s)"));
+
+    let dir = setup(
+        "check_errors",
+        &[("bad.def", "struct Foo { a: Missing }\nstruct {")],
+    );
+    let output = apigen(&["check"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("unknown type `Missing`"));
+    assert!(stderr(&output).contains("2 error(s)"));
+}
+
+#[test]
+fn test_bad_arguments() {
+    let dir = setup("bad_arguments", &[("image.def", IMAGE_DEF)]);
+
+    let output = apigen(&["frobnicate"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("unknown command `frobnicate`"));
+
+    let output = apigen(&["gen", "c"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("missing -o <out>"));
+
+    let output = apigen(&["dump", "--format", "yaml"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("unknown format `yaml`"));
+}
+
+#[test]
+fn test_missing_files() {
+    let dir = setup("missing_files", &[("readme.txt", "not a def file")]);
+
+    for command in ["check", "dump"] {
+        let output = apigen(&[command], &dir.join("missing"));
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("missing` doesn't exist"));
+
+        let output = apigen(&[command], &dir);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("no .def files in"));
+    }
+
+    let out = dir.join("out");
+    let output = apigen(&["gen", "c", "-o", out.to_str().unwrap()], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("no .def files in"));
+    assert!(!out.exists());
+}
+
+#[test]
+fn test_gen() {
+    let dir = setup("gen", &[("image.def", IMAGE_DEF)]);
+    let out = dir.join("out");
+    let out_arg = out.to_str().unwrap();
+
+    let output = apigen(&["gen", "c", "-p", "FL_", "-o", out_arg], &dir);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let header = std::fs::read_to_string(out.join("image.h")).unwrap();
+    assert!(header.contains("FL_Image_create_from_file"));
+
+    // The generated header is in the input directory now and must not be parsed
+    let output = apigen(&["gen", "c", "-p", "FL_", "-o", out_arg], &dir);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+
+    let output = apigen(&["gen", "cobol", "-o", out_arg], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("unknown backend `cobol`"));
+}
+
+#[test]
+fn test_gen_and_dump_validate() {
+    let dir = setup(
+        "gen_validate",
+        &[("dup.def", "struct Foo {\n    a: u32,\n    a: u32,\n}\n")],
+    );
+    let out = dir.join("out");
+
+    let output = apigen(&["gen", "c", "-o", out.to_str().unwrap()], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("`a` is defined more than once in `Foo`"));
+    assert!(!out.join("dup.h").exists());
+
+    let output = apigen(&["dump"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_dump() {
+    let dir = setup("dump", &[("image.def", IMAGE_DEF)]);
+
+    let output = apigen(&["dump"], &dir);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("create_from_file"));
+
+    let output = apigen(&["dump", "--format", "json"], &dir);
+
+    if cfg!(feature = "ir") {
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(json["files"][0]["structs"][0]["name"], "ImageInfo");
+    } else {
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("`ir` feature"));
+    }
+}
+
+#[test]
+fn test_fmt() {
+    let dir = setup(
+        "fmt",
+        &[
+            ("a.def", "struct Foo{a:u32}"),
+            ("notes.txt", "not a def file"),
+        ],
+    );
+
+    let output = apigen(&["fmt", "--check"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("a.def: not formatted"));
+
+    let output = apigen(&["fmt"], &dir);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("a.def")).unwrap(),
+        "struct Foo {\n    a: u32,\n}\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("notes.txt")).unwrap(),
+        "not a def file"
+    );
+
+    let output = apigen(&["fmt", "--check"], &dir);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+}